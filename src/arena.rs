@@ -0,0 +1,108 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::bot::BotSpec;
+use crate::level::Level;
+use crate::replay::Replay;
+use crate::simulation::{Rules, Simulation};
+
+// running seeded games headless, with a bot making the turns
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GameResult {
+    pub bot: String,
+    pub seed: u64,
+    pub score: u32,
+    pub length: usize,
+    pub ticks: u64,
+    pub death: String,
+    #[serde(skip)]
+    pub replay: Replay,
+}
+
+impl GameResult {
+    pub const CSV_HEADER: &'static str = "bot,seed,score,length,ticks,death";
+
+    pub fn to_csv(&self) -> String {
+        format!("{},{},{},{},{},{}",
+                csv_field(&self.bot), self.seed, self.score, self.length, self.ticks, self.death)
+    }
+
+    // a name for the replay file that is safe to use on any file system
+    pub fn replay_file_name(&self) -> String {
+        let bot: String = self.bot.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect();
+        format!("{}-{}.snkr", bot, self.seed)
+    }
+}
+
+// the level is chosen like in the game, random gives every seed a level of its own
+pub fn run_game(spec: &BotSpec, seed: u64, rules: &Rules, level: &str, max_ticks: u64) -> std::io::Result<GameResult> {
+    run_game_with(spec, seed, rules, level, max_ticks, |_| {})
+}
+
+// a game that lets the caller see it after every tick, to stream it
+pub fn run_game_with(spec: &BotSpec, seed: u64, rules: &Rules, level: &str, max_ticks: u64,
+                     mut on_tick: impl FnMut(&Simulation)) -> std::io::Result<GameResult> {
+    let mut bot = spec.create()?;
+    let level = Level::choose(level, seed).unwrap_or_default();
+    let mut simulation = Simulation::with_level(seed, rules.clone(), level, 1);
+    while !simulation.is_over() && simulation.tick() < max_ticks {
+        if let Some(direction) = bot.decide(&simulation, 0) {
+            simulation.turn(0, direction);
+        }
+        simulation.step();
+        on_tick(&simulation);
+    }
+
+    let snake = simulation.snake(0);
+    Ok(GameResult {
+        bot: spec.name(),
+        seed,
+        score: snake.score,
+        length: snake.len(),
+        ticks: simulation.tick(),
+        death: snake.death.map_or("timeout", |cause| cause.name()).to_string(),
+        replay: Replay::record(&simulation),
+    })
+}
+
+// every bot plays every seed, the games are shared between the threads
+pub fn run_tournament(bots: &[BotSpec], seeds: &[u64], rules: &Rules, level: &str,
+                      max_ticks: u64, threads: usize) -> std::io::Result<Vec<GameResult>> {
+    let games: Vec<(&BotSpec, u64)> = bots.iter()
+        .flat_map(|bot| seeds.iter().map(move |seed| (bot, *seed)))
+        .collect();
+    let next_game = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<std::io::Result<GameResult>>>> =
+        Mutex::new(games.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next_game.fetch_add(1, Ordering::Relaxed);
+                let Some((bot, seed)) = games.get(index) else {
+                    break;
+                };
+                let result = run_game(bot, *seed, rules, level, max_ticks);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter()
+        .map(|result| result.expect("every game is run"))
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,213 @@
+// Snake - headless tournament runner
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use snake::arena::{GameResult, run_game_with, run_tournament};
+use snake::bot::BotSpec;
+use snake::connection::Connection;
+use snake::level::{Level, LEVEL_CHOICES};
+use snake::net::{ClientMessage, RoomSettings, ServerMessage, MAX_SPECTATORS};
+use snake::simulation::{Rules, Simulation, TICK_MILLIS};
+
+const USAGE: &str = "\
+Usage: snake-arena [options]
+
+Runs seeded headless games and prints one result row per game.
+
+Options:
+  --bot <spec>          bot to play: greedy or external:<command> (repeatable, default greedy)
+  --games <n>           games per bot, seeds counting up from --seed (default 100)
+  --seed <n>            first seed (default 0)
+  --max-ticks <n>       end a game after this many ticks (default 10000)
+  --level <name>        level to play: open, pillars, bars, portals, patrols, random
+                        for a generated one for every seed, or a generated one like
+                        maze-42 (default open)
+  --threads <n>         games run in parallel (default: number of cpus)
+  --format <csv|json>   output format (default csv)
+  --food-ticks <n>      ticks between food spawns, 0 for none
+  --bomb-ticks <n>      ticks between bomb spawns, 0 for none
+  --fuse-ticks <n>      ticks before a bomb explodes
+  --replays <dir>       save a replay of every game to this directory
+  --stream <url>        play the games one at a time at the normal speed and stream
+                        them to a snake-server room for spectators to watch
+  --help                show this help";
+
+// the spectators see the end of a streamed game before the next one starts
+const STREAM_BREAK_MILLIS: u64 = 3000;
+
+struct Options {
+    bots: Vec<BotSpec>,
+    games: u64,
+    seed: u64,
+    max_ticks: u64,
+    level: String,
+    threads: usize,
+    json: bool,
+    rules: Rules,
+    replays: Option<PathBuf>,
+    stream: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        bots: Vec::new(),
+        games: 100,
+        seed: 0,
+        max_ticks: 10000,
+        level: Level::default().name,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        json: false,
+        rules: Rules::default(),
+        replays: None,
+        stream: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("{} needs a value", arg))?;
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_str() {
+            "--bot" => options.bots.push(BotSpec::parse(&value)?),
+            "--games" => options.games = number()?,
+            "--seed" => options.seed = number()?,
+            "--max-ticks" => options.max_ticks = number()?,
+            "--level" => {
+                if Level::choose(&value, 0).is_none() {
+                    return Err(format!("unknown level {}, the levels are {}, or a generated one like maze-42",
+                                       value, LEVEL_CHOICES.join(", ")));
+                }
+                options.level = value;
+            }
+            "--threads" => options.threads = number()? as usize,
+            "--format" => options.json = match value.as_str() {
+                "csv" => false,
+                "json" => true,
+                _ => return Err(format!("unknown format: {}", value)),
+            },
+            "--food-ticks" => options.rules.food_spawn_ticks = number()?,
+            "--bomb-ticks" => options.rules.bomb_spawn_ticks = number()?,
+            "--fuse-ticks" => {
+                options.rules.bomb_fuse_ticks = number()?.try_into().map_err(|_| format!("{} is too long", value))?;
+                if options.rules.bomb_fuse_ticks == 0 {
+                    return Err("a bomb fuse is at least 1 tick".to_string());
+                }
+            }
+            "--replays" => options.replays = Some(PathBuf::from(value)),
+            "--stream" => options.stream = Some(value),
+            _ => return Err(format!("unknown option: {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if options.bots.is_empty() {
+        options.bots.push(BotSpec::Greedy);
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    if std::env::args().skip(1).any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let seeds: Vec<u64> = (0..options.games).map(|n| options.seed + n).collect();
+    let results = match &options.stream {
+        Some(url) => stream_tournament(&options, &seeds, url),
+        None => run_tournament(&options.bots, &seeds, &options.rules, &options.level, options.max_ticks, options.threads)
+            .map_err(|error| format!("Failed to run a bot: {}", error)),
+    };
+    let results = match results {
+        Ok(results) => results,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(directory) = &options.replays {
+        if let Err(error) = save_replays(directory, &results) {
+            eprintln!("Failed to save the replays: {}", error);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&results).expect("results serialize"));
+    }
+    else {
+        println!("{}", GameResult::CSV_HEADER);
+        for result in results {
+            println!("{}", result.to_csv());
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// every game is played in real time, so it is streamed as it is played
+fn stream_tournament(options: &Options, seeds: &[u64], url: &str) -> Result<Vec<GameResult>, String> {
+    let mut connection = Connection::open(url);
+    let settings = RoomSettings {
+        level: options.level.clone(),
+        rules: options.rules.clone(),
+        spectators: MAX_SPECTATORS,
+        ..RoomSettings::default()
+    };
+    let mut room = None;
+    let mut results = Vec::new();
+    for bot in &options.bots {
+        for seed in seeds {
+            connection.send(&ClientMessage::Stream {
+                name: "snake-arena".to_string(),
+                settings: settings.clone(),
+                seed: *seed,
+                names: vec![bot.name()],
+            });
+            let mut failed = None;
+            let result = run_game_with(bot, *seed, &options.rules, &options.level, options.max_ticks, |simulation: &Simulation| {
+                let tick = simulation.tick() - 1;
+                let turns = simulation.turns().iter()
+                    .filter(|(turn_tick, _, _)| *turn_tick == tick)
+                    .map(|(_, player, direction)| (*player, *direction))
+                    .collect();
+                connection.send(&ClientMessage::StreamTick { tick, turns });
+                std::thread::sleep(Duration::from_millis(TICK_MILLIS));
+                while let Some(message) = connection.receive() {
+                    match message {
+                        Ok(ServerMessage::Room { code, .. }) if room.as_ref() != Some(&code) => {
+                            eprintln!("Streaming to room {}", code);
+                            room = Some(code);
+                        }
+                        Ok(ServerMessage::Error { message }) => failed = Some(format!("Server error: {}", message)),
+                        Ok(_) => {}
+                        Err(message) => failed = Some(message),
+                    }
+                }
+            }).map_err(|error| format!("Failed to run a bot: {}", error))?;
+            if let Some(message) = failed {
+                return Err(message);
+            }
+            if result.death == "timeout" {
+                connection.send(&ClientMessage::StreamEnd);
+            }
+            eprintln!("{}", result.to_csv());
+            results.push(result);
+            std::thread::sleep(Duration::from_millis(STREAM_BREAK_MILLIS));
+        }
+    }
+    Ok(results)
+}
+
+fn save_replays(directory: &Path, results: &[GameResult]) -> std::io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    for result in results {
+        std::fs::write(directory.join(result.replay_file_name()), result.replay.encode())?;
+    }
+    Ok(())
+}
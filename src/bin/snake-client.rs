@@ -0,0 +1,188 @@
+// Snake - headless online client
+
+use std::process::ExitCode;
+use std::time::Duration;
+
+use snake::bot::BotSpec;
+use snake::connection::{Connection, default_server_url};
+use snake::level::Level;
+use snake::net::{ClientMessage, NetGame, RoomSettings, ServerMessage};
+
+const USAGE: &str = "\
+Usage: snake-client [options]
+
+Joins an online game with a bot playing, without any graphics,
+and prints the result when the game is over. Without --room or --create
+it joins any room with a free seat. The bot is always ready to play.
+
+Options:
+  --server <url>   server to join (default ws://127.0.0.1:9000, or SNAKE_SERVER)
+  --name <name>    name shown to the other players (default: the bot)
+  --bot <spec>     bot to play: greedy or external:<command> (default greedy)
+  --room <code>    join the room with this code
+  --spectate       watch the game in the room instead of playing
+  --create         create a new room and print its code
+  --level <name>   level of the new room (default open)
+  --players <n>    players in the new room (default 2)
+  --help           show this help";
+
+// how often the connection is checked for new messages
+const POLL_MILLIS: u64 = 2;
+
+struct Options {
+    server: String,
+    name: Option<String>,
+    bot: BotSpec,
+    room: Option<String>,
+    spectate: bool,
+    create: bool,
+    // of the room to create
+    settings: RoomSettings,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        server: default_server_url(),
+        name: None,
+        bot: BotSpec::Greedy,
+        room: None,
+        spectate: false,
+        create: false,
+        settings: RoomSettings::default(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => return Err(USAGE.to_string()),
+            "--spectate" => {
+                options.spectate = true;
+                continue;
+            }
+            "--create" => {
+                options.create = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = args.next().ok_or(format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--server" => options.server = value,
+            "--name" => options.name = Some(value),
+            "--bot" => options.bot = BotSpec::parse(&value)?,
+            "--room" => options.room = Some(value),
+            "--level" => options.settings.level = value,
+            "--players" => options.settings.players = value.parse().map_err(|_| format!("--players needs a number, got {}", value))?,
+            _ => return Err(format!("unknown option: {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if options.spectate && options.room.is_none() {
+        return Err("--spectate needs a --room to watch".to_string());
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+    match play(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn play(options: &Options) -> Result<(), String> {
+    let mut bot = options.bot.create().map_err(|error| format!("Failed to run the bot: {}", error))?;
+    let mut connection = Connection::open(&options.server);
+    let name = options.name.clone().unwrap_or_else(|| options.bot.name());
+    connection.send(&match &options.room {
+        _ if options.create => ClientMessage::CreateRoom { name, settings: options.settings.clone() },
+        Some(code) => ClientMessage::JoinRoom { code: code.clone(), name, spectate: options.spectate },
+        None => ClientMessage::Join { name },
+    });
+
+    let mut room = String::new();
+    let mut game: Option<NetGame> = None;
+    loop {
+        let Some(message) = connection.receive() else {
+            std::thread::sleep(Duration::from_millis(POLL_MILLIS));
+            continue;
+        };
+        match message? {
+            ServerMessage::Rooms { .. } => {}
+            ServerMessage::Room { code, settings, seats, you, .. } => {
+                if code != room {
+                    eprintln!("In room {}, level {}", code, settings.level);
+                    room = code;
+                }
+                if game.is_none() {
+                    eprintln!("Waiting for players, {}/{}", seats.len(), settings.players);
+                }
+                if let Some(seat) = you.and_then(|you| seats.get(you)).filter(|seat| !seat.ready) {
+                    eprintln!("{} is ready", seat.name);
+                    connection.send(&ClientMessage::Ready { ready: true });
+                }
+            }
+            ServerMessage::Start { seed, rules, level, names, you } => {
+                match you {
+                    Some(you) => eprintln!("Playing as player {} with seed {} on {}: {}", you + 1, seed, level, names.join(", ")),
+                    None => eprintln!("Watching with seed {} on {}: {}", seed, level, names.join(", ")),
+                }
+                let level = Level::named(&level).ok_or(format!("unknown level {}", level))?;
+                game = Some(NetGame::new(seed, rules, level, names, you));
+            }
+            ServerMessage::Catchup { tick, turns } => {
+                let game = game.as_mut().ok_or("catch up before the game started")?;
+                eprintln!("Catching up to tick {}", tick);
+                game.catch_up(tick, &turns)?;
+            }
+            ServerMessage::Tick { tick, turns } => {
+                let game = game.as_mut().ok_or("tick before the game started")?;
+                game.confirm(tick, &turns)?;
+                let confirmed_tick = game.confirmed().tick();
+                game.predict(confirmed_tick);
+                // the turn gets to the server before its next tick
+                let simulation = game.confirmed();
+                let Some(player) = game.player().filter(|player| simulation.snake(*player).is_alive()) else {
+                    continue;
+                };
+                if simulation.is_over() {
+                    continue;
+                }
+                if let Some(direction) = bot.decide(simulation, player) {
+                    if let Some(message) = game.turn(direction) {
+                        connection.send(&message);
+                    }
+                }
+            }
+            ServerMessage::End { winner } => {
+                let game = game.as_ref().ok_or("the game ended before it started")?;
+                print_result(game, winner);
+                return Ok(());
+            }
+            ServerMessage::Error { message } => return Err(format!("Server error: {}", message)),
+        }
+    }
+}
+
+fn print_result(game: &NetGame, winner: Option<usize>) {
+    let simulation = game.confirmed();
+    for (player, snake) in simulation.snakes().iter().enumerate() {
+        println!("{}{}: score {}, length {}, death: {}",
+                 game.names()[player],
+                 if Some(player) == game.player() { " (you)" } else { "" },
+                 snake.score, snake.len(),
+                 snake.death.map_or("none", |cause| cause.name()));
+    }
+    match winner {
+        Some(player) => println!("{} wins after {} ticks", game.names()[player], simulation.tick()),
+        None => println!("Draw after {} ticks", simulation.tick()),
+    }
+}
@@ -0,0 +1,35 @@
+// Snake - level file checker
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use snake::checks::FileCheck;
+use snake::level::Level;
+
+const USAGE: &str = "\
+Usage: snake-levels <level file or directory>...
+
+Reads level files, the ones the level editor saves, and checks that they can
+be played: the map fits on the grid, the snake does not start on or facing a
+wall, every place that is not a wall can be reached from the start, going
+through the portals too, every portal has two ends, no conveyor pushes the
+snake into a wall, the enemies do not start next to the snake or walk onto
+walls, and the food and bomb spawns are on places the snake can reach.
+Directories are searched for .json files recursively.
+
+Exits with a non-zero status if any level can not be read or has a problem.";
+
+fn check(path: &Path) -> Result<Vec<String>, String> {
+    let data = std::fs::read(path).map_err(|error| error.to_string())?;
+    let level = Level::from_file(&data)?;
+    let mut problems = level.problems();
+    // the editor finds a level by its name
+    if path.file_stem().is_some_and(|stem| *stem != *level.name) {
+        problems.insert(0, format!("the level is called {}, so its file should be {}.json", level.name, level.name));
+    }
+    Ok(problems)
+}
+
+fn main() -> ExitCode {
+    FileCheck { usage: USAGE, extension: "json", noun: "levels", check }.run(std::env::args().skip(1))
+}
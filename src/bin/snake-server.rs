@@ -0,0 +1,87 @@
+// Snake - online game server
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::ExitCode;
+
+#[cfg(not(target_arch = "wasm32"))]
+use snake::server::{run, ServerConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use snake::net::RoomSettings;
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+Usage: snake-server [options]
+
+Runs online games: players join rooms over WebSocket, and the game
+in a room starts when all of its players are ready. The options
+are the settings of the rooms made by quick match.
+
+Options:
+  --address <host:port>  address to listen on (default 0.0.0.0:9000)
+  --level <name>         level to play: open, pillars, bars, portals, patrols,
+                         random or a generated one like maze-42 (default open)
+  --players <n>          players in a game (default 2)
+  --spectators <n>       spectators that can watch a game (default 8)
+  --seed <n>             play every game with this seed (default random)
+  --food-ticks <n>       ticks between food spawns
+  --bomb-ticks <n>       ticks between bomb spawns
+  --fuse-ticks <n>       ticks before a bomb explodes
+  --help                 show this help";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<ServerConfig, String> {
+    let mut config = ServerConfig {
+        address: format!("0.0.0.0:{}", snake::net::DEFAULT_PORT),
+        settings: RoomSettings::default(),
+        seed: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("{} needs a value", arg))?;
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_str() {
+            "--address" => config.address = value.clone(),
+            "--level" => config.settings.level = value.clone(),
+            "--players" => config.settings.players = number()? as usize,
+            "--spectators" => config.settings.spectators = number()? as usize,
+            "--seed" => config.seed = Some(number()?),
+            "--food-ticks" => config.settings.rules.food_spawn_ticks = number()?,
+            "--bomb-ticks" => config.settings.rules.bomb_spawn_ticks = number()?,
+            "--fuse-ticks" => config.settings.rules.bomb_fuse_ticks = number()?.try_into().map_err(|_| format!("{} is too long", value))?,
+            _ => return Err(format!("unknown option: {}\n\n{}", arg, USAGE)),
+        }
+    }
+    snake::server::validate(&config.settings)?;
+    Ok(config)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> ExitCode {
+    if std::env::args().skip(1).any(|arg| arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    match runtime.block_on(run(config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Server failed: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// the server needs sockets, it is not built for the browser
+#[cfg(target_arch = "wasm32")]
+fn main() {}
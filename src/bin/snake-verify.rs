@@ -0,0 +1,26 @@
+// Snake - replay verification
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use snake::checks::FileCheck;
+use snake::replay::Replay;
+
+const USAGE: &str = "\
+Usage: snake-verify <replay or directory>...
+
+Plays the replays again without showing them, and checks that every game
+ends with the score, length, tick count and death cause the replay recorded.
+Directories are searched for .snkr files recursively.
+
+Exits with a non-zero status if any replay can not be read or does not match.";
+
+fn verify(path: &Path) -> Result<Vec<String>, String> {
+    let data = std::fs::read(path).map_err(|error| error.to_string())?;
+    let replay = Replay::decode(&data).map_err(|error| error.to_string())?;
+    replay.verify().map(|()| Vec::new())
+}
+
+fn main() -> ExitCode {
+    FileCheck { usage: USAGE, extension: "snkr", noun: "replays", check: verify }.run(std::env::args().skip(1))
+}
@@ -2,17 +2,14 @@ use bevy::prelude::*;
 
 use crate::common::ScreenPosition;
 
-#[derive(Component, Default)]
+#[derive(Component)]
 pub(crate) struct Bomb {
-    pub timer: Timer,
+    pub id: u32,
 }
 
 impl Bomb {
-    pub fn default() -> Bomb {
-        let seconds = 5.0;
-        Bomb {
-            timer: Timer::from_seconds(seconds, TimerMode::Once),
-        }
+    pub fn new(id: u32) -> Bomb {
+        Bomb { id }
     }
 }
 
@@ -67,7 +64,7 @@ impl ParticleSystem {
             Vec2::new(-distance, distance),
             Vec2::new(-distance, -distance),
         ];
-        let mut p = position.clone();
+        let mut p = position;
         p.z = 400.0; //TODO: define this somewhere
         let size = 40.0;
         for vel in velocities {
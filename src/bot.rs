@@ -0,0 +1,242 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::common::{Direction, GridPosition};
+use crate::level::Terrain;
+use crate::simulation::{Simulation, is_in_blast};
+
+// a bomb this close to exploding is given a wide berth
+const BOMB_DANGER_TICKS: u32 = 3;
+
+// an external bot answering slower than this is given up on
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub trait Bot {
+    fn decide(&mut self, simulation: &Simulation, player: usize) -> Option<Direction>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BotSpec {
+    Greedy,
+    External(String),
+}
+
+impl BotSpec {
+    // "greedy" or "external:<command line>"
+    pub fn parse(spec: &str) -> Result<BotSpec, String> {
+        if spec == "greedy" {
+            Ok(BotSpec::Greedy)
+        }
+        else if let Some(command) = spec.strip_prefix("external:") {
+            if command.trim().is_empty() {
+                Err("external bot needs a command".to_string())
+            }
+            else {
+                Ok(BotSpec::External(command.to_string()))
+            }
+        }
+        else {
+            Err(format!("unknown bot: {}", spec))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            BotSpec::Greedy => "greedy".to_string(),
+            BotSpec::External(command) => command.clone(),
+        }
+    }
+
+    pub fn create(&self) -> std::io::Result<Box<dyn Bot>> {
+        match self {
+            BotSpec::Greedy => Ok(Box::new(GreedyBot)),
+            BotSpec::External(command) => Ok(Box::new(ExternalBot::spawn(command)?)),
+        }
+    }
+}
+
+// heads for the closest food along the shortest free path,
+// or just tries to stay alive when there is no food to reach
+pub struct GreedyBot;
+
+impl Bot for GreedyBot {
+    fn decide(&mut self, simulation: &Simulation, player: usize) -> Option<Direction> {
+        let snake = simulation.snake(player);
+        let head = snake.head();
+        // the places next to an enemy are as bad as the enemy, it may step onto them
+        let blocked = |position: GridPosition| {
+            simulation.is_blocked(position) ||
+                simulation.bombs().iter().any(|bomb| {
+                    bomb.fuse <= BOMB_DANGER_TICKS && is_in_blast(position, bomb.position)
+                }) ||
+                simulation.enemies().iter().any(|enemy| {
+                    enemy.position.x.abs_diff(position.x) + enemy.position.y.abs_diff(position.y) <= 1
+                })
+        };
+        // there is no turning on ice
+        let can_turn = simulation.level().terrain_at(head).is_none_or(Terrain::lets_turn);
+        let moves: Vec<(Direction, GridPosition)> = Direction::ALL.into_iter()
+            .filter(|direction| *direction != snake.direction.opposite())
+            .filter(|direction| can_turn || *direction == snake.direction)
+            .map(|direction| (direction, simulation.level().step(head, direction)))
+            .filter(|(_, position)| !blocked(*position))
+            .collect();
+
+        // breadth first search from each possible first step
+        let mut visited: HashSet<GridPosition> = moves.iter().map(|(_, p)| *p).collect();
+        let mut queue: VecDeque<(Direction, GridPosition)> = moves.iter().copied().collect();
+        while let Some((first, position)) = queue.pop_front() {
+            if simulation.foods().iter().any(|food| food.position == position) {
+                return Some(first);
+            }
+            for direction in Direction::ALL {
+                let next = simulation.level().step(position, direction);
+                if !blocked(next) && visited.insert(next) {
+                    queue.push_back((first, next));
+                }
+            }
+        }
+
+        // no food in reach, keep going to where there is the most room
+        moves.into_iter()
+            .max_by_key(|(_, position)| free_neighbours(simulation, *position))
+            .map(|(direction, _)| direction)
+    }
+}
+
+fn free_neighbours(simulation: &Simulation, position: GridPosition) -> usize {
+    Direction::ALL.into_iter()
+        .filter(|direction| !simulation.is_blocked(simulation.level().step(position, *direction)))
+        .count()
+}
+
+// a bot running as a separate process
+//
+// on every tick the game state is written to the bot's stdin as one line of JSON:
+//   {"tick":1,"width":30,"height":20,"player":0,"direction":"left","score":0,
+//    "snake":[[15,10],...],"others":[[[x,y],...],...],
+//    "foods":[[x,y,value],...],"bombs":[[x,y,fuse],...],"enemies":[[x,y],...],"walls":[[x,y],...],
+//    "portals":[[[x,y],[x,y]],...],"terrain":[[x,y,"~"],...]}
+// the snakes are listed head first, "others" has the other living snakes,
+// a snake going into one end of a portal comes out of the other,
+// the terrain is drawn the way the level files draw it, and the walls,
+// portals and terrain are only sent on the first tick
+//
+// the bot answers with one line: "left", "right", "up", "down" or "none",
+// a bot that does not answer in time or fails otherwise makes no more turns
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    // the lines the bot writes, read on a thread of their own so that waiting for them can time out
+    answers: Receiver<std::io::Result<String>>,
+    walls_sent: bool,
+    failed: bool,
+}
+
+impl ExternalBot {
+    pub fn spawn(command: &str) -> std::io::Result<ExternalBot> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let (sender, answers) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(ExternalBot { child, stdin, answers, walls_sent: false, failed: false })
+    }
+
+    fn exchange(&mut self, simulation: &Simulation, player: usize) -> std::io::Result<String> {
+        let mut state = state_json(simulation, player);
+        if !self.walls_sent {
+            let walls: Vec<[u32; 2]> = simulation.walls().map(|p| [p.x, p.y]).collect();
+            state["walls"] = json!(walls);
+            let portals: Vec<[[u32; 2]; 2]> = simulation.level().portals.iter()
+                .map(|(first, second)| [[first.x, first.y], [second.x, second.y]])
+                .collect();
+            state["portals"] = json!(portals);
+            let terrain: Vec<(u32, u32, String)> = simulation.level().terrain.iter()
+                .map(|(position, terrain)| (position.x, position.y, terrain.cell().to_string()))
+                .collect();
+            state["terrain"] = json!(terrain);
+            self.walls_sent = true;
+        }
+        writeln!(self.stdin, "{}", state)?;
+        self.stdin.flush()?;
+
+        match self.answers.recv_timeout(ANSWER_TIMEOUT) {
+            Ok(answer) => answer,
+            Err(RecvTimeoutError::Timeout) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no answer in time")),
+            Err(RecvTimeoutError::Disconnected) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+impl Bot for ExternalBot {
+    fn decide(&mut self, simulation: &Simulation, player: usize) -> Option<Direction> {
+        if self.failed {
+            return None;
+        }
+        match self.exchange(simulation, player) {
+            Ok(answer) => Direction::from_name(answer.trim()),
+            Err(error) => {
+                eprintln!("External bot failed: {}", error);
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn state_json(simulation: &Simulation, player: usize) -> serde_json::Value {
+    let snake = simulation.snake(player);
+    let body: Vec<[u32; 2]> = snake.body.iter().map(|p| [p.x, p.y]).collect();
+    let others: Vec<Vec<[u32; 2]>> = simulation.snakes().iter()
+        .enumerate()
+        .filter(|(other, other_snake)| *other != player && other_snake.is_alive())
+        .map(|(_, other_snake)| other_snake.body.iter().map(|p| [p.x, p.y]).collect())
+        .collect();
+    let foods: Vec<[u32; 3]> = simulation.foods().iter()
+        .map(|food| [food.position.x, food.position.y, food.value()])
+        .collect();
+    let bombs: Vec<[u32; 3]> = simulation.bombs().iter()
+        .map(|bomb| [bomb.position.x, bomb.position.y, bomb.fuse])
+        .collect();
+    let enemies: Vec<[u32; 2]> = simulation.enemies().iter()
+        .map(|enemy| [enemy.position.x, enemy.position.y])
+        .collect();
+    json!({
+        "tick": simulation.tick(),
+        "width": crate::common::GRID_WIDTH,
+        "height": crate::common::GRID_HEIGHT,
+        "player": player,
+        "direction": snake.direction.name(),
+        "score": snake.score,
+        "snake": body,
+        "others": others,
+        "foods": foods,
+        "bombs": bombs,
+        "enemies": enemies,
+    })
+}
@@ -0,0 +1,263 @@
+use std::sync::OnceLock;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{AppState, Text};
+use crate::gameplay::{GameMode, GameSetup, GameSimulation, step_simulation_system};
+use crate::input::GameInput;
+use crate::level::Level;
+use crate::simulation::{GameEvent, Rules, TICK_MILLIS};
+use crate::storage;
+
+// the campaign is a row of levels played alone, each with a goal,
+// reaching the goal unlocks the next level
+//
+// the levels are read from assets/campaign.json, which is built into the game
+
+pub const CAMPAIGN_PROGRESS: &str = "campaign.json";
+
+pub struct CampaignPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Goal {
+    // grow this long
+    Length(usize),
+    // eat this many pieces of food
+    Eat(u32),
+    // stay alive for this many seconds, counted in ticks so that
+    // the speed the game runs at does not change how long it is
+    Survive(u64),
+}
+
+impl Goal {
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::Length(length) => format!("grow to a length of {}", length),
+            Goal::Eat(count) => format!("eat {} pieces of food", count),
+            Goal::Survive(seconds) => format!("stay alive for {} seconds", seconds),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CampaignLevel {
+    pub name: String,
+    // the name of a builtin level
+    pub level: String,
+    pub goal: Goal,
+    // the rules of the difficulty chosen are used when there are none
+    #[serde(default)]
+    pub rules: Option<Rules>,
+}
+
+impl CampaignLevel {
+    pub fn level(&self) -> Level {
+        Level::builtin(&self.level).expect("the levels of the campaign are checked when it is read")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Campaign {
+    levels: Vec<CampaignLevel>,
+}
+
+pub fn levels() -> &'static [CampaignLevel] {
+    static LEVELS: OnceLock<Vec<CampaignLevel>> = OnceLock::new();
+    LEVELS.get_or_init(|| {
+        let campaign: Campaign = serde_json::from_str(include_str!("../assets/campaign.json"))
+            .unwrap_or_else(|error| panic!("the campaign can not be read: {}", error));
+        assert!(!campaign.levels.is_empty(), "the campaign has no levels");
+        for level in &campaign.levels {
+            assert!(Level::builtin(&level.level).is_some(), "unknown level {} in the campaign", level.level);
+        }
+        campaign.levels
+    })
+}
+
+// how many levels have been completed, the next one is unlocked
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CampaignProgress {
+    pub completed: usize,
+}
+
+impl CampaignProgress {
+    // the campaign starts over once it has been played through
+    pub fn next_level(&self) -> usize {
+        if self.completed < levels().len() { self.completed } else { 0 }
+    }
+}
+
+// how the level being played is going, shown on the HUD
+#[derive(Resource, Default)]
+pub struct CampaignRun {
+    eaten: u32,
+    pub status: String,
+}
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_progress())
+            .add_system(start_campaign_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(start_run_system.in_schedule(OnEnter(AppState::Gameplay)))
+            .add_system(goal_system
+                .after(step_simulation_system)
+                .run_if(resource_exists::<CampaignRun>())
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_system(end_run_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(campaign_game_over_system.in_schedule(OnEnter(AppState::GameOver)))
+            .add_system(spawn_level_complete_system.in_schedule(OnEnter(AppState::LevelComplete)))
+            .add_system(level_complete_input_system.in_set(OnUpdate(AppState::LevelComplete)));
+    }
+}
+
+fn load_progress() -> CampaignProgress {
+    let progress = storage::load(CAMPAIGN_PROGRESS)
+        .and_then(|data| serde_json::from_slice::<CampaignProgress>(&data).map_err(std::io::Error::from));
+    match progress {
+        Ok(progress) => progress,
+        Err(error) => {
+            debug!("No campaign progress loaded: {}", error);
+            CampaignProgress::default()
+        }
+    }
+}
+
+fn start_campaign_input_system(mut state: ResMut<NextState<AppState>>,
+                               mut setup: ResMut<GameSetup>,
+                               mut mode: ResMut<GameMode>,
+                               progress: Res<CampaignProgress>,
+                               keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::L) {
+        setup.players = 1;
+        *mode = GameMode::Campaign { level: progress.next_level() };
+        state.set(AppState::Gameplay);
+    }
+}
+
+fn start_run_system(mut commands: Commands,
+                    mode: Res<GameMode>) {
+    if let GameMode::Campaign { level } = *mode {
+        debug!("Playing campaign level {}: {}", level + 1, levels()[level].name);
+        commands.insert_resource(CampaignRun::default());
+    }
+}
+
+fn goal_system(mut state: ResMut<NextState<AppState>>,
+               mode: Res<GameMode>,
+               simulation: Res<GameSimulation>,
+               mut progress: ResMut<CampaignProgress>,
+               mut run: ResMut<CampaignRun>,
+               mut events: EventReader<GameEvent>) {
+    let GameMode::Campaign { level } = *mode else {
+        return;
+    };
+    for event in events.iter() {
+        if let GameEvent::FoodEaten { player: 0, .. } = event {
+            run.eaten += 1;
+        }
+    }
+    let (reached, status) = match levels()[level].goal {
+        Goal::Length(length) => (simulation.snake(0).len() >= length,
+                                 format!("Length: {}/{}", simulation.snake(0).len(), length)),
+        Goal::Eat(count) => (run.eaten >= count, format!("Eaten: {}/{}", run.eaten, count)),
+        Goal::Survive(seconds) => {
+            let left = (seconds * 1000 / TICK_MILLIS).saturating_sub(simulation.tick());
+            (left == 0, format!("Survive: {}s", (left * TICK_MILLIS).div_ceil(1000)))
+        }
+    };
+    run.status = status;
+    // the snake has to be alive when the goal is reached
+    if reached && simulation.snake(0).is_alive() {
+        debug!("Campaign level {} complete", level + 1);
+        if progress.completed <= level {
+            progress.completed = level + 1;
+            let data = serde_json::to_vec_pretty(&*progress).expect("the progress can always be serialized");
+            if let Err(error) = storage::save(CAMPAIGN_PROGRESS, &data) {
+                error!("Failed to save the campaign progress: {}", error);
+            }
+        }
+        state.set(AppState::LevelComplete);
+    }
+}
+
+fn end_run_system(mut commands: Commands) {
+    commands.remove_resource::<CampaignRun>();
+}
+
+// the level failed is tried again by continuing
+fn campaign_game_over_system(mut commands: Commands,
+                             asset_server: Res<AssetServer>,
+                             mode: Res<GameMode>) {
+    if let GameMode::Campaign { level } = *mode {
+        let campaign_level = &levels()[level];
+        spawn_text(&mut commands, &asset_server,
+                   format!("Level {} of {}: {}\nThe goal is to {}",
+                           level + 1, levels().len(), campaign_level.name, campaign_level.goal.describe()),
+                   22.0, Val::Px(10.0));
+    }
+}
+
+fn spawn_level_complete_system(mut commands: Commands,
+                               asset_server: Res<AssetServer>,
+                               mode: Res<GameMode>) {
+    let GameMode::Campaign { level } = *mode else {
+        return;
+    };
+    spawn_text(&mut commands, &asset_server,
+               format!("Level {} complete", level + 1), 80.0, Val::Percent(25.0));
+    let next = match levels().get(level + 1) {
+        Some(next) => format!("Continue to level {}: {}\nThe goal is to {}", level + 2, next.name, next.goal.describe()),
+        None => "That was the last level, the campaign is complete\nContinue to get back to the menu".to_string(),
+    };
+    spawn_text(&mut commands, &asset_server, next, 30.0, Val::Percent(45.0));
+}
+
+fn level_complete_input_system(mut state: ResMut<NextState<AppState>>,
+                               mut mode: ResMut<GameMode>,
+                               mut input: EventReader<GameInput>) {
+    let GameMode::Campaign { level } = *mode else {
+        return;
+    };
+    for event in input.iter() {
+        match event {
+            GameInput::Confirm if level + 1 < levels().len() => {
+                *mode = GameMode::Campaign { level: level + 1 };
+                state.set(AppState::Gameplay);
+            }
+            GameInput::Confirm | GameInput::Back => {
+                *mode = GameMode::Classic;
+                state.set(AppState::GameOver);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn spawn_text(commands: &mut Commands, asset_server: &Res<AssetServer>, value: String, font_size: f32, top: Val) {
+    commands.spawn(
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size,
+                color: Color::GRAY,
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top,
+                left: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
+}
@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+// the command line tools that check files, like snake-verify and snake-levels:
+// they take files and directories, search the directories for the files
+// with their extension, and print a line for every file checked
+
+pub struct FileCheck {
+    pub usage: &'static str,
+    // the extension of the files searched for, and what they are called
+    pub extension: &'static str,
+    pub noun: &'static str,
+    // the problems the file has, or why it could not be checked
+    pub check: fn(&Path) -> Result<Vec<String>, String>,
+}
+
+impl FileCheck {
+    pub fn run(&self, args: impl Iterator<Item = String>) -> ExitCode {
+        let args: Vec<String> = args.collect();
+        if args.iter().any(|arg| arg == "--help") {
+            println!("{}", self.usage);
+            return ExitCode::SUCCESS;
+        }
+        if args.is_empty() {
+            eprintln!("{}", self.usage);
+            return ExitCode::FAILURE;
+        }
+
+        let mut files = Vec::new();
+        for arg in &args {
+            if let Err(error) = self.collect(Path::new(arg), &mut files) {
+                eprintln!("{}: {}", arg, error);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        let mut failures = 0;
+        for path in &files {
+            match (self.check)(path) {
+                Ok(problems) if problems.is_empty() => println!("ok       {}", path.display()),
+                Ok(problems) => {
+                    println!("FAILED   {}", path.display());
+                    for problem in problems {
+                        println!("         {}", problem);
+                    }
+                    failures += 1;
+                }
+                Err(message) => {
+                    println!("FAILED   {}: {}", path.display(), message);
+                    failures += 1;
+                }
+            }
+        }
+
+        println!("{} {}, {} failed", files.len(), self.noun, failures);
+        if failures > 0 {
+            ExitCode::FAILURE
+        }
+        else {
+            ExitCode::SUCCESS
+        }
+    }
+
+    fn collect(&self, path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<_>>()?;
+            entries.sort();
+            for entry in entries {
+                if entry.is_dir() || entry.extension().is_some_and(|extension| extension == self.extension) {
+                    self.collect(&entry, files)?;
+                }
+            }
+        }
+        else {
+            files.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+
+// the clocks picked up for more time are drawn as diamonds
+#[derive(Component)]
+pub(crate) struct Clock {
+    pub id: u32,
+}
+
+impl Clock {
+    pub const COLOR: Color = Color::rgb(0.3, 0.8, 1.0);
+    pub const SIZE: f32 = 16.0;
+
+    pub fn new(id: u32) -> Clock {
+        Clock { id }
+    }
+}
@@ -1,25 +1,25 @@
 use bevy::prelude::{Component, Deref, DerefMut, Timer, TimerMode, States};
+use serde::{Deserialize, Serialize};
 
-use rand::prelude::*;
+pub const GRID_WIDTH: u32 = 30;
+pub const GRID_HEIGHT: u32 = 20;
+pub const GRID_SIZE: f32 = 30.0;
 
-pub(crate) const GRID_WIDTH: u32 = 30;
-pub(crate) const GRID_HEIGHT: u32 = 20;
-pub(crate) const GRID_SIZE: f32 = 30.0;
+pub const WINDOW_WIDTH: f32 = GRID_WIDTH as f32 * GRID_SIZE;
+pub const WINDOW_HEIGHT: f32 = GRID_HEIGHT as f32 * GRID_SIZE;
 
-pub(crate) const WINDOW_WIDTH: f32 = GRID_WIDTH as f32 * GRID_SIZE;
-pub(crate) const WINDOW_HEIGHT: f32 = GRID_HEIGHT as f32 * GRID_SIZE;
-
-#[derive(Debug, Clone, Eq, PartialEq, Hash, States)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default, States)]
 pub enum AppState {
+    #[default]
     SplashScreen,
     Gameplay,
-    GameOver
-}
-
-impl Default for AppState {
-    fn default() -> Self {
-        Self::SplashScreen
-    }
+    GameOver,
+    Replay,
+    Lobby,
+    Online,
+    Settings,
+    LevelComplete,
+    Editor,
 }
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
@@ -31,13 +31,17 @@ pub struct Text;
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
+// which snake a sprite belongs to, the index of the snake in the simulation
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlayerId(pub usize);
+
 #[derive(Component, Deref, DerefMut, Default)]
 pub struct DeathTimer {
     pub timer: Timer,
 }
 
 impl DeathTimer {
-    pub fn default() -> Self {
+    pub fn new() -> Self {
         let seconds = 0.5;
         Self {
             timer: Timer::from_seconds(seconds, TimerMode::Once),
@@ -45,7 +49,8 @@ impl DeathTimer {
     }
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Direction {
     Left,
     Right,
@@ -53,6 +58,46 @@ pub enum Direction {
     Down
 }
 
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    // a quarter turn counterclockwise, as seen on the screen
+    pub fn turned_left(&self) -> Direction {
+        match self {
+            Direction::Left => Direction::Down,
+            Direction::Right => Direction::Up,
+            Direction::Up => Direction::Left,
+            Direction::Down => Direction::Right,
+        }
+    }
+
+    pub fn turned_right(&self) -> Direction {
+        self.turned_left().opposite()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL.into_iter().find(|direction| direction.name() == name)
+    }
+}
+
 #[derive(Component, Clone, Copy)]
 pub struct ScreenPosition {
     pub x: f32,
@@ -76,7 +121,7 @@ impl std::fmt::Display for ScreenPosition {
     }
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct GridPosition {
     pub x: u32,
     pub y: u32,
@@ -87,13 +132,6 @@ impl GridPosition {
         GridPosition { x, y }
     }
 
-    pub fn random(max_x: u32, max_y: u32) -> GridPosition {
-        let mut rng = rand::thread_rng();
-        let x: i32 = rng.gen::<i32>() % max_x as i32;
-        let y: i32 = rng.gen::<i32>() % max_y as i32;
-        GridPosition { x: x.abs() as u32, y: y.abs() as u32}
-    }
-
     pub fn move_position(&mut self, direction: Direction, length: u32) {
         match direction {
             Direction::Left => {
@@ -117,7 +155,7 @@ impl GridPosition {
                     self.y += length;
                 }
                 else {
-                    self.x = GRID_WIDTH;
+                    self.y = GRID_HEIGHT;
                 }
             },
             Direction::Down => {
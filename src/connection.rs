@@ -0,0 +1,213 @@
+// the client end of the WebSocket to the game server,
+// a thread with a blocking socket on native builds
+// and the browser's WebSocket on wasm
+//
+// neither blocks, messages are picked up with receive() once a frame
+
+use crate::net::{ClientMessage, ServerMessage, DEFAULT_PORT};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::io::ErrorKind;
+    use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+    use std::time::Duration;
+
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::Message;
+
+    // how long the socket thread waits for the server before checking for messages to send
+    const POLL_MILLIS: u64 = 5;
+
+    pub struct Connection {
+        outgoing: Sender<String>,
+        incoming: Receiver<Result<String, String>>,
+    }
+
+    impl Connection {
+        pub fn open(url: &str) -> Connection {
+            let (outgoing, to_send) = channel::<String>();
+            let (received, incoming) = channel();
+            let url = url.to_string();
+            std::thread::spawn(move || {
+                let mut socket = match tungstenite::connect(url.as_str()) {
+                    Ok((socket, _)) => socket,
+                    Err(error) => {
+                        let _ = received.send(Err(format!("could not connect to {}: {}", url, error)));
+                        return;
+                    }
+                };
+                if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+                    let _ = stream.set_read_timeout(Some(Duration::from_millis(POLL_MILLIS)));
+                }
+                loop {
+                    loop {
+                        match to_send.try_recv() {
+                            Ok(text) => {
+                                if let Err(error) = socket.send(Message::Text(text)) {
+                                    let _ = received.send(Err(error.to_string()));
+                                    return;
+                                }
+                            }
+                            Err(TryRecvError::Empty) => break,
+                            Err(TryRecvError::Disconnected) => {
+                                let _ = socket.close(None);
+                                let _ = socket.flush();
+                                return;
+                            }
+                        }
+                    }
+                    match socket.read() {
+                        Ok(Message::Text(text)) => {
+                            if received.send(Ok(text)).is_err() {
+                                return;
+                            }
+                        }
+                        Ok(Message::Close(_)) => {
+                            let _ = received.send(Err("the server closed the connection".to_string()));
+                            return;
+                        }
+                        Ok(_) => {}
+                        Err(tungstenite::Error::Io(error))
+                            if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                        Err(error) => {
+                            let _ = received.send(Err(error.to_string()));
+                            return;
+                        }
+                    }
+                }
+            });
+            Connection { outgoing, incoming }
+        }
+
+        pub fn send_text(&self, text: String) {
+            let _ = self.outgoing.send(text);
+        }
+
+        pub fn receive_text(&mut self) -> Option<Result<String, String>> {
+            match self.incoming.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Err("the connection is closed".to_string())),
+            }
+        }
+    }
+
+    pub fn server_host() -> String {
+        std::env::var("SNAKE_SERVER").unwrap_or_else(|_| "127.0.0.1".to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use wasm_bindgen::prelude::*;
+    use web_sys::{CloseEvent, MessageEvent, WebSocket};
+
+    type Inbox = Rc<RefCell<VecDeque<Result<String, String>>>>;
+
+    pub struct Connection {
+        socket: Option<WebSocket>,
+        inbox: Inbox,
+        // messages written before the socket was open
+        queued: Vec<String>,
+        // the callbacks have to live as long as the socket
+        _callbacks: Vec<Closure<dyn FnMut(JsValue)>>,
+    }
+
+    impl Connection {
+        pub fn open(url: &str) -> Connection {
+            let inbox: Inbox = Rc::new(RefCell::new(VecDeque::new()));
+            let socket = match WebSocket::new(url) {
+                Ok(socket) => socket,
+                Err(_) => {
+                    inbox.borrow_mut().push_back(Err(format!("could not connect to {}", url)));
+                    return Connection { socket: None, inbox, queued: Vec::new(), _callbacks: Vec::new() };
+                }
+            };
+
+            let messages = inbox.clone();
+            let on_message = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+                if let Some(text) = event.dyn_into::<MessageEvent>().ok().and_then(|event| event.data().as_string()) {
+                    messages.borrow_mut().push_back(Ok(text));
+                }
+            });
+            let closes = inbox.clone();
+            let on_close = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| {
+                let reason = event.dyn_into::<CloseEvent>().map(|event| event.reason()).unwrap_or_default();
+                closes.borrow_mut().push_back(Err(format!("the connection was closed {}", reason)));
+            });
+            socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+            Connection { socket: Some(socket), inbox, queued: Vec::new(), _callbacks: vec![on_message, on_close] }
+        }
+
+        pub fn send_text(&mut self, text: String) {
+            self.queued.push(text);
+            self.flush();
+        }
+
+        pub fn receive_text(&mut self) -> Option<Result<String, String>> {
+            self.flush();
+            self.inbox.borrow_mut().pop_front()
+        }
+
+        fn flush(&mut self) {
+            if let Some(socket) = &self.socket {
+                if socket.ready_state() == WebSocket::OPEN {
+                    for text in self.queued.drain(..) {
+                        let _ = socket.send_with_str(&text);
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for Connection {
+        fn drop(&mut self) {
+            if let Some(socket) = &self.socket {
+                socket.set_onmessage(None);
+                socket.set_onclose(None);
+                let _ = socket.close();
+            }
+        }
+    }
+
+    // the game is expected to be served from the same host as the server
+    pub fn server_host() -> String {
+        web_sys::window()
+            .and_then(|window| window.location().hostname().ok())
+            .filter(|host| !host.is_empty())
+            .unwrap_or_else(|| "127.0.0.1".to_string())
+    }
+}
+
+pub struct Connection(platform::Connection);
+
+impl Connection {
+    pub fn open(url: &str) -> Connection {
+        Connection(platform::Connection::open(url))
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.0.send_text(message.to_json());
+    }
+
+    // the next message from the server, an error ends the connection
+    pub fn receive(&mut self) -> Option<Result<ServerMessage, String>> {
+        self.0.receive_text().map(|result| {
+            result.and_then(|text| {
+                serde_json::from_str(&text).map_err(|error| format!("bad message from the server: {}", error))
+            })
+        })
+    }
+}
+
+// SNAKE_SERVER picks the server on native builds,
+// the browser connects to the host the game was loaded from
+pub fn default_server_url() -> String {
+    format!("ws://{}:{}", platform::server_host(), DEFAULT_PORT)
+}
@@ -0,0 +1,319 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::common::{AppState, Text};
+use crate::difficulty::Difficulty;
+use crate::embed;
+use crate::gameplay::{GameClock, GameMode, GameSetup, GameSimulation, step_simulation_system};
+use crate::highscores::{HighScore, HighScores};
+use crate::level::{Level, BUILTIN_LEVELS};
+use crate::simulation::{DeathCause, GameEvent};
+use crate::storage;
+
+// the daily challenge is the same game for everybody on the same day:
+// the seed, the level, the difficulty and the modifiers all come from the UTC date,
+// and every day has a high score table of its own
+
+pub const DAILY_RESULT: &str = "daily-result.txt";
+pub const DAILY_EVENT: &str = "snake-daily";
+
+const DAILY_DIFFICULTIES: [&str; 3] = ["easy", "normal", "hard"];
+
+pub struct DailyPlugin;
+
+// each modifier changes one of the rules of the difficulty, at most one of each kind is used
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Modifier {
+    Feast,
+    Famine,
+    Minefield,
+    Calm,
+    ShortFuses,
+    LongFuses,
+}
+
+impl Modifier {
+    const KINDS: [[Modifier; 2]; 3] = [
+        [Modifier::Feast, Modifier::Famine],
+        [Modifier::Minefield, Modifier::Calm],
+        [Modifier::ShortFuses, Modifier::LongFuses],
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Feast => "feast",
+            Modifier::Famine => "famine",
+            Modifier::Minefield => "minefield",
+            Modifier::Calm => "calm",
+            Modifier::ShortFuses => "short fuses",
+            Modifier::LongFuses => "long fuses",
+        }
+    }
+
+    fn apply(self, difficulty: &mut Difficulty) {
+        let rules = &mut difficulty.rules;
+        match self {
+            Modifier::Feast => rules.food_spawn_ticks = (rules.food_spawn_ticks / 2).max(1),
+            Modifier::Famine => rules.food_spawn_ticks *= 2,
+            Modifier::Minefield => rules.bomb_spawn_ticks = (rules.bomb_spawn_ticks / 2).max(1),
+            Modifier::Calm => rules.bomb_spawn_ticks *= 2,
+            Modifier::ShortFuses => rules.bomb_fuse_ticks = (rules.bomb_fuse_ticks / 2).max(1),
+            Modifier::LongFuses => rules.bomb_fuse_ticks *= 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Challenge {
+    // days since 1970-01-01
+    pub day: u64,
+    pub seed: u64,
+    pub level: Level,
+    pub difficulty: Difficulty,
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Challenge {
+    pub fn for_day(day: u64) -> Challenge {
+        let mut rng = StdRng::seed_from_u64(day);
+        let seed = rng.gen();
+        let level = Level::builtin(BUILTIN_LEVELS.choose(&mut rng).expect("there are builtin levels"))
+            .expect("the builtin levels exist");
+        let mut difficulty = Difficulty::builtin(DAILY_DIFFICULTIES.choose(&mut rng).expect("there are difficulties"))
+            .expect("the daily difficulties are builtin");
+        // a kind is left as it is a third of the time
+        let modifiers: Vec<Modifier> = Modifier::KINDS.iter()
+            .filter_map(|kind| kind.get(rng.gen_range(0..=kind.len())).copied())
+            .collect();
+        for modifier in &modifiers {
+            modifier.apply(&mut difficulty);
+        }
+        Challenge { day, seed, level, difficulty, modifiers }
+    }
+
+    // like 2026-10-19
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_date(self.day);
+        format!("{}-{:02}-{:02}", year, month, day)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut value = format!("{} on {}", self.level.name, self.difficulty.name);
+        if !self.modifiers.is_empty() {
+            let names: Vec<&str> = self.modifiers.iter().map(|modifier| modifier.name()).collect();
+            value += &format!(", {}", names.join(", "));
+        }
+        value
+    }
+
+    fn scores_name(&self) -> String {
+        format!("daily-{}.json", self.date())
+    }
+}
+
+// the year, month and day of a day counted from 1970-01-01,
+// from http://howardhinnant.github.io/date_algorithms.html
+fn civil_date(day: u64) -> (u64, u64, u64) {
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    pub fn today() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs() / 86_400).unwrap_or(0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    pub fn today() -> u64 {
+        (js_sys::Date::now() / 86_400_000.0) as u64
+    }
+}
+
+// the days since 1970-01-01 in UTC
+pub use platform::today;
+
+// how the daily game being played is going
+#[derive(Resource, Default)]
+struct DailyRun {
+    eaten: u32,
+}
+
+// the table of the day last played, and the place and the result of the last game
+#[derive(Resource, Default)]
+struct DailyScores {
+    day: Option<u64>,
+    scores: HighScores,
+    last: Option<usize>,
+    result: Option<String>,
+}
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DailyScores>()
+            .add_system(start_daily_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(start_run_system.in_schedule(OnEnter(AppState::Gameplay)))
+            .add_system(count_food_system
+                .after(step_simulation_system)
+                .run_if(resource_exists::<DailyRun>())
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_system(record_score_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(daily_game_over_system.in_schedule(OnEnter(AppState::GameOver)));
+    }
+}
+
+fn start_daily_input_system(mut state: ResMut<NextState<AppState>>,
+                            mut setup: ResMut<GameSetup>,
+                            mut mode: ResMut<GameMode>,
+                            keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::D) {
+        setup.players = 1;
+        *mode = GameMode::Daily { day: today() };
+        state.set(AppState::Gameplay);
+    }
+}
+
+fn start_run_system(mut commands: Commands,
+                    mode: Res<GameMode>) {
+    if let GameMode::Daily { day } = *mode {
+        let challenge = Challenge::for_day(day);
+        debug!("Playing the daily challenge of {}: {}", challenge.date(), challenge.describe());
+        commands.insert_resource(DailyRun::default());
+    }
+}
+
+fn count_food_system(mut run: ResMut<DailyRun>,
+                     mut events: EventReader<GameEvent>) {
+    for event in events.iter() {
+        if let GameEvent::FoodEaten { player: 0, .. } = event {
+            run.eaten += 1;
+        }
+    }
+}
+
+// a game left before it is over counts as well
+fn record_score_system(mut commands: Commands,
+                       mode: Res<GameMode>,
+                       simulation: Res<GameSimulation>,
+                       clock: Res<GameClock>,
+                       run: Option<Res<DailyRun>>,
+                       mut scores: ResMut<DailyScores>) {
+    scores.last = None;
+    scores.result = None;
+    let (GameMode::Daily { day }, Some(run)) = (*mode, run) else {
+        return;
+    };
+    commands.remove_resource::<DailyRun>();
+    let challenge = Challenge::for_day(day);
+    if scores.day != Some(day) {
+        scores.day = Some(day);
+        scores.scores = HighScores::load(&challenge.scores_name());
+    }
+    let snake = simulation.snake(0);
+    scores.last = scores.scores.insert(HighScore { score: snake.score, length: snake.len() as u32 });
+    if scores.last.is_some() {
+        if let Err(error) = scores.scores.save(&challenge.scores_name()) {
+            error!("Failed to save the daily scores: {}", error);
+        }
+    }
+
+    let result = share(&challenge, &simulation, run.eaten, clock.played().as_secs());
+    info!("Daily challenge result:\n{}", result);
+    if let Err(error) = storage::save(DAILY_RESULT, result.as_bytes()) {
+        error!("Failed to save the daily result: {}", error);
+    }
+    embed::dispatch_event(DAILY_EVENT, &serde_json::json!({
+        "date": challenge.date(),
+        "score": snake.score,
+        "share": result,
+    }).to_string());
+    scores.result = Some(result);
+}
+
+// a few lines to paste to the others, without giving away how the game went
+fn share(challenge: &Challenge, simulation: &GameSimulation, eaten: u32, seconds: u64) -> String {
+    let snake = simulation.snake(0);
+    let death = match snake.death {
+        Some(DeathCause::Wall) => "🧱",
+        Some(DeathCause::Body) => "🌀",
+        Some(DeathCause::Snake | DeathCause::HeadOn) => "🐍",
+        Some(DeathCause::Explosion) => "💥",
+        Some(DeathCause::Enemy) => "👾",
+        None => "🏳️",
+    };
+    format!("Snake daily {} ({})\n🍎 {}  📏 {}  ⏱️ {}:{:02}  {}\n🏆 {}",
+            challenge.date(), challenge.describe(),
+            eaten, snake.len(), seconds / 60, seconds % 60, death,
+            snake.score)
+}
+
+fn daily_game_over_system(mut commands: Commands,
+                          asset_server: Res<AssetServer>,
+                          mode: Res<GameMode>,
+                          scores: Res<DailyScores>) {
+    let GameMode::Daily { day } = *mode else {
+        return;
+    };
+    let challenge = Challenge::for_day(day);
+    let mut lines = scores.scores.top_lines(scores.last);
+    lines.insert(0, format!("Daily challenge {}", challenge.date()));
+    lines.insert(1, challenge.describe());
+    if scores.result.is_some() {
+        lines.push(format!("The result to share is in {}", DAILY_RESULT));
+    }
+
+    commands.spawn(
+        TextBundle::from_section(
+            lines.join("\n"),
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 22.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_are_dated() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_745), (2026, 10, 19));
+    }
+
+    #[test]
+    fn a_day_always_has_the_same_challenge() {
+        let challenge = Challenge::for_day(20_745);
+        assert_eq!(challenge, Challenge::for_day(20_745));
+        assert_eq!(challenge.date(), "2026-10-19");
+        // everybody has to get the very same game, so a change here breaks the challenge
+        assert_eq!(challenge.seed, 14_956_534_433_384_366_590);
+        assert_eq!(challenge.describe(), "patrols on hard, famine, minefield, short fuses");
+    }
+}
@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{Rules, Simulation};
+
+// how hard the local games are: how fast the snakes move at first and how much
+// faster they get, and how often food and bombs appear and how long the fuses burn
+//
+// the spawns and the fuses are counted in ticks and go into the rules of the game,
+// so replays stay the same, the speed only decides how long a tick takes
+
+pub const DIFFICULTIES: [&str; 4] = ["easy", "normal", "hard", "custom"];
+
+// the custom difficulty is changed in the saved settings,
+// the ticks never get shorter or longer than these
+const MIN_TICK_MILLIS: u64 = 30;
+const MAX_TICK_MILLIS: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedUp {
+    None,
+    // milliseconds off every tick for each piece the longest snake has grown
+    Length(f32),
+    // milliseconds off every tick for each second played
+    Time(f32),
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Difficulty {
+    // the custom one is always called custom
+    #[serde(skip)]
+    pub name: String,
+    // how long a tick takes at the start, and at the fastest
+    pub tick_millis: u64,
+    pub min_tick_millis: u64,
+    pub speed_up: SpeedUp,
+    pub rules: Rules,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::builtin("normal").expect("normal is a builtin difficulty")
+    }
+}
+
+impl Difficulty {
+    pub fn builtin(name: &str) -> Option<Difficulty> {
+        let (tick_millis, min_tick_millis, speed_up, rules) = match name {
+            "easy" => (250, 180, SpeedUp::Length(1.0), Rules {
+                food_spawn_ticks: 16,
+                bomb_spawn_ticks: 160,
+                bomb_fuse_ticks: 32,
+                ..Rules::default()
+            }),
+            // custom starts out the same as normal
+            "normal" | "custom" => (200, 120, SpeedUp::Length(2.0), Rules::default()),
+            "hard" => (160, 70, SpeedUp::Time(1.0), Rules {
+                food_spawn_ticks: 30,
+                bomb_spawn_ticks: 60,
+                bomb_fuse_ticks: 15,
+                ..Rules::default()
+            }),
+            _ => return None,
+        };
+        Some(Difficulty { name: name.to_string(), tick_millis, min_tick_millis, speed_up, rules })
+    }
+
+    // how long a tick takes now, from how long the longest snake alive is
+    // or how long the game has been played
+    pub fn tick_duration(&self, simulation: &Simulation, played: Duration) -> Duration {
+        let length = simulation.snakes().iter()
+            .filter(|snake| snake.is_alive())
+            .map(|snake| snake.len())
+            .max()
+            .unwrap_or(1);
+        let faster = match self.speed_up {
+            SpeedUp::None => 0.0,
+            SpeedUp::Length(millis) => millis * (length - 1) as f32,
+            SpeedUp::Time(millis) => millis * played.as_secs_f32(),
+        };
+        let millis = (self.tick_millis as f32 - faster).max(self.min_tick_millis as f32);
+        Duration::from_secs_f32(millis / 1000.0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_TICK_MILLIS..=MAX_TICK_MILLIS).contains(&self.tick_millis) {
+            return Err(format!("ticks must take from {} to {} ms, not {}", MIN_TICK_MILLIS, MAX_TICK_MILLIS, self.tick_millis));
+        }
+        if !(MIN_TICK_MILLIS..=self.tick_millis).contains(&self.min_tick_millis) {
+            return Err(format!("the fastest ticks must take from {} to {} ms, not {}",
+                               MIN_TICK_MILLIS, self.tick_millis, self.min_tick_millis));
+        }
+        if let SpeedUp::Length(millis) | SpeedUp::Time(millis) = self.speed_up {
+            if !millis.is_finite() || millis < 0.0 {
+                return Err(format!("the speed up must be a positive number of ms, not {}", millis));
+            }
+        }
+        if self.rules.food_spawn_ticks == 0 || self.rules.bomb_spawn_ticks == 0 || self.rules.bomb_fuse_ticks == 0 {
+            return Err("food, bombs and fuses need at least one tick".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let speed_up = match self.speed_up {
+            SpeedUp::None => String::new(),
+            SpeedUp::Length(millis) => format!(" and {} ms faster for every piece grown down to {} ms", millis, self.min_tick_millis),
+            SpeedUp::Time(millis) => format!(" and {} ms faster every second down to {} ms", millis, self.min_tick_millis),
+        };
+        format!("{} ms a step{}\nfood every {} steps, bombs every {} steps with {} step fuses",
+                self.tick_millis, speed_up, self.rules.food_spawn_ticks,
+                self.rules.bomb_spawn_ticks, self.rules.bomb_fuse_ticks)
+    }
+}
+
+// the difficulty chosen for the local games, and what the custom one is like
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultySettings {
+    pub selected: String,
+    pub custom: Difficulty,
+}
+
+impl Default for DifficultySettings {
+    fn default() -> Self {
+        DifficultySettings {
+            selected: Difficulty::default().name,
+            custom: Difficulty::builtin("custom").expect("custom is a builtin difficulty"),
+        }
+    }
+}
+
+impl DifficultySettings {
+    pub fn difficulty(&self) -> Difficulty {
+        if self.selected == "custom" {
+            Difficulty { name: self.selected.clone(), ..self.custom.clone() }
+        }
+        else {
+            Difficulty::builtin(&self.selected).unwrap_or_default()
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        let index = DIFFICULTIES.iter().position(|name| *name == self.selected).unwrap_or(0);
+        self.selected = DIFFICULTIES[(index + 1) % DIFFICULTIES.len()].to_string();
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !DIFFICULTIES.contains(&self.selected.as_str()) {
+            return Err(format!("unknown difficulty {}", self.selected));
+        }
+        self.custom.validate().map_err(|error| format!("the custom difficulty is not right, {}", error))
+    }
+}
@@ -0,0 +1,536 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use rand::prelude::*;
+
+use crate::common::{AppState, BackgroundImage, Direction, GridPosition, ScreenPosition, GRID_HEIGHT, GRID_SIZE, GRID_WIDTH};
+use crate::difficulty::DifficultySettings;
+use crate::food::FOODS;
+use crate::gameplay::{GameMode, GameSetup, NewGame};
+use crate::input::{Action, Bindings, GameInput, key_name};
+use crate::enemy::Enemy;
+use crate::level::{EnemyKind, Level, Terrain, MAX_PORTALS};
+use crate::snake::SnakeHead;
+use crate::storage;
+use crate::terrain::{self, Conveyor, Ice, Mud};
+use crate::portal::Portal;
+use crate::wall::Wall;
+
+// the editor draws a level with the mouse, tries it out right away
+// and saves it as a level file, the way Level::from_file reads them
+//
+// the level is kept while it is tried out, going back from the editor forgets it
+
+const DEFAULT_NAME: &str = "my-level";
+const MAX_NAME_LENGTH: usize = 24;
+
+const SPRITE_Z_DEPTH: f32 = 50.0;
+const BACKGROUND_Z_DEPTH: f32 = 0.0;
+
+const BOMB_SPAWN_COLOR: Color = Color::rgba(0.9, 0.2, 0.2, 0.6);
+const FOOD_SPAWN_ALPHA: f32 = 0.6;
+const PATROL_PATH_COLOR: Color = Color::rgba(1.0, 0.55, 0.1, 0.6);
+
+pub struct EditorPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tool {
+    Wall,
+    Start,
+    Food,
+    Bomb,
+    Portal,
+    Terrain(Terrain),
+    Patrol,
+    Chaser,
+}
+
+impl Tool {
+    const ALL: [Tool; 10] = [Tool::Wall, Tool::Start, Tool::Food, Tool::Bomb, Tool::Portal,
+        Tool::Terrain(Terrain::Ice), Tool::Terrain(Terrain::Conveyor(Direction::Right)), Tool::Terrain(Terrain::Mud),
+        Tool::Patrol, Tool::Chaser];
+    const KEYS: [KeyCode; 10] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+        KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9, KeyCode::Key0];
+
+    fn name(self) -> String {
+        let name = match self {
+            Tool::Wall => "walls",
+            Tool::Start => "the start",
+            Tool::Food => "food spawns",
+            Tool::Bomb => "bomb spawns",
+            Tool::Portal => "portals",
+            Tool::Terrain(Terrain::Conveyor(direction)) => return format!("conveyors running {}", direction.name()),
+            Tool::Terrain(terrain) => terrain.name(),
+            Tool::Patrol => "patrols",
+            Tool::Chaser => "chasers",
+        };
+        name.to_string()
+    }
+
+    // as it is drawn on the map, the portals are numbered in the order they are placed
+    fn cell(self) -> Option<char> {
+        match self {
+            Tool::Wall => Some('#'),
+            Tool::Start => Some('H'),
+            Tool::Food => Some('*'),
+            Tool::Bomb => Some('B'),
+            Tool::Portal | Tool::Patrol => None,
+            Tool::Terrain(terrain) => Some(terrain.cell()),
+            Tool::Chaser => Some('C'),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct Editor {
+    level: Level,
+    tool: Tool,
+    status: String,
+    // the name of the level is being typed
+    naming: bool,
+    // the first end of a portal waits for the second one
+    portal_end: Option<GridPosition>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            level: Level { name: DEFAULT_NAME.to_string(), ..Level::default() },
+            tool: Tool::Wall,
+            status: String::new(),
+            naming: false,
+            portal_end: None,
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorSprite;
+
+#[derive(Component)]
+struct EditorText;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(open_editor_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(spawn_editor_system.in_schedule(OnEnter(AppState::Editor)))
+            .add_systems((editor_input_system, paint_system, draw_level_system, editor_text_system)
+                .chain()
+                .in_set(OnUpdate(AppState::Editor)))
+            .add_system(despawn_editor_system.in_schedule(OnExit(AppState::Editor)))
+            .add_system(playtest_input_system.in_set(OnUpdate(AppState::Gameplay)));
+    }
+}
+
+fn open_editor_input_system(mut state: ResMut<NextState<AppState>>,
+                            keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::E) {
+        state.set(AppState::Editor);
+    }
+}
+
+// the game tried out leaves its walls, portals and terrain behind,
+// and the level it was played on is drawn again
+fn spawn_editor_system(mut commands: Commands,
+                       asset_server: Res<AssetServer>,
+                       editor: Option<ResMut<Editor>>,
+                       query: Query<Entity, Or<(&Wall, &Portal, &Ice, &Conveyor, &Mud, &BackgroundImage)>>) {
+    match editor {
+        Some(mut editor) => editor.set_changed(),
+        None => commands.init_resource::<Editor>(),
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let scale_factor = crate::common::WINDOW_HEIGHT / 99.0;
+    commands.spawn(
+        SpriteBundle {
+            texture: asset_server.load("background.png"),
+            transform: Transform {
+                scale: Vec3::new(scale_factor, scale_factor, 1.0),
+                translation: Vec3::new(0.0, 0.0, BACKGROUND_Z_DEPTH),
+                ..default()
+            },
+            ..default()
+        }).insert(BackgroundImage);
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(EditorText);
+}
+
+fn editor_input_system(mut commands: Commands,
+                       mut state: ResMut<NextState<AppState>>,
+                       (mut setup, mut mode): (ResMut<GameSetup>, ResMut<GameMode>),
+                       keyboard_input: Res<Input<KeyCode>>,
+                       (mut input, mut characters): (EventReader<GameInput>, EventReader<ReceivedCharacter>),
+                       difficulty: Res<DifficultySettings>,
+                       mut editor: ResMut<Editor>) {
+    // read on every frame, so the letter of the key that starts the naming is not left in the name
+    let typed: Vec<char> = characters.iter().map(|event| event.char).collect();
+    let input: Vec<GameInput> = input.iter().copied().collect();
+
+    if editor.naming {
+        for letter in typed.iter().filter(|letter| letter.is_ascii_alphanumeric() || **letter == '-') {
+            if editor.level.name.len() < MAX_NAME_LENGTH {
+                editor.level.name.push(letter.to_ascii_lowercase());
+            }
+        }
+        if input.contains(&GameInput::Back) {
+            editor.level.name.pop();
+        }
+        if input.contains(&GameInput::Confirm) && !editor.level.name.is_empty() {
+            editor.naming = false;
+        }
+        return;
+    }
+
+    if input.contains(&GameInput::Back) {
+        commands.remove_resource::<Editor>();
+        *mode = GameMode::Classic;
+        state.set(AppState::GameOver);
+        return;
+    }
+    if input.contains(&GameInput::Confirm) {
+        debug!("Trying out the level {}", editor.level.name);
+        setup.players = 1;
+        *mode = GameMode::Editor;
+        commands.insert_resource(NewGame {
+            seed: rand::thread_rng().gen(),
+            rules: difficulty.difficulty().rules,
+            level: editor.level.clone(),
+            players: 1,
+        });
+        state.set(AppState::Gameplay);
+        return;
+    }
+    // the turn keys turn the conveyors while they are drawn, and the start otherwise
+    for event in &input {
+        if let GameInput::Turn { player: 0, direction } = event {
+            if let Tool::Terrain(Terrain::Conveyor(_)) = editor.tool {
+                editor.tool = Tool::Terrain(Terrain::Conveyor(*direction));
+            }
+            else if let Some((position, _)) = editor.level.start {
+                editor.level.start = Some((position, *direction));
+            }
+        }
+    }
+    if let Some(index) = Tool::KEYS.iter().position(|key| keyboard_input.just_pressed(*key)) {
+        editor.tool = Tool::ALL[index];
+    }
+    if keyboard_input.just_pressed(KeyCode::N) {
+        editor.naming = true;
+        editor.level.name.clear();
+        editor.status.clear();
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        editor.level = Level { name: editor.level.name.clone(), ..Level::default() };
+        editor.portal_end = None;
+        editor.status = "Cleared".to_string();
+    }
+    if keyboard_input.just_pressed(KeyCode::S) {
+        let file_name = Level::file_name(&editor.level.name);
+        editor.status = match storage::save(&file_name, &editor.level.to_file()) {
+            Ok(()) => format!("Saved to {}", file_name),
+            Err(error) => format!("Failed to save {}: {}", file_name, error),
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::L) {
+        let file_name = Level::file_name(&editor.level.name);
+        let level = storage::load(&file_name)
+            .map_err(|error| error.to_string())
+            .and_then(|data| Level::from_file(&data));
+        editor.status = match level {
+            Ok(level) => {
+                editor.level = level;
+                editor.portal_end = None;
+                format!("Loaded {}", file_name)
+            }
+            Err(error) => format!("Failed to load {}: {}", file_name, error),
+        };
+    }
+}
+
+// the left button draws with the tool chosen and the right one clears
+fn paint_system(mouse_input: Res<Input<MouseButton>>,
+                windows: Query<&Window, With<PrimaryWindow>>,
+                mut editor: ResMut<Editor>) {
+    let tool = if mouse_input.pressed(MouseButton::Left) {
+        Some(editor.tool)
+    }
+    else if mouse_input.pressed(MouseButton::Right) {
+        None
+    }
+    else {
+        return;
+    };
+    let Some(position) = windows.get_single().ok().and_then(cursor_position) else {
+        return;
+    };
+    if editor.naming {
+        return;
+    }
+    // a portal is placed a click at a time, not by drawing
+    if tool == Some(Tool::Portal) {
+        if mouse_input.just_pressed(MouseButton::Left) {
+            place_portal(&mut editor, position);
+        }
+        return;
+    }
+    // a patrol is drawn from where it starts along the way it walks
+    if tool == Some(Tool::Patrol) {
+        if mouse_input.just_pressed(MouseButton::Left) {
+            paint(&mut editor.level, position, None);
+            editor.level.enemies.push(EnemyKind::Patrol(vec![position]));
+        }
+        else {
+            extend_patrol(&mut editor, position);
+        }
+        return;
+    }
+    // only changed when it changes, so the level is not drawn again on every frame
+    if Some(editor.level.cell(position)) == tool.map_or(Some('.'), Tool::cell) {
+        return;
+    }
+    paint(&mut editor.level, position, tool);
+    if editor.portal_end == Some(position) {
+        editor.portal_end = None;
+    }
+}
+
+// the first click places one end and the second one the other end,
+// clicking the first end again takes it back
+fn place_portal(editor: &mut Editor, position: GridPosition) {
+    match editor.portal_end.take() {
+        Some(first) if first == position => {}
+        Some(first) => {
+            paint(&mut editor.level, position, None);
+            editor.level.portals.push((first, position));
+        }
+        None if editor.level.portals.len() >= MAX_PORTALS => {
+            editor.status = format!("There can be {} portals at most", MAX_PORTALS);
+        }
+        None => {
+            paint(&mut editor.level, position, None);
+            editor.portal_end = Some(position);
+        }
+    }
+}
+
+// the place under the cursor inside the border, the editor keeps the camera
+// in the middle, where the cursor is counted from the bottom left corner
+fn cursor_position(window: &Window) -> Option<GridPosition> {
+    let cursor = window.cursor_position()? - Vec2::new(window.width(), window.height()) / 2.0;
+    let x = ((cursor.x + (GRID_WIDTH as f32 * GRID_SIZE) / 2.0) / GRID_SIZE).round();
+    let y = ((cursor.y + (GRID_HEIGHT as f32 * GRID_SIZE) / 2.0) / GRID_SIZE).round();
+    let inside = x >= 1.0 && x < GRID_WIDTH as f32 && y >= 1.0 && y < GRID_HEIGHT as f32;
+    inside.then(|| GridPosition::new(x as u32, y as u32))
+}
+
+// the path of the patrol drawn last goes on a place at a time to the cursor,
+// and stops at walls, portals, the start and where the enemies start
+fn extend_patrol(editor: &mut ResMut<Editor>, position: GridPosition) {
+    let level = &editor.bypass_change_detection().level;
+    let Some(EnemyKind::Patrol(path)) = level.enemies.last() else {
+        return;
+    };
+    let mut last = *path.last().expect("a path starts somewhere");
+    let mut added = Vec::new();
+    while last != position {
+        let direction = if last.x < position.x { Direction::Right }
+            else if last.x > position.x { Direction::Left }
+            else if last.y < position.y { Direction::Up }
+            else { Direction::Down };
+        last.move_position(direction, 1);
+        if matches!(level.cell(last), '#' | 'H' | 'P' | 'C' | '1'..='9') {
+            break;
+        }
+        added.push(last);
+    }
+    if added.is_empty() {
+        return;
+    }
+    if let Some(EnemyKind::Patrol(path)) = editor.level.enemies.last_mut() {
+        path.extend(added);
+    }
+}
+
+// a place has one thing on it at most, and the start keeps its direction when it is moved,
+// clearing either end of a portal takes the whole portal away, and the enemies
+// walk over spawns and terrain, but anything else takes away the ones there
+fn paint(level: &mut Level, position: GridPosition, tool: Option<Tool>) {
+    if matches!(tool, Some(Tool::Food | Tool::Bomb | Tool::Terrain(_))) {
+        level.enemies.retain(|enemy| enemy.start() != position);
+    }
+    else {
+        level.enemies.retain(|enemy| !enemy.places().contains(&position));
+    }
+    level.walls.retain(|wall| *wall != position);
+    level.portals.retain(|(first, second)| *first != position && *second != position);
+    level.terrain.retain(|(place, _)| *place != position);
+    level.food_spawns.retain(|spawn| *spawn != position);
+    level.bomb_spawns.retain(|spawn| *spawn != position);
+    if level.start.is_some_and(|(start, _)| start == position) {
+        level.start = None;
+    }
+    match tool {
+        Some(Tool::Wall) => level.walls.push(position),
+        Some(Tool::Start) => {
+            let direction = level.start.map_or(Direction::Left, |(_, direction)| direction);
+            level.start = Some((position, direction));
+        }
+        Some(Tool::Food) => level.food_spawns.push(position),
+        Some(Tool::Bomb) => level.bomb_spawns.push(position),
+        Some(Tool::Terrain(terrain)) => level.terrain.push((position, terrain)),
+        Some(Tool::Chaser) => level.enemies.push(EnemyKind::Chaser(position)),
+        Some(Tool::Portal | Tool::Patrol) | None => {}
+    }
+}
+
+fn draw_level_system(mut commands: Commands,
+                     asset_server: Res<AssetServer>,
+                     editor: Res<Editor>,
+                     query: Query<Entity, With<EditorSprite>>) {
+    if !editor.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let border = (0..=GRID_WIDTH).flat_map(|x| [GridPosition::new(x, 0), GridPosition::new(x, GRID_HEIGHT)])
+        .chain((1..GRID_HEIGHT).flat_map(|y| [GridPosition::new(0, y), GridPosition::new(GRID_WIDTH, y)]));
+    for position in border.chain(editor.level.walls.iter().copied()) {
+        spawn_sprite(&mut commands, position, SpriteBundle {
+            texture: asset_server.load("brickwall.png"),
+            ..default()
+        });
+    }
+    for position in &editor.level.food_spawns {
+        spawn_sprite(&mut commands, *position, SpriteBundle {
+            texture: asset_server.load(FOODS[0].0),
+            sprite: Sprite { color: Color::rgba(1.0, 1.0, 1.0, FOOD_SPAWN_ALPHA), ..default() },
+            ..default()
+        });
+    }
+    for position in &editor.level.bomb_spawns {
+        spawn_sprite(&mut commands, *position, SpriteBundle {
+            sprite: Sprite { color: BOMB_SPAWN_COLOR, custom_size: Some(Vec2::splat(GRID_SIZE)), ..default() },
+            ..default()
+        });
+    }
+    for (position, kind) in &editor.level.terrain {
+        for bundle in terrain::sprites(*kind) {
+            spawn_sprite(&mut commands, *position, bundle);
+        }
+    }
+    let portals = editor.level.portals.iter().enumerate()
+        .flat_map(|(index, (first, second))| [(index, *first), (index, *second)])
+        .chain(editor.portal_end.map(|position| (editor.level.portals.len(), position)));
+    for (index, position) in portals {
+        for bundle in Portal::sprites(Portal::color(index)) {
+            spawn_sprite(&mut commands, position, bundle);
+        }
+    }
+    // the enemies where they start, and the ways the patrols walk
+    for enemy in &editor.level.enemies {
+        if let EnemyKind::Patrol(path) = enemy {
+            for position in &path[1..] {
+                spawn_sprite(&mut commands, *position, SpriteBundle {
+                    sprite: Sprite { color: PATROL_PATH_COLOR, custom_size: Some(Vec2::splat(GRID_SIZE / 4.0)), ..default() },
+                    ..default()
+                });
+            }
+        }
+        let (color, rotation) = match enemy {
+            EnemyKind::Patrol(_) => (Enemy::PATROL_COLOR, Quat::IDENTITY),
+            EnemyKind::Chaser(_) => (Enemy::CHASER_COLOR, Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+        };
+        spawn_sprite(&mut commands, enemy.start(), SpriteBundle {
+            sprite: Sprite { color, custom_size: Some(Vec2::splat(Enemy::SIZE)), ..default() },
+            transform: Transform::from_rotation(rotation),
+            ..default()
+        });
+    }
+    // the head with a mark on the side it heads to
+    if let Some((position, direction)) = editor.level.start {
+        spawn_sprite(&mut commands, position, SpriteBundle {
+            sprite: Sprite { color: SnakeHead::PLAYER_COLORS[0], custom_size: Some(Vec2::splat(GRID_SIZE)), ..default() },
+            ..default()
+        });
+        let mark = match direction {
+            Direction::Left => Vec2::new(-1.0, 0.0),
+            Direction::Right => Vec2::new(1.0, 0.0),
+            Direction::Up => Vec2::new(0.0, 1.0),
+            Direction::Down => Vec2::new(0.0, -1.0),
+        } * GRID_SIZE / 3.0;
+        spawn_sprite(&mut commands, position, SpriteBundle {
+            sprite: Sprite { color: Color::DARK_GRAY, custom_size: Some(Vec2::splat(GRID_SIZE / 3.0)), ..default() },
+            transform: Transform::from_xyz(mark.x, mark.y, 1.0),
+            ..default()
+        });
+    }
+}
+
+// the transform of the bundle is taken from the place
+fn spawn_sprite(commands: &mut Commands, position: GridPosition, bundle: SpriteBundle) {
+    let screen_pos = ScreenPosition::from(position);
+    let translation = Vec3::new(screen_pos.x, screen_pos.y, SPRITE_Z_DEPTH) + bundle.transform.translation;
+    commands.spawn(SpriteBundle {
+        transform: Transform { translation, ..bundle.transform },
+        ..bundle
+    }).insert(EditorSprite);
+}
+
+fn editor_text_system(bindings: Res<Bindings>,
+                      editor: Res<Editor>,
+                      mut query: Query<&mut Text, With<EditorText>>) {
+    if !editor.is_changed() {
+        return;
+    }
+    let name = if editor.naming { format!("[{}_]", editor.level.name) } else { editor.level.name.clone() };
+    let start = editor.level.start.map_or("in the middle".to_string(), |(_, direction)| format!("heading {}", direction.name()));
+    let key = |action| key_name(bindings.keys(0, action)[0]);
+    let lines = [
+        format!("Editor - {}    drawing {}    the snake starts {}    {}",
+                name, editor.tool.name(), start, editor.status),
+        "Left click draws, right click clears: 1 walls, 2 the start, 3 food spawns, 4 bomb spawns, 5 portals".to_string(),
+        "6 ice, 7 conveyors, 8 mud, 9 patrols along the way they are drawn, 0 chasers, the turn keys turn the start or the conveyors".to_string(),
+        format!("N names the level, S saves it, L loads it, Delete clears it, {} tries it out and {} goes back",
+                key(Action::Confirm), key(Action::Back)),
+    ];
+    for mut text in &mut query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn despawn_editor_system(mut commands: Commands,
+                         query: Query<Entity, Or<(With<EditorSprite>, With<EditorText>, With<BackgroundImage>)>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+// a game tried out goes back to the editor
+fn playtest_input_system(mut state: ResMut<NextState<AppState>>,
+                         mode: Res<GameMode>,
+                         mut input: EventReader<GameInput>) {
+    if *mode == GameMode::Editor && input.iter().any(|event| *event == GameInput::Back) {
+        state.set(AppState::Editor);
+    }
+}
@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::common::AppState;
+use crate::difficulty::DifficultySettings;
+use crate::gameplay::{GameClock, GameMode, GameSetup, GameSimulation, NewGame, MAX_LOCAL_PLAYERS};
+use crate::launch::LaunchConfig;
+use crate::level::LevelSettings;
+use crate::simulation::Rules;
+use crate::timeattack::Countdown;
+
+// the page the game is embedded in can control it: the functions exported
+// to JavaScript queue commands that are carried out on the next frame,
+// and the page hears of the scores and the end of each game from DOM events
+
+pub const SCORE_EVENT: &str = "snake-score";
+pub const GAME_OVER_EVENT: &str = "snake-gameover";
+
+static COMMANDS: Mutex<Vec<EmbedCommand>> = Mutex::new(Vec::new());
+static SCORES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+pub struct EmbedPlugin;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmbedCommand {
+    Start,
+    Pause,
+    Resume,
+    Restart,
+    Configure(EmbedConfig),
+}
+
+// the games started from the page are played with these,
+// anything left out is chosen as usual, on the level the game was launched with
+// and with the rules of the difficulty chosen
+#[derive(Resource, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbedConfig {
+    pub seed: Option<u64>,
+    pub players: Option<usize>,
+    pub rules: Option<Rules>,
+}
+
+impl EmbedConfig {
+    pub fn parse(json: &str) -> Result<EmbedConfig, String> {
+        let config: EmbedConfig = serde_json::from_str(json).map_err(|error| error.to_string())?;
+        if let Some(players) = config.players.filter(|players| !(1..=MAX_LOCAL_PLAYERS).contains(players)) {
+            return Err(format!("players must be from 1 to {}, not {}", MAX_LOCAL_PLAYERS, players));
+        }
+        Ok(config)
+    }
+}
+
+pub fn send(command: EmbedCommand) {
+    COMMANDS.lock().unwrap().push(command);
+}
+
+pub fn take_commands() -> Vec<EmbedCommand> {
+    std::mem::take(&mut *COMMANDS.lock().unwrap())
+}
+
+// the scores of the game being played, or of the last one
+pub fn scores() -> Vec<u32> {
+    SCORES.lock().unwrap().clone()
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use wasm_bindgen::prelude::*;
+
+    use super::{send, scores, EmbedCommand, EmbedConfig};
+
+    #[wasm_bindgen]
+    pub fn start() {
+        send(EmbedCommand::Start);
+    }
+
+    #[wasm_bindgen]
+    pub fn pause() {
+        send(EmbedCommand::Pause);
+    }
+
+    #[wasm_bindgen]
+    pub fn resume() {
+        send(EmbedCommand::Resume);
+    }
+
+    #[wasm_bindgen]
+    pub fn restart() {
+        send(EmbedCommand::Restart);
+    }
+
+    // takes an object like { seed: 42, players: 2 }
+    #[wasm_bindgen(js_name = setConfig)]
+    pub fn set_config(config: JsValue) -> Result<(), JsValue> {
+        let json: String = js_sys::JSON::stringify(&config)?.into();
+        let config = EmbedConfig::parse(&json).map_err(|error| JsValue::from_str(&error))?;
+        send(EmbedCommand::Configure(config));
+        Ok(())
+    }
+
+    // the score of the first snake
+    #[wasm_bindgen(js_name = getScore)]
+    pub fn get_score() -> u32 {
+        scores().first().copied().unwrap_or(0)
+    }
+
+    #[wasm_bindgen(js_name = getScores)]
+    pub fn get_scores() -> Vec<u32> {
+        scores()
+    }
+
+    // the detail of the event is the JSON parsed into an object,
+    // there is no window to send it to under Node
+    pub fn dispatch_event(name: &str, detail: &str) {
+        let Some(window) = web_sys::window() else {
+            return;
+        };
+        let detail = js_sys::JSON::parse(detail).unwrap_or(JsValue::NULL);
+        let mut init = web_sys::CustomEventInit::new();
+        init.detail(&detail);
+        match web_sys::CustomEvent::new_with_event_init_dict(name, &init) {
+            Ok(event) => {
+                let _ = window.dispatch_event(&event);
+            }
+            Err(error) => bevy::log::error!("Could not create the {} event: {:?}", name, error),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    pub fn dispatch_event(name: &str, detail: &str) {
+        bevy::log::debug!("{} {}", name, detail);
+    }
+}
+
+// the other modes tell the page of their own results the same way
+pub(crate) use platform::dispatch_event;
+
+#[cfg(target_arch = "wasm32")]
+pub use platform::{get_score, get_scores, pause, restart, resume, set_config, start};
+
+impl Plugin for EmbedPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EmbedConfig>()
+            .add_system(embed_command_system)
+            .add_system(score_event_system.run_if(resource_exists::<GameSimulation>()))
+            .add_system(game_over_event_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(game_over_event_system.in_schedule(OnExit(AppState::Online)));
+    }
+}
+
+fn embed_command_system(mut commands: Commands,
+                        state: Res<State<AppState>>,
+                        mut next_state: ResMut<NextState<AppState>>,
+                        mut config: ResMut<EmbedConfig>,
+                        mut setup: ResMut<GameSetup>,
+                        (launch, difficulty, levels, mode): (Res<LaunchConfig>, Res<DifficultySettings>, Res<LevelSettings>, Res<GameMode>),
+                        clock: Option<ResMut<GameClock>>) {
+    let playing = matches!(state.0, AppState::Gameplay | AppState::Online);
+    let mut clock = clock.filter(|_| state.0 == AppState::Gameplay);
+    for command in take_commands() {
+        debug!("Command from the page: {:?}", command);
+        match command {
+            EmbedCommand::Start if playing => {}
+            EmbedCommand::Start | EmbedCommand::Restart => {
+                let seed = mode.seed().or(config.seed).unwrap_or_else(|| rand::thread_rng().gen());
+                commands.insert_resource(NewGame {
+                    seed,
+                    rules: mode.rules(config.rules.clone().unwrap_or_else(|| difficulty.difficulty().rules)),
+                    level: mode.level().or_else(|| launch.level(seed)).unwrap_or_else(|| levels.level(seed)),
+                    players: setup.players,
+                });
+                next_state.set(AppState::Gameplay);
+            }
+            EmbedCommand::Pause | EmbedCommand::Resume => {
+                if let Some(clock) = clock.as_mut() {
+                    clock.paused = command == EmbedCommand::Pause;
+                }
+            }
+            EmbedCommand::Configure(new_config) => {
+                if let Some(players) = new_config.players {
+                    setup.players = players;
+                }
+                *config = new_config;
+            }
+        }
+    }
+}
+
+// replays are not games being played, so their scores are not told
+fn score_event_system(state: Res<State<AppState>>,
+                      simulation: Res<GameSimulation>,
+                      mut last: Local<Vec<u32>>) {
+    if !matches!(state.0, AppState::Gameplay | AppState::Online) {
+        return;
+    }
+    let scores: Vec<u32> = simulation.snakes().iter().map(|snake| snake.score).collect();
+    if scores == *last {
+        return;
+    }
+    for (player, score) in scores.iter().enumerate() {
+        if last.get(player) != Some(score) {
+            platform::dispatch_event(SCORE_EVENT, &serde_json::json!({ "player": player, "score": score }).to_string());
+        }
+    }
+    SCORES.lock().unwrap().clone_from(&scores);
+    *last = scores;
+}
+
+// also sent when a game is left before it is over, which tells it is not finished,
+// a time attack game is finished when the time is up
+fn game_over_event_system(simulation: Res<GameSimulation>,
+                          countdown: Option<Res<Countdown>>) {
+    let detail = serde_json::json!({
+        "scores": simulation.snakes().iter().map(|snake| snake.score).collect::<Vec<_>>(),
+        "winner": simulation.winner(),
+        "ticks": simulation.tick(),
+        "seed": simulation.seed(),
+        "finished": simulation.is_over() || countdown.is_some_and(|countdown| countdown.is_up()),
+    });
+    platform::dispatch_event(GAME_OVER_EVENT, &detail.to_string());
+}
@@ -0,0 +1,17 @@
+use bevy::prelude::*;
+
+// the patrollers are drawn as squares and the chasers as diamonds
+#[derive(Component)]
+pub(crate) struct Enemy {
+    pub id: u32,
+}
+
+impl Enemy {
+    pub const PATROL_COLOR: Color = Color::rgb(1.0, 0.55, 0.1);
+    pub const CHASER_COLOR: Color = Color::rgb(0.75, 0.2, 0.9);
+    pub const SIZE: f32 = 22.0;
+
+    pub fn new(id: u32) -> Enemy {
+        Enemy { id }
+    }
+}
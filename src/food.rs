@@ -1,7 +1,6 @@
 use bevy::prelude::Component;
-use rand::prelude::*;
 
-const FOODS: [(&str, u32); 3] = [
+pub const FOODS: [(&str, u32); 3] = [
     ("apple.png", 2),
     ("banana.png", 5),
     ("watermelon.png", 8),
@@ -9,14 +8,12 @@ const FOODS: [(&str, u32); 3] = [
 
 #[derive(Component)]
 pub(crate) struct Food {
-    pub value: u32,
+    pub id: u32,
     pub asset: String,
 }
 
 impl Food {
-    pub fn random() -> Food {
-        let mut rng = rand::thread_rng();
-        let kind: usize = rng.gen::<usize>() % FOODS.len() as usize;
-        Food { value: FOODS[kind].1, asset: FOODS[kind].0.to_string() }
+    pub fn new(id: u32, kind: usize) -> Food {
+        Food { id, asset: FOODS[kind].0.to_string() }
     }
 }
@@ -1,17 +1,34 @@
 use bevy::prelude::*;
 use bevy::time::common_conditions::on_timer;
 use bevy::utils::Duration;
+use rand::prelude::*;
 
 use crate::common::AppState;
 use crate::common::BackgroundImage;
 use crate::common::{GridPosition, ScreenPosition};
-use crate::common::{GRID_SIZE, GRID_WIDTH, GRID_HEIGHT};
+use crate::common::GRID_SIZE;
 use crate::common::AnimationTimer;
 use crate::common::DeathTimer;
+use crate::common::Direction;
+use crate::common::PlayerId;
 use crate::snake::{SnakeHead, SnakeBodyPiece};
 use crate::wall::Wall;
+use crate::portal::Portal;
+use crate::terrain;
 use crate::food::Food;
 use crate::bomb::{Bomb, ParticleSystem, Particle};
+use crate::clock::Clock;
+use crate::enemy::Enemy;
+use crate::simulation::{Simulation, Rules, GameEvent, DeathCause, TICK_MILLIS};
+use crate::replayscreen::ReplayPlayback;
+use crate::online::OnlineGame;
+use crate::input::{Bindings, GameInput, LOCAL_PLAYERS};
+use crate::launch::LaunchConfig;
+use crate::level::{EnemyKind, Level, LevelSettings};
+use crate::difficulty::{Difficulty, DifficultySettings};
+use crate::campaign;
+use crate::puzzle;
+use crate::daily::Challenge;
 
 pub struct GameplayPlugin;
 
@@ -20,54 +37,252 @@ impl GameplayPlugin {
     const SNAKE_BODY_Z_DEPTH: f32 = 99.0;
     const FOOD_Z_DEPTH: f32 = 50.0;
     const BOMB_Z_DEPTH: f32 = 51.0;
+    const ENEMY_Z_DEPTH: f32 = 98.0;
     const WALL_Z_DEPTH: f32 = 200.0;
+    const PORTAL_Z_DEPTH: f32 = 40.0;
+    const TERRAIN_Z_DEPTH: f32 = 30.0;
     const BACKGROUND_Z_DEPTH: f32 = 0.0;
 }
 
+// the running game, the gameplay systems only pass input to it
+// and keep the sprites in sync with it
+#[derive(Resource, Deref, DerefMut)]
+pub struct GameSimulation(pub Simulation);
+
+// what the next game should be like, a game with a random seed is started without it
+#[derive(Resource, Clone)]
+pub struct NewGame {
+    pub seed: u64,
+    pub rules: Rules,
+    pub level: Level,
+    pub players: usize,
+}
+
+// the number of snakes on the keyboard, kept from one game to the next
+#[derive(Resource, Clone)]
+pub struct GameSetup {
+    pub players: usize,
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        GameSetup { players: 1 }
+    }
+}
+
+pub const MAX_LOCAL_PLAYERS: usize = LOCAL_PLAYERS;
+
+// how the local games are played, kept from one game to the next,
+// a time attack game also ends when the time is up
+// and a campaign level when its goal is reached,
+// the daily challenge of a day is the same game for everybody,
+// a puzzle only moves when the player does
+// and a level being made in the editor goes back to it when the game is over
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    TimeAttack,
+    Campaign { level: usize },
+    Daily { day: u64 },
+    Puzzle { level: usize },
+    Editor,
+}
+
+impl GameMode {
+    // a clock every 15 seconds in time attack games
+    const CLOCK_SPAWN_TICKS: u64 = 15000 / TICK_MILLIS;
+
+    pub fn rules(self, rules: Rules) -> Rules {
+        match self {
+            GameMode::Classic | GameMode::Editor => rules,
+            GameMode::TimeAttack => Rules { clock_spawn_ticks: GameMode::CLOCK_SPAWN_TICKS, ..rules },
+            GameMode::Campaign { level } => campaign::levels()[level].rules.clone().unwrap_or(rules),
+            GameMode::Daily { day } => Challenge::for_day(day).difficulty.rules,
+            GameMode::Puzzle { .. } => puzzle::rules(),
+        }
+    }
+
+    pub fn seed(self) -> Option<u64> {
+        match self {
+            GameMode::Daily { day } => Some(Challenge::for_day(day).seed),
+            _ => None,
+        }
+    }
+
+    // the difficulty chosen in the settings is used when the mode does not decide it
+    pub fn difficulty(self) -> Option<Difficulty> {
+        match self {
+            GameMode::Daily { day } => Some(Challenge::for_day(day).difficulty),
+            _ => None,
+        }
+    }
+
+    // the walls of the game, when the mode decides them
+    pub fn level(self) -> Option<Level> {
+        match self {
+            GameMode::Campaign { level } => Some(campaign::levels()[level].level()),
+            GameMode::Daily { day } => Some(Challenge::for_day(day).level),
+            GameMode::Puzzle { level } => Some(puzzle::puzzles()[level].level.clone()),
+            _ => None,
+        }
+    }
+
+    // puts what the mode decides on the new game
+    pub fn prepare(self, simulation: &mut Simulation) {
+        if let GameMode::Puzzle { level } = self {
+            puzzle::puzzles()[level].place(simulation);
+        }
+    }
+
+    pub fn is_turn_based(self) -> bool {
+        matches!(self, GameMode::Puzzle { .. })
+    }
+
+    pub fn game_over_state(self) -> AppState {
+        if self == GameMode::Editor { AppState::Editor } else { AppState::GameOver }
+    }
+}
+
+// the systems running the game, both when it is played and when a replay is shown
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameplaySet;
+
+// decides when the simulation steps, can be paused, and sped up for replays
+// and as the local games get harder
+#[derive(Resource)]
+pub struct GameClock {
+    timer: Timer,
+    pub speed: f32,
+    pub paused: bool,
+    // a manual clock only steps when it is told to
+    pub manual: bool,
+    single_steps: u32,
+    // how long the game has been played, without the pauses
+    played: Duration,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock {
+            timer: Timer::new(Duration::from_millis(TICK_MILLIS), TimerMode::Repeating),
+            speed: 1.0,
+            paused: false,
+            manual: false,
+            single_steps: 0,
+            played: Duration::ZERO,
+        }
+    }
+}
+
+impl GameClock {
+    pub fn step_once(&mut self) {
+        self.single_steps += 1;
+    }
+
+    pub fn played(&self) -> Duration {
+        self.played
+    }
+
+    pub fn set_tick_duration(&mut self, duration: Duration) {
+        if self.timer.duration() != duration {
+            self.timer.set_duration(duration);
+        }
+    }
+
+    // how many steps the simulation should take on this frame
+    pub fn update(&mut self, delta: Duration) -> u32 {
+        if self.paused || self.manual {
+            return std::mem::take(&mut self.single_steps);
+        }
+        self.played += delta.mul_f32(self.speed);
+        self.timer.tick(delta.mul_f32(self.speed));
+        self.timer.times_finished_this_tick()
+    }
+}
+
 impl Plugin for GameplayPlugin {
     fn build(&self, app: &mut App) {
         let particle_system = ParticleSystem::new();
         app
-            .add_systems((spawn_background_system, spawn_walls_system, spawn_snake_system)
-                .in_schedule(OnEnter(AppState::Gameplay)))
-            .add_systems((control_snake_system, sprite_animation_system)
+            .add_event::<GameEvent>()
+            .init_resource::<GameSetup>()
+            .init_resource::<GameMode>()
+            .init_resource::<LaunchConfig>()
+            .init_resource::<DifficultySettings>()
+            .init_resource::<LevelSettings>()
+            .configure_set(GameplaySet.run_if(game_running))
+            .add_systems((pause_system, control_snake_system, speed_up_system)
+                .before(step_simulation_system)
                 .in_set(OnUpdate(AppState::Gameplay)))
-            .add_system(wall_collision_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(grow_snake_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(move_snake_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(food_collision_system.after(move_snake_system)
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(bomb_collision_system.after(move_snake_system)
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(snake_body_collision_system.after(move_snake_system)
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(bomb_timer_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(update_particles_system.after(bomb_timer_system)
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(death_delay_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(200))))
-            .add_system(spawn_food_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(5000))))
-            .add_system(spawn_bomb_system
-                .in_set(OnUpdate(AppState::Gameplay))
-                .run_if(on_timer(Duration::from_millis(27000))))
-            .add_system(despawn_gameplay_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_systems((sprite_animation_system, death_delay_system)
+                .in_set(GameplaySet))
+            // online games are stepped by the server
+            .add_system(step_simulation_system
+                .run_if(not(in_state(AppState::Online)))
+                .in_set(GameplaySet))
+            .add_systems((sync_snake_system, sync_food_system, sync_bomb_system, sync_clock_system, sync_enemy_system,
+                          explosion_system, snake_death_system)
+                .after(step_simulation_system)
+                .in_set(GameplaySet))
+            .add_system(update_particles_system
+                .in_set(GameplaySet)
+                .run_if(on_timer(Duration::from_millis(TICK_MILLIS))))
             .insert_resource(particle_system);
+
+        for state in [AppState::Gameplay, AppState::Replay, AppState::Online] {
+            app
+                .add_systems((start_simulation_system, apply_system_buffers,
+                              spawn_background_system, spawn_walls_system, spawn_portals_system, spawn_terrain_system, spawn_snake_system)
+                    .chain()
+                    .in_schedule(OnEnter(state.clone())))
+                .add_system(despawn_gameplay_system.in_schedule(OnExit(state)));
+        }
+    }
+}
+
+fn game_running(state: Res<State<AppState>>) -> bool {
+    matches!(state.0, AppState::Gameplay | AppState::Replay | AppState::Online)
+}
+
+// a replay that is about to be shown, or an online game that has started,
+// decides the seed and rules of the game
+// and the options the game was launched with set up the other local games,
+// which are played at the difficulty and on the level chosen
+fn start_simulation_system(mut commands: Commands,
+                           (setup, mode, levels): (Res<GameSetup>, Res<GameMode>, Res<LevelSettings>),
+                           launch: Res<LaunchConfig>,
+                           difficulty: Res<DifficultySettings>,
+                           playback: Option<Res<ReplayPlayback>>,
+                           online: Option<Res<OnlineGame>>,
+                           new_game: Option<Res<NewGame>>) {
+    let local = playback.is_none() && online.is_none();
+    let difficulty = mode.difficulty().unwrap_or_else(|| difficulty.difficulty());
+    let mut simulation = match (playback, online, new_game) {
+        (Some(playback), _, _) => playback.replay.new_simulation(),
+        (None, Some(online), _) => online.game.predicted().clone(),
+        (None, None, Some(new_game)) => Simulation::with_level(new_game.seed, new_game.rules.clone(),
+                                                               new_game.level.clone(), new_game.players),
+        (None, None, None) => {
+            let seed = mode.seed().or(launch.seed).unwrap_or_else(|| rand::thread_rng().gen());
+            let level = mode.level().or_else(|| launch.level(seed)).unwrap_or_else(|| levels.level(seed));
+            Simulation::with_level(seed, mode.rules(difficulty.rules.clone()), level, setup.players)
+        }
+    };
+    commands.remove_resource::<NewGame>();
+    debug!("Starting a new game with seed {}", simulation.seed());
+    let mut clock = GameClock::default();
+    if local {
+        mode.prepare(&mut simulation);
+        clock.manual = mode.is_turn_based();
+        debug!("Playing on {}: {}", difficulty.name, difficulty.describe().replace('\n', ", "));
+        // the daily challenge is played at the same speed by everybody
+        clock.speed = if matches!(*mode, GameMode::Daily { .. }) { 1.0 } else { launch.speed.unwrap_or(1.0) };
+        clock.set_tick_duration(difficulty.tick_duration(&simulation, Duration::ZERO));
+        commands.insert_resource(difficulty);
     }
+    commands.insert_resource(GameSimulation(simulation));
+    commands.insert_resource(clock);
 }
 
 fn spawn_background_system(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -86,19 +301,12 @@ fn spawn_background_system(mut commands: Commands, asset_server: Res<AssetServer
         }).insert(BackgroundImage);
 }
 
-fn spawn_walls_system(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn spawn_walls_system(mut commands: Commands,
+                      asset_server: Res<AssetServer>,
+                      simulation: Res<GameSimulation>) {
     debug!("Running spawn walls system");
-    for x in 0..=GRID_WIDTH {
-        for y in [0, GRID_HEIGHT] {
-            let position = GridPosition::new(x, y);
-            spawn_wall(&mut commands, &asset_server, position);
-        }
-    }
-    for y in 0..GRID_HEIGHT {
-        for x in [0, GRID_WIDTH] {
-            let position = GridPosition::new(x, y);
-            spawn_wall(&mut commands, &asset_server, position);
-        }
+    for position in simulation.walls() {
+        spawn_wall(&mut commands, &asset_server, *position);
     }
 }
 
@@ -120,84 +328,176 @@ fn spawn_wall(commands: &mut Commands, asset_server: &Res<AssetServer>, position
         .insert(Wall::new());
 }
 
-fn spawn_snake_system(mut commands: Commands) {
+fn spawn_portals_system(mut commands: Commands, simulation: Res<GameSimulation>) {
+    for (index, (first, second)) in simulation.level().portals.iter().enumerate() {
+        for position in [*first, *second] {
+            let screen_pos = ScreenPosition::from(position);
+            for bundle in Portal::sprites(Portal::color(index)) {
+                let translation = Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::PORTAL_Z_DEPTH) + bundle.transform.translation;
+                commands.spawn(SpriteBundle { transform: Transform::from_translation(translation), ..bundle })
+                    .insert(position)
+                    .insert(Portal::new());
+            }
+        }
+    }
+}
+
+fn spawn_terrain_system(mut commands: Commands, simulation: Res<GameSimulation>) {
+    for (position, kind) in &simulation.level().terrain {
+        let screen_pos = ScreenPosition::from(*position);
+        for bundle in terrain::sprites(*kind) {
+            let translation = Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::TERRAIN_Z_DEPTH) + bundle.transform.translation;
+            let entity = terrain::spawn(&mut commands, *kind, SpriteBundle { transform: Transform::from_translation(translation), ..bundle });
+            commands.entity(entity).insert(*position);
+        }
+    }
+}
+
+fn spawn_snake_system(mut commands: Commands, simulation: Res<GameSimulation>) {
     debug!("Running spawn snake system");
-    let position = GridPosition::new(GRID_WIDTH / 2, GRID_HEIGHT / 2);
-    commands
-        .spawn(SpriteBundle {
-            sprite: Sprite {
-                color: SnakeHead::HEAD_COLOR,
-                ..default()
-            },
-            transform: Transform {
-                scale: Vec3::new(GRID_SIZE, GRID_SIZE, 1.0),
-                translation: Vec3::new(0.0, 0.0, GameplayPlugin::SNAKE_HEAD_Z_DEPTH),
+    for (player, snake) in simulation.snakes().iter().enumerate() {
+        let position = snake.head();
+        let screen_pos = ScreenPosition::from(position);
+        commands
+            .spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: SnakeHead::PLAYER_COLORS[player],
+                    ..default()
+                },
+                transform: Transform {
+                    scale: Vec3::new(GRID_SIZE, GRID_SIZE, 1.0),
+                    translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::SNAKE_HEAD_Z_DEPTH),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        })
-        .insert(SnakeHead::new())
-        .insert(position);
+            })
+            .insert(SnakeHead::new())
+            .insert(PlayerId(player))
+            .insert(position);
+    }
 }
 
-fn control_snake_system(keyboard_input: Res<Input<KeyCode>>, mut q: Query<&mut SnakeHead>) {
-    let mut snake = q.single_mut();
+// only a game on this computer can wait for the player
+fn pause_system(mut input: EventReader<GameInput>,
+                mut clock: ResMut<GameClock>) {
+    for event in input.iter() {
+        if *event == GameInput::Pause {
+            clock.paused = !clock.paused;
+        }
+    }
+}
 
-    if snake.next_turn {
+// a turn made while the last one still waits for its tick is kept for the next tick,
+// and a relative turn turns from where the snake is heading after the turn waiting
+fn control_snake_system(mut input: EventReader<GameInput>,
+                        bindings: Res<Bindings>,
+                        clock: Res<GameClock>,
+                        mut pending: Local<Vec<Option<Direction>>>,
+                        mut simulation: ResMut<GameSimulation>) {
+    // the turn-based games move the snake themselves
+    if clock.manual {
         return;
     }
-
-    if keyboard_input.pressed(KeyCode::Left) {
-        snake.turn(crate::common::Direction::Left);
+    // nothing is kept for the next game
+    if simulation.is_over() {
+        pending.clear();
     }
-    else if keyboard_input.pressed(KeyCode::Right) {
-        snake.turn(crate::common::Direction::Right);
+    pending.resize(simulation.players(), None);
+    for event in input.iter() {
+        if let GameInput::Turn { player, direction } = event {
+            if let Some(pending) = pending.get_mut(*player) {
+                let heading = pending.unwrap_or(simulation.snake(*player).direction);
+                if let Some(direction) = bindings.steering(*player).direction(*direction, heading) {
+                    *pending = Some(direction);
+                }
+            }
+        }
     }
-    else if keyboard_input.pressed(KeyCode::Down) {
-        snake.turn(crate::common::Direction::Down);
+    for (player, pending) in pending.iter_mut().enumerate() {
+        if simulation.snake(player).next_turn {
+            continue;
+        }
+        if let Some(direction) = pending.take() {
+            simulation.turn(player, direction);
+        }
     }
-    else if keyboard_input.pressed(KeyCode::Up) {
-        snake.turn(crate::common::Direction::Up);
+}
+
+// the snakes speed up as the game goes on
+fn speed_up_system(difficulty: Res<Difficulty>,
+                   simulation: Res<GameSimulation>,
+                   mut clock: ResMut<GameClock>) {
+    let duration = difficulty.tick_duration(&simulation, clock.played());
+    clock.set_tick_duration(duration);
+}
+
+pub fn step_simulation_system(time: Res<Time>,
+                          mut clock: ResMut<GameClock>,
+                          mut simulation: ResMut<GameSimulation>,
+                          mut playback: Option<ResMut<ReplayPlayback>>,
+                          mut events: EventWriter<GameEvent>) {
+    for _ in 0..clock.update(time.delta()) {
+        let step_events = match playback.as_mut() {
+            Some(playback) => playback.playback.step(&mut simulation),
+            None => simulation.step(),
+        };
+        events.send_batch(step_events);
     }
 }
 
-// move last body piece where the head is (before the move)
-// move the head one step to current direction
-fn move_snake_system(mut head_q: Query<(&mut GridPosition, &mut Transform, &mut SnakeHead)>,
-                     mut body_q: Query<(&mut GridPosition, &mut Transform), (With<SnakeBodyPiece>, Without<SnakeHead>)>) {
-    // the last body piece to the front
-    let (mut head_position, mut transform, mut snake) = head_q.single_mut();
-    if let Some(entity) = snake.get_last_body_piece() {
-        if let Ok((mut position, mut transform)) = body_q.get_mut(entity) {
-            *position = *head_position;
-            let screen_pos = ScreenPosition::from(*position);
-            transform.translation.x = screen_pos.x;
-            transform.translation.y = screen_pos.y;
-            snake.move_last_body_piece_to_front();
+// move the head and body sprites to where the snakes are,
+// adding and removing body pieces when the length has changed
+fn sync_snake_system(mut commands: Commands,
+                     simulation: Res<GameSimulation>,
+                     mut head_q: Query<(&PlayerId, &mut GridPosition, &mut Transform, &mut Sprite, &mut SnakeHead)>,
+                     mut body_q: Query<(&mut GridPosition, &mut Transform, &mut Sprite), (With<SnakeBodyPiece>, Without<SnakeHead>)>) {
+    for (player, mut head_position, mut transform, mut sprite, mut head) in &mut head_q {
+        let snake = simulation.snake(player.0);
+        set_grid_position(&mut head_position, &mut transform, snake.head());
+
+        while head.body().len() + 1 < snake.len() {
+            let position = snake.body[head.body().len() + 1];
+            debug!("Spawning new snake body piece at position: {}", position);
+            let entity = spawn_body_piece(&mut commands, *player, position);
+            head.add_body_piece(entity);
+        }
+        while head.body().len() + 1 > snake.len() {
+            if let Some(entity) = head.remove_body_piece() {
+                commands.entity(entity).despawn();
+            }
         }
-    }
 
-    // move snake head one step forward
-    head_position.move_position(snake.direction, 1);
-    let screen_pos = ScreenPosition::from(*head_position);
-    transform.translation.x = screen_pos.x;
-    transform.translation.y = screen_pos.y;
-    snake.next_turn = false;
+        // a snake out of a multiplayer game stays on the board, greyed out
+        let dead = !snake.is_alive() && simulation.players() > 1;
+        if dead {
+            sprite.color = SnakeHead::DEAD_COLOR;
+        }
+        for (entity, piece_position) in head.body().iter().zip(snake.body.iter().skip(1)) {
+            if let Ok((mut position, mut transform, mut sprite)) = body_q.get_mut(*entity) {
+                set_grid_position(&mut position, &mut transform, *piece_position);
+                if dead {
+                    sprite.color = SnakeHead::DEAD_COLOR;
+                }
+            }
+        }
+    }
 }
 
-fn grow_snake_system(mut commands: Commands,
-                     mut q: Query<(&mut SnakeHead, &GridPosition)>) {
-    let (mut snake, position) = q.single_mut();
-    if !snake.can_grow() {
-        return;
+fn set_grid_position(position: &mut GridPosition, transform: &mut Transform, new_position: GridPosition) {
+    if *position != new_position {
+        *position = new_position;
+        let screen_pos = ScreenPosition::from(new_position);
+        transform.translation.x = screen_pos.x;
+        transform.translation.y = screen_pos.y;
     }
+}
 
-    debug!("Spawning new snake body piece at position: {}", *position);
-    let screen_pos = ScreenPosition::from(*position);
-    let entity = commands.spawn(
+fn spawn_body_piece(commands: &mut Commands, player: PlayerId, position: GridPosition) -> Entity {
+    let screen_pos = ScreenPosition::from(position);
+    commands.spawn(
         SpriteBundle {
             sprite: Sprite {
-                color: SnakeBodyPiece::BODY_COLOR,
+                color: SnakeBodyPiece::PLAYER_COLORS[player.0],
                 ..default()
             },
             transform: Transform {
@@ -210,131 +510,205 @@ fn grow_snake_system(mut commands: Commands,
             },
             ..default()
         })
-        .insert(*position)
+        .insert(position)
         .insert(SnakeBodyPiece::new())
-        .id();
-    snake.add_body_piece(entity);
+        .insert(player)
+        .id()
 }
 
-fn spawn_food_system(mut commands: Commands,
-                     asset_server: Res<AssetServer>,
-                     query: Query<&GridPosition>) {
-    let position = find_free_position(query);
-    let screen_pos = ScreenPosition::from(position);
-    let food = Food::random();
-    debug!("Spawning food at position: {}", position);
-    commands.spawn(
-        SpriteBundle {
-            texture: asset_server.load(food.asset.as_str()),
-            transform: Transform {
-                translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::FOOD_Z_DEPTH),
-                ..default()
-            },
-            ..default()
-        })
-        .insert(food)
-        .insert(position);
-}
+fn sync_food_system(mut commands: Commands,
+                    asset_server: Res<AssetServer>,
+                    simulation: Res<GameSimulation>,
+                    query: Query<(Entity, &Food)>) {
+    for (entity, food) in query.iter() {
+        if !simulation.foods().iter().any(|item| item.id == food.id) {
+            debug!("Despawning food {}", food.id);
+            commands.entity(entity).despawn();
+        }
+    }
 
-fn spawn_bomb_system(mut commands: Commands,
-                     asset_server: Res<AssetServer>,
-                     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-                     query: Query<&GridPosition>) {
-    let position = find_free_position(query);
-    let screen_pos = ScreenPosition::from(position);
-    let bomb = Bomb::default();
-    let scale_factor = 3.0;
-    debug!("Spawning a bomb at position: {}", position);
-
-    let texture_handle = asset_server.load("bomb_spritesheet.png");
-    let texture_atlas = TextureAtlas::from_grid(
-        texture_handle,
-        Vec2::new(30.0, 30.0), 4, 1, None, None);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    commands.spawn((
-        SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle,
-            transform: Transform {
-                scale: Vec3::new(scale_factor, scale_factor, 1.0),
-                translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::BOMB_Z_DEPTH),
+    for item in simulation.foods() {
+        if query.iter().any(|(_, food)| food.id == item.id) {
+            continue;
+        }
+        let screen_pos = ScreenPosition::from(item.position);
+        let food = Food::new(item.id, item.kind);
+        debug!("Spawning food at position: {}", item.position);
+        commands.spawn(
+            SpriteBundle {
+                texture: asset_server.load(food.asset.as_str()),
+                transform: Transform {
+                    translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::FOOD_Z_DEPTH),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-    ))
-    .insert(bomb)
-    .insert(position);
+            })
+            .insert(food)
+            .insert(item.position);
+    }
 }
 
-fn wall_collision_system(mut state: ResMut<NextState<AppState>>,
-                         snake_pos_q: Query<&GridPosition, With<SnakeHead>>) {
-    let snake_position = snake_pos_q.single();
-
-    if (snake_position.x <= 0) || (snake_position.x >= GRID_WIDTH) {
-        state.set(AppState::GameOver);
+fn sync_bomb_system(mut commands: Commands,
+                    asset_server: Res<AssetServer>,
+                    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+                    simulation: Res<GameSimulation>,
+                    query: Query<(Entity, &Bomb)>) {
+    for (entity, bomb) in query.iter() {
+        if !simulation.bombs().iter().any(|item| item.id == bomb.id) {
+            debug!("Despawning bomb {}", bomb.id);
+            commands.entity(entity).despawn();
+        }
     }
-    if (snake_position.y <= 0) || (snake_position.y >= GRID_HEIGHT) {
-        state.set(AppState::GameOver);
+
+    for item in simulation.bombs() {
+        if query.iter().any(|(_, bomb)| bomb.id == item.id) {
+            continue;
+        }
+        let screen_pos = ScreenPosition::from(item.position);
+        let scale_factor = 3.0;
+        debug!("Spawning a bomb at position: {}", item.position);
+
+        let texture_handle = asset_server.load("bomb_spritesheet.png");
+        let texture_atlas = TextureAtlas::from_grid(
+            texture_handle,
+            Vec2::new(30.0, 30.0), 4, 1, None, None);
+        let texture_atlas_handle = texture_atlases.add(texture_atlas);
+        commands.spawn((
+            SpriteSheetBundle {
+                texture_atlas: texture_atlas_handle,
+                transform: Transform {
+                    scale: Vec3::new(scale_factor, scale_factor, 1.0),
+                    translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::BOMB_Z_DEPTH),
+                    ..default()
+                },
+                ..default()
+            },
+            AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+        ))
+        .insert(Bomb::new(item.id))
+        .insert(item.position);
     }
 }
 
-fn food_collision_system(mut commands: Commands,
-                         mut snake_query: Query<(&mut SnakeHead, &GridPosition)>,
-                         food_query: Query<(Entity, &Food, &GridPosition), With<Food>>) {
-    let (mut snake, snake_position) = snake_query.single_mut();
-
-    for (entity, food, food_position) in food_query.iter() {
-        if snake_position == food_position {
-            debug!("Food eaten. Despawning a food at position: {}", food_position);
-            snake.grow(food.value);
+fn sync_clock_system(mut commands: Commands,
+                     simulation: Res<GameSimulation>,
+                     query: Query<(Entity, &Clock)>) {
+    for (entity, clock) in query.iter() {
+        if !simulation.clocks().iter().any(|item| item.id == clock.id) {
+            debug!("Despawning clock {}", clock.id);
             commands.entity(entity).despawn();
         }
     }
-}
-
-fn bomb_collision_system(mut commands: Commands,
-                         mut snake_position_query: Query<&GridPosition, With<SnakeHead>>,
-                         bomb_query: Query<(Entity, &GridPosition), With<Bomb>>) {
-    let snake_position = snake_position_query.single_mut();
 
-    for (entity, bomb_position) in bomb_query.iter() {
-        if snake_position == bomb_position {
-            debug!("Bomb eaten. Despawning a bomb at position: {}", bomb_position);
-            commands.entity(entity).despawn();
+    for item in simulation.clocks() {
+        if query.iter().any(|(_, clock)| clock.id == item.id) {
+            continue;
         }
+        let screen_pos = ScreenPosition::from(item.position);
+        debug!("Spawning a clock at position: {}", item.position);
+        commands.spawn(
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Clock::COLOR,
+                    ..default()
+                },
+                transform: Transform {
+                    scale: Vec3::new(Clock::SIZE, Clock::SIZE, 1.0),
+                    translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::FOOD_Z_DEPTH),
+                    rotation: Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+                },
+                ..default()
+            })
+            .insert(Clock::new(item.id))
+            .insert(item.position);
     }
 }
 
-fn snake_body_collision_system(mut state: ResMut<NextState<AppState>>,
-                               snake_query: Query<(&SnakeHead, &GridPosition), With<SnakeHead>>,
-                               body_query: Query<&GridPosition, With<SnakeBodyPiece>>) {
-    let (snake, snake_position) = snake_query.single();
-    let mut next_position = snake_position.clone();
-    next_position.move_position(snake.direction, 1);
-    for body_position in body_query.iter() {
-        if next_position == *body_position {
-            state.set(AppState::GameOver);
+// the enemies move, so the ones still there are moved along
+fn sync_enemy_system(mut commands: Commands,
+                     simulation: Res<GameSimulation>,
+                     mut query: Query<(Entity, &Enemy, &mut GridPosition, &mut Transform)>) {
+    for (entity, enemy, mut position, mut transform) in &mut query {
+        match simulation.enemies().iter().find(|item| item.id == enemy.id) {
+            Some(item) => set_grid_position(&mut position, &mut transform, item.position),
+            None => {
+                debug!("Despawning enemy {}", enemy.id);
+                commands.entity(entity).despawn();
+            }
         }
     }
+
+    for item in simulation.enemies() {
+        if query.iter().any(|(_, enemy, _, _)| enemy.id == item.id) {
+            continue;
+        }
+        let screen_pos = ScreenPosition::from(item.position);
+        let (color, rotation) = match simulation.level().enemies[item.enemy] {
+            EnemyKind::Patrol(_) => (Enemy::PATROL_COLOR, Quat::IDENTITY),
+            EnemyKind::Chaser(_) => (Enemy::CHASER_COLOR, Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+        };
+        debug!("Spawning an enemy at position: {}", item.position);
+        commands.spawn(
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    ..default()
+                },
+                transform: Transform {
+                    scale: Vec3::new(Enemy::SIZE, Enemy::SIZE, 1.0),
+                    translation: Vec3::new(screen_pos.x, screen_pos.y, GameplayPlugin::ENEMY_Z_DEPTH),
+                    rotation,
+                },
+                ..default()
+            })
+            .insert(Enemy::new(item.id))
+            .insert(item.position);
+    }
 }
 
-fn bomb_timer_system(mut commands: Commands,
-                     mut particle_system: ResMut<ParticleSystem>,
-                     snake_query: Query<&GridPosition, Or<(&SnakeHead, &SnakeBodyPiece)>>,
-                     mut bomb_query: Query<(Entity, &mut Bomb, &GridPosition), With<Bomb>>,
-                     time: Res<Time>) {
-    for (entity, mut bomb, position) in bomb_query.iter_mut() {
-        bomb.timer.tick(time.delta());
-        if bomb.timer.finished() {
+fn explosion_system(mut commands: Commands,
+                    mut particle_system: ResMut<ParticleSystem>,
+                    mut events: EventReader<GameEvent>) {
+    for event in events.iter() {
+        if let GameEvent::BombExploded { position } = event {
             debug!("Bomb exploded at position: {}", position);
             particle_system.create_explosion(&mut commands, ScreenPosition::from(*position));
-            check_if_snake_exploded(&mut commands, &snake_query, position);
-            commands.entity(entity).despawn();
         }
     }
 }
 
+// when a bomb is exploding, and snake is next to it,
+// wait a while and show the effect on the screen before ending the game
+fn snake_death_system(mut commands: Commands,
+                      mut state: ResMut<NextState<AppState>>,
+                      mode: Res<GameMode>,
+                      simulation: Res<GameSimulation>,
+                      mut events: EventReader<GameEvent>) {
+    let mut died = false;
+    let mut exploded = false;
+    for event in events.iter() {
+        if let GameEvent::SnakeDied { player, cause } = event {
+            debug!("Snake {} died: {}", player, cause);
+            died = true;
+            exploded |= *cause == DeathCause::Explosion;
+        }
+    }
+    // with more snakes the game goes on until only one is left
+    if !died || !simulation.is_over() {
+        return;
+    }
+    if exploded {
+        commands.spawn(
+            SpriteBundle {
+                ..default()
+            })
+            .insert(DeathTimer::new());
+    }
+    else {
+        state.set(mode.game_over_state());
+    }
+}
+
 fn update_particles_system(commands: Commands,
                            query: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
                            mut particle_system: ResMut<ParticleSystem>) {
@@ -355,23 +729,22 @@ fn sprite_animation_system(
     }
 }
 
-// when a bomb is exploding, and snake is next to it,
-// wait a while and show the effect on the screen before ending the game
 fn death_delay_system(mut state: ResMut<NextState<AppState>>,
+                      mode: Res<GameMode>,
                       mut query: Query<&mut DeathTimer>,
                       time: Res<Time>) {
     for mut timer in &mut query {
         timer.tick(time.delta());
         if timer.finished() {
-            state.set(AppState::GameOver);
+            state.set(mode.game_over_state());
         }
     }
 }
 
 fn despawn_gameplay_system(mut commands: Commands,
                            mut particle_system: ResMut<ParticleSystem>,
-                           query: Query<Entity, Or<(&Food, &SnakeHead, &SnakeBodyPiece, &Bomb)>>) {
-    // notice that Walls and BackgroundImage are not cleaned up
+                           query: Query<Entity, Or<(&Food, &SnakeHead, &SnakeBodyPiece, &Bomb, &Clock, &Enemy, &DeathTimer)>>) {
+    // notice that Walls, Portals, the terrain and BackgroundImage are not cleaned up
     // GameOver system will cleanup everything
     debug!("Running despawn gameplay system");
     for entity in query.iter() {
@@ -379,35 +752,3 @@ fn despawn_gameplay_system(mut commands: Commands,
     }
     particle_system.despawn_particles(commands);
 }
-
-// a bad way of finding a free position from the grid
-// by randomly testing for a free positions
-fn find_free_position(query: Query<&GridPosition>) -> GridPosition {
-    let mut position = GridPosition::random(GRID_WIDTH, GRID_HEIGHT);
-    loop {
-        if query.iter().find_map(|p| if *p == position { Some(p) } else { None }) == None {
-            break;
-        }
-        position = GridPosition::random(GRID_WIDTH, GRID_HEIGHT);
-    }
-    position
-}
-
-fn check_if_snake_exploded(commands: &mut Commands,
-                           snake_query: &Query<&GridPosition, Or<(&SnakeHead, &SnakeBodyPiece)>>,
-                           bomb_position: &GridPosition) {
-    debug!("Checking if snake exploded...");
-    for snake_piece_position in snake_query.iter() {
-        debug!("Snake pos: {}, Bomb pos: {}", snake_piece_position, bomb_position);
-        if ((snake_piece_position.x as i32 - bomb_position.x as i32).abs() <= 1) &&
-            ((snake_piece_position.y as i32 - bomb_position.y as i32).abs() <= 1) {
-            debug!("Snake is in the explosion zone!");
-            commands.spawn(
-                SpriteBundle {
-                    ..default()
-                })
-                .insert(DeathTimer::default());
-            break;
-        }
-    }
-}
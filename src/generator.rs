@@ -0,0 +1,273 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::prelude::*;
+
+use crate::common::{Direction, GridPosition, GRID_HEIGHT, GRID_WIDTH};
+use crate::level::{Level, portal_exit};
+use crate::simulation::{start_position, MAX_PLAYERS};
+
+// levels made from a seed: mazes, rooms joined by corridors and scattered obstacles
+//
+// a generated level is named after its kind and seed, like maze-42,
+// so the name is enough to make the same level again for replays and online games
+//
+// the rows the snakes start on are kept clear and joined to each other,
+// and the pockets that can not be reached from the start are walled up,
+// so food never appears where the snakes can not get to it
+
+pub const GENERATED_LEVELS: [&str; 3] = ["maze", "rooms", "obstacles"];
+
+// how far ahead of a snake is kept clear at the start
+const CLEAR_AHEAD: u32 = 5;
+
+const MIN_ROOMS: usize = 6;
+
+pub fn generate(kind: &str, seed: u64) -> Option<Level> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut walls = match kind {
+        "maze" => maze(&mut rng),
+        "rooms" => rooms(&mut rng),
+        "obstacles" => obstacles(&mut rng),
+        _ => return None,
+    };
+    clear_starts(&mut walls);
+    let (start, _) = start_position(0, 1);
+    let reachable = reachable(&walls, &[], start);
+    walls.extend(inside().filter(|position| !reachable.contains(position)));
+
+    let mut walls: Vec<GridPosition> = walls.into_iter().collect();
+    walls.sort_by_key(|position| (position.x, position.y));
+    Some(Level { name: format!("{}-{}", kind, seed), walls, ..Level::default() })
+}
+
+// any kind of generated level, chosen by the seed as well
+pub fn random(seed: u64) -> Level {
+    let kind = GENERATED_LEVELS[StdRng::seed_from_u64(seed).gen_range(0..GENERATED_LEVELS.len())];
+    generate(kind, seed).expect("the generated levels can be generated")
+}
+
+// whether every place inside the border that is not a wall can be reached from the start
+pub fn is_connected(level: &Level) -> bool {
+    unreachable(level).is_empty()
+}
+
+// the places inside the border that are not walls and can not be reached from the start
+pub fn unreachable(level: &Level) -> Vec<GridPosition> {
+    let walls: HashSet<GridPosition> = level.walls.iter().copied().collect();
+    let (start, _) = level.start.unwrap_or_else(|| start_position(0, 1));
+    let reachable = reachable(&walls, &level.portals, start);
+    inside().filter(|position| !walls.contains(position) && !reachable.contains(position)).collect()
+}
+
+pub(crate) fn inside() -> impl Iterator<Item = GridPosition> {
+    (1..GRID_WIDTH).flat_map(|x| (1..GRID_HEIGHT).map(move |y| GridPosition::new(x, y)))
+}
+
+pub(crate) fn is_inside(position: GridPosition) -> bool {
+    (1..GRID_WIDTH).contains(&position.x) && (1..GRID_HEIGHT).contains(&position.y)
+}
+
+fn neighbours(position: GridPosition) -> impl Iterator<Item = GridPosition> {
+    Direction::ALL.into_iter()
+        .map(move |direction| {
+            let mut next = position;
+            next.move_position(direction, 1);
+            next
+        })
+        .filter(move |next| *next != position && is_inside(*next))
+}
+
+// where the snakes start in the games of one and of the most players
+fn starts() -> Vec<(GridPosition, Direction)> {
+    std::iter::once(start_position(0, 1))
+        .chain((0..MAX_PLAYERS).map(|player| start_position(player, MAX_PLAYERS)))
+        .collect()
+}
+
+// a portal is never stood on going into it, only coming out of its other end
+fn reachable(walls: &HashSet<GridPosition>, portals: &[(GridPosition, GridPosition)], start: GridPosition) -> HashSet<GridPosition> {
+    let mut reached = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(position) = queue.pop_front() {
+        for next in neighbours(position) {
+            let next = portal_exit(portals, next).unwrap_or(next);
+            if !walls.contains(&next) && reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+// the way ahead of every start is cleared, and a start cut off from the first one
+// gets a corridor to it, first along its row and then up or down
+fn clear_starts(walls: &mut HashSet<GridPosition>) {
+    for (start, direction) in starts() {
+        let mut position = start;
+        walls.remove(&position);
+        for _ in 0..CLEAR_AHEAD {
+            position.move_position(direction, 1);
+            walls.remove(&position);
+        }
+    }
+    let (first, _) = start_position(0, 1);
+    for (start, _) in starts() {
+        if reachable(walls, &[], first).contains(&start) {
+            continue;
+        }
+        let mut position = start;
+        while position != first {
+            let direction = if position.x < first.x { Direction::Right }
+                else if position.x > first.x { Direction::Left }
+                else if position.y < first.y { Direction::Up }
+                else { Direction::Down };
+            position.move_position(direction, 1);
+            walls.remove(&position);
+        }
+    }
+}
+
+// a maze of corridors one wide, with the passages at odd places and the walls between them,
+// every dead end is opened up so that a long snake is never trapped
+fn maze(rng: &mut StdRng) -> HashSet<GridPosition> {
+    let mut walls: HashSet<GridPosition> = inside().collect();
+    let cells: Vec<GridPosition> = inside().filter(|position| position.x % 2 == 1 && position.y % 2 == 1).collect();
+    let cell_neighbours = |cell: GridPosition| -> Vec<(GridPosition, GridPosition)> {
+        Direction::ALL.into_iter()
+            .filter_map(|direction| {
+                let mut between = cell;
+                between.move_position(direction, 1);
+                let mut next = between;
+                next.move_position(direction, 1);
+                (next != between && is_inside(next)).then_some((between, next))
+            })
+            .collect()
+    };
+
+    let first = *cells.choose(rng).expect("the grid has room for a maze");
+    walls.remove(&first);
+    let mut path = vec![first];
+    while let Some(cell) = path.last().copied() {
+        let unvisited: Vec<(GridPosition, GridPosition)> = cell_neighbours(cell).into_iter()
+            .filter(|(_, next)| walls.contains(next))
+            .collect();
+        match unvisited.choose(rng) {
+            Some((between, next)) => {
+                walls.remove(between);
+                walls.remove(next);
+                path.push(*next);
+            }
+            None => {
+                path.pop();
+            }
+        }
+    }
+
+    for cell in cells {
+        let closed: Vec<GridPosition> = cell_neighbours(cell).into_iter()
+            .map(|(between, _)| between)
+            .filter(|between| walls.contains(between))
+            .collect();
+        if closed.len() + 1 >= cell_neighbours(cell).len() {
+            if let Some(between) = closed.choose(rng) {
+                walls.remove(between);
+            }
+        }
+    }
+    walls
+}
+
+// rooms joined in a ring by corridors, so every room has two ways out,
+// there are rooms until at least half of the field is open
+fn rooms(rng: &mut StdRng) -> HashSet<GridPosition> {
+    let mut walls: HashSet<GridPosition> = inside().collect();
+    let open_enough = walls.len() / 2;
+    let mut centers = Vec::new();
+    while centers.len() < MIN_ROOMS || walls.len() > open_enough {
+        let (width, height) = (rng.gen_range(4..=8), rng.gen_range(3..=5));
+        let left = rng.gen_range(1..GRID_WIDTH - width);
+        let bottom = rng.gen_range(1..GRID_HEIGHT - height);
+        for x in left..left + width {
+            for y in bottom..bottom + height {
+                walls.remove(&GridPosition::new(x, y));
+            }
+        }
+        centers.push(GridPosition::new(left + width / 2, bottom + height / 2));
+    }
+
+    for (index, from) in centers.iter().enumerate() {
+        let to = centers[(index + 1) % centers.len()];
+        // half of the corridors turn the other way round
+        let turn = if rng.gen_bool(0.5) { GridPosition::new(to.x, from.y) } else { GridPosition::new(from.x, to.y) };
+        for (start, end) in [(*from, turn), (turn, to)] {
+            for x in start.x.min(end.x)..=start.x.max(end.x) {
+                for y in start.y.min(end.y)..=start.y.max(end.y) {
+                    walls.remove(&GridPosition::new(x, y));
+                }
+            }
+        }
+    }
+    walls
+}
+
+// blocks of a few walls scattered around an open field
+fn obstacles(rng: &mut StdRng) -> HashSet<GridPosition> {
+    let mut walls = HashSet::new();
+    for _ in 0..rng.gen_range(20..=30) {
+        let (width, height) = if rng.gen_bool(0.5) { (rng.gen_range(1..=3), 1) } else { (1, rng.gen_range(1..=3)) };
+        let left = rng.gen_range(1..GRID_WIDTH - width);
+        let bottom = rng.gen_range(1..GRID_HEIGHT - height);
+        for x in left..left + width {
+            for y in bottom..bottom + height {
+                walls.insert(GridPosition::new(x, y));
+            }
+        }
+    }
+    walls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: std::ops::Range<u64> = 0..200;
+
+    fn assert_playable(level: &Level) {
+        assert!(is_connected(level), "{} has places that can not be reached", level.name);
+        assert_eq!(level.problems(), Vec::<String>::new(), "{} has problems", level.name);
+        for (start, direction) in starts() {
+            let mut position = start;
+            for _ in 0..=CLEAR_AHEAD {
+                assert!(!level.walls.contains(&position), "{} has a wall at {} ahead of a start", level.name, position);
+                position.move_position(direction, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_levels_are_playable() {
+        for kind in GENERATED_LEVELS {
+            for seed in SEEDS {
+                assert_playable(&generate(kind, seed).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn random_levels_are_playable() {
+        for seed in SEEDS {
+            let level = random(seed);
+            assert_eq!(Level::named(&level.name), Some(level.clone()));
+            assert_playable(&level);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_level() {
+        for kind in GENERATED_LEVELS {
+            assert_eq!(generate(kind, 42), generate(kind, 42));
+            assert_ne!(generate(kind, 42), generate(kind, 43));
+        }
+        assert_eq!(generate("caves", 42), None);
+    }
+}
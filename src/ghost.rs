@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+
+use crate::common::{AppState, GridPosition, ScreenPosition};
+use crate::common::GRID_SIZE;
+use crate::gameplay::{GameMode, GameSimulation, NewGame, step_simulation_system};
+use crate::replay::{Playback, Replay};
+use crate::simulation::Simulation;
+use crate::storage;
+
+// the modes raced against, with the files their best games are kept in
+pub const RACED_MODES: [(GameMode, &str); 2] = [
+    (GameMode::Classic, "best.snkr"),
+    (GameMode::TimeAttack, "best-time-attack.snkr"),
+];
+
+pub struct GhostPlugin;
+
+impl GhostPlugin {
+    const GHOST_Z_DEPTH: f32 = 98.0;
+    const GHOST_COLOR: Color = Color::rgba(0.8, 0.85, 1.0, 0.35);
+}
+
+// the highest scoring single player game played so far in each of the raced modes
+#[derive(Resource, Default)]
+pub struct BestReplays(Vec<(GameMode, Replay)>);
+
+impl BestReplays {
+    pub fn get(&self, mode: GameMode) -> Option<&Replay> {
+        self.0.iter().find(|(raced, _)| *raced == mode).map(|(_, replay)| replay)
+    }
+
+    fn set(&mut self, mode: GameMode, replay: Replay) {
+        self.0.retain(|(raced, _)| *raced != mode);
+        self.0.push((mode, replay));
+    }
+}
+
+// the best game played again next to the live one,
+// it has a simulation of its own so it never collides with anything
+#[derive(Resource)]
+pub struct GhostRace {
+    pub simulation: Simulation,
+    playback: Playback,
+    // a time attack game ends when the time is up, and so does its ghost
+    end: u64,
+    pieces: Vec<Entity>,
+}
+
+#[derive(Component)]
+struct GhostPiece;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(load_best_replay_system)
+            .add_system(race_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(step_ghost_system
+                .after(step_simulation_system)
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_systems((record_best_replay_system, despawn_ghost_system)
+                .in_schedule(OnExit(AppState::Gameplay)));
+    }
+}
+
+fn load_best_replay_system(mut commands: Commands) {
+    let mut best_replays = BestReplays::default();
+    for (mode, file) in RACED_MODES {
+        match storage::load(file).and_then(|data| Replay::decode(&data)) {
+            Ok(replay) => best_replays.set(mode, replay),
+            Err(error) => debug!("No personal best loaded from {}: {}", file, error),
+        }
+    }
+    commands.insert_resource(best_replays);
+}
+
+// the best games of the classic and time attack modes are raced against, each in its own mode
+fn record_best_replay_system(simulation: Res<GameSimulation>,
+                             mode: Res<GameMode>,
+                             mut best_replays: ResMut<BestReplays>) {
+    let Some((_, file)) = RACED_MODES.into_iter().find(|(raced, _)| *raced == *mode) else {
+        return;
+    };
+    if simulation.players() > 1 ||
+        best_replays.get(*mode).is_some_and(|best| best.result.score() >= simulation.snake(0).score) {
+        return;
+    }
+    let replay = Replay::record(&simulation);
+    debug!("New personal best in {:?}: {}", *mode, replay.result);
+    if let Err(error) = storage::save(file, &replay.encode()) {
+        error!("Failed to save the personal best: {}", error);
+    }
+    best_replays.set(*mode, replay);
+}
+
+// the race is played in the mode of the game just over,
+// with the seed, rules and level of the best game of that mode
+fn race_input_system(mut commands: Commands,
+                     mut state: ResMut<NextState<AppState>>,
+                     mode: Res<GameMode>,
+                     keyboard_input: Res<Input<KeyCode>>,
+                     best_replays: Res<BestReplays>) {
+    if let Some(replay) = best_replays.get(*mode) {
+        if keyboard_input.just_pressed(KeyCode::G) {
+            let simulation = replay.new_simulation();
+            commands.insert_resource(NewGame {
+                seed: replay.seed,
+                rules: replay.rules.clone(),
+                level: simulation.level().clone(),
+                players: 1,
+            });
+            commands.insert_resource(GhostRace {
+                simulation,
+                playback: Playback::new(replay),
+                end: replay.result.ticks,
+                pieces: Vec::new(),
+            });
+            state.set(AppState::Gameplay);
+        }
+    }
+}
+
+// the ghost keeps to the tick of the live game
+fn step_ghost_system(mut commands: Commands,
+                     simulation: Res<GameSimulation>,
+                     ghost: Option<ResMut<GhostRace>>,
+                     mut query: Query<(&mut GridPosition, &mut Transform), With<GhostPiece>>) {
+    let Some(mut ghost) = ghost else {
+        return;
+    };
+    let ghost = &mut *ghost;
+    while !ghost.simulation.is_over() && ghost.simulation.tick() < simulation.tick().min(ghost.end) {
+        ghost.playback.step(&mut ghost.simulation);
+    }
+
+    let body = &ghost.simulation.snake(0).body;
+    while ghost.pieces.len() < body.len() {
+        let position = body[ghost.pieces.len()];
+        ghost.pieces.push(spawn_ghost_piece(&mut commands, position));
+    }
+    while ghost.pieces.len() > body.len() {
+        if let Some(entity) = ghost.pieces.pop() {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (entity, piece_position) in ghost.pieces.iter().zip(body.iter()) {
+        if let Ok((mut position, mut transform)) = query.get_mut(*entity) {
+            if *position != *piece_position {
+                *position = *piece_position;
+                let screen_pos = ScreenPosition::from(*piece_position);
+                transform.translation.x = screen_pos.x;
+                transform.translation.y = screen_pos.y;
+            }
+        }
+    }
+}
+
+fn spawn_ghost_piece(commands: &mut Commands, position: GridPosition) -> Entity {
+    let screen_pos = ScreenPosition::from(position);
+    commands.spawn(
+        SpriteBundle {
+            sprite: Sprite {
+                color: GhostPlugin::GHOST_COLOR,
+                ..default()
+            },
+            transform: Transform {
+                scale: Vec3::new(GRID_SIZE, GRID_SIZE, 1.0),
+                translation: Vec3::new(screen_pos.x, screen_pos.y, GhostPlugin::GHOST_Z_DEPTH),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(position)
+        .insert(GhostPiece)
+        .id()
+}
+
+fn despawn_ghost_system(mut commands: Commands,
+                        query: Query<Entity, With<GhostPiece>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<GhostRace>();
+}
@@ -0,0 +1,118 @@
+use bevy::log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+// the best scores of a game mode, each table saved in a file of its own
+
+pub const MAX_HIGH_SCORES: usize = 10;
+
+// the game over screens only have room for the top of the table
+pub const SHOWN_HIGH_SCORES: usize = 5;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: u32,
+    pub length: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScores {
+    // the highest first
+    pub scores: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn load(name: &str) -> HighScores {
+        let scores = storage::load(name)
+            .and_then(|data| serde_json::from_slice::<HighScores>(&data).map_err(std::io::Error::from));
+        match scores {
+            Ok(mut scores) => {
+                scores.scores.sort_by_key(|high| std::cmp::Reverse(high.score));
+                scores.scores.truncate(MAX_HIGH_SCORES);
+                scores
+            }
+            Err(error) => {
+                debug!("No high scores loaded from {}: {}", name, error);
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self, name: &str) -> std::io::Result<()> {
+        let data = serde_json::to_vec_pretty(self).expect("high scores can always be serialized");
+        storage::save(name, &data)
+    }
+
+    // the place the score got in the table, none when it was not high enough,
+    // a score as high as an earlier one goes after it
+    pub fn insert(&mut self, score: HighScore) -> Option<usize> {
+        let place = self.scores.iter().position(|high| high.score < score.score).unwrap_or(self.scores.len());
+        if place >= MAX_HIGH_SCORES {
+            return None;
+        }
+        self.scores.insert(place, score);
+        self.scores.truncate(MAX_HIGH_SCORES);
+        Some(place)
+    }
+
+    // the table as text, with the place given marked
+    pub fn lines(&self, marked: Option<usize>) -> Vec<String> {
+        if self.scores.is_empty() {
+            return vec!["No scores yet".to_string()];
+        }
+        self.scores.iter()
+            .enumerate()
+            .map(|(place, high)| {
+                let mark = if Some(place) == marked { "  <" } else { "" };
+                format!("{:>2}. {:>5}   length {}{}", place + 1, high.score, high.length, mark)
+            })
+            .collect()
+    }
+
+    // the top of the table, with the place given after it when it is further down
+    pub fn top_lines(&self, marked: Option<usize>) -> Vec<String> {
+        let mut lines = self.lines(marked);
+        let shown = lines.len().min(SHOWN_HIGH_SCORES);
+        let last = marked.filter(|place| (shown..lines.len()).contains(place)).map(|place| lines.swap_remove(place));
+        lines.truncate(shown);
+        lines.extend(last);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(scores: &[u32]) -> HighScores {
+        let mut table = HighScores::default();
+        for score in scores {
+            table.insert(HighScore { score: *score, length: 3 });
+        }
+        table
+    }
+
+    #[test]
+    fn scores_go_in_their_place() {
+        let mut scores = table(&[10, 30, 20]);
+        assert_eq!(scores.scores.iter().map(|high| high.score).collect::<Vec<_>>(), [30, 20, 10]);
+        assert_eq!(scores.insert(HighScore { score: 20, length: 3 }), Some(2));
+        let mut full = table(&[100; MAX_HIGH_SCORES]);
+        assert_eq!(full.insert(HighScore { score: 100, length: 3 }), None);
+        assert_eq!(full.scores.len(), MAX_HIGH_SCORES);
+    }
+
+    #[test]
+    fn only_the_top_is_shown_with_the_place_marked() {
+        let table = table(&[80, 70, 60, 50, 40, 30, 20]);
+        let lines = table.top_lines(Some(6));
+        assert_eq!(lines.len(), SHOWN_HIGH_SCORES + 1);
+        assert_eq!(lines[0], " 1.    80   length 3");
+        assert_eq!(lines[SHOWN_HIGH_SCORES], " 7.    20   length 3  <");
+        assert_eq!(table.top_lines(Some(1)).len(), SHOWN_HIGH_SCORES);
+        assert_eq!(table.top_lines(None).len(), SHOWN_HIGH_SCORES);
+        assert_eq!(HighScores::default().top_lines(None), ["No scores yet"]);
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+
+use crate::common::AppState;
+use crate::gameplay::{GameClock, GameSimulation};
+use crate::ghost::GhostRace;
+use crate::timeattack::Countdown;
+use crate::campaign::CampaignRun;
+use crate::puzzle::PuzzleRun;
+
+pub struct HudPlugin;
+
+#[derive(Component)]
+struct HudText;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(update_hud_system.run_if(resource_exists::<GameSimulation>()));
+        for state in [AppState::Gameplay, AppState::Online] {
+            app
+                .add_system(spawn_hud_system.in_schedule(OnEnter(state.clone())))
+                .add_system(despawn_hud_system.in_schedule(OnExit(state)));
+        }
+    }
+}
+
+fn spawn_hud_system(mut commands: Commands,
+                    asset_server: Res<AssetServer>) {
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 24.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(HudText);
+}
+
+fn update_hud_system(simulation: Res<GameSimulation>,
+                     clock: Option<Res<GameClock>>,
+                     ghost: Option<Res<GhostRace>>,
+                     countdown: Option<Res<Countdown>>,
+                     run: Option<Res<CampaignRun>>,
+                     puzzle: Option<Res<PuzzleRun>>,
+                     mut query: Query<&mut Text, With<HudText>>) {
+    let mut value = if simulation.players() > 1 {
+        simulation.snakes().iter()
+            .enumerate()
+            .map(|(player, snake)| format!("P{}: {}", player + 1, snake.score))
+            .collect::<Vec<_>>()
+            .join("    ")
+    }
+    else {
+        let score = simulation.snake(0).score;
+        let mut value = format!("Score: {}", score);
+        if let Some(ghost) = ghost {
+            let ghost_score = ghost.simulation.snake(0).score;
+            let delta = score as i64 - ghost_score as i64;
+            value += &format!("    Ghost: {} ({:+})", ghost_score, delta);
+        }
+        value
+    };
+    if let Some(countdown) = countdown {
+        let seconds = countdown.left.as_secs_f32().ceil() as u64;
+        value += &format!("    Time: {}:{:02}", seconds / 60, seconds % 60);
+    }
+    if let Some(run) = run {
+        value += &format!("    {}", run.status);
+    }
+    if let Some(puzzle) = puzzle {
+        value += &format!("    {}", puzzle.status);
+    }
+    if clock.is_some_and(|clock| clock.paused) {
+        value += "    Paused";
+    }
+    for mut text in &mut query {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+fn despawn_hud_system(mut commands: Commands,
+                      query: Query<Entity, With<HudText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
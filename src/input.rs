@@ -0,0 +1,456 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::common::{AppState, Direction};
+
+// the input of the game is read from the keyboard, the gamepads and the touch screen
+// (in touch.rs) and sent on as GameInput events, the systems of the game only read those,
+// so anything else can play the game by sending them too
+
+// the stick has to be pushed this far to steer
+pub const STICK_DEADZONE: f32 = 0.5;
+// and a direction is kept until the stick is let back this far,
+// so that it does not flicker between two directions on a diagonal
+pub const STICK_RELEASE: f32 = 0.3;
+
+// the players that can share the keyboard, each with keys of their own
+pub const LOCAL_PLAYERS: usize = 2;
+
+pub struct GameInputPlugin;
+
+// turns also move the selection in menus, where the first player is in control
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameInput {
+    Turn { player: usize, direction: Direction },
+    Pause,
+    Confirm,
+    Back,
+}
+
+// what a key or a gamepad button can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    TurnUp,
+    TurnDown,
+    TurnLeft,
+    TurnRight,
+    Pause,
+    Confirm,
+    Back,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::TurnUp, Action::TurnDown, Action::TurnLeft, Action::TurnRight,
+        Action::Pause, Action::Confirm, Action::Back,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::TurnUp => "Turn up",
+            Action::TurnDown => "Turn down",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::Pause => "Pause",
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+        }
+    }
+
+    pub fn input(self, player: usize) -> GameInput {
+        let turn = |direction| GameInput::Turn { player, direction };
+        match self {
+            Action::TurnUp => turn(Direction::Up),
+            Action::TurnDown => turn(Direction::Down),
+            Action::TurnLeft => turn(Direction::Left),
+            Action::TurnRight => turn(Direction::Right),
+            Action::Pause => GameInput::Pause,
+            Action::Confirm => GameInput::Confirm,
+            Action::Back => GameInput::Back,
+        }
+    }
+}
+
+// how a player steers, with a key for each direction, or with two keys
+// that turn the snake to its left and right
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Steering {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+impl Steering {
+    pub fn name(self) -> &'static str {
+        match self {
+            Steering::Absolute => "absolute",
+            Steering::Relative => "relative",
+        }
+    }
+
+    pub fn toggled(self) -> Steering {
+        match self {
+            Steering::Absolute => Steering::Relative,
+            Steering::Relative => Steering::Absolute,
+        }
+    }
+
+    // where a turn takes a snake heading the way it is, relative steering
+    // only turns left and right so up and down do nothing
+    pub fn direction(self, turn: Direction, heading: Direction) -> Option<Direction> {
+        match (self, turn) {
+            (Steering::Absolute, _) => Some(turn),
+            (Steering::Relative, Direction::Left) => Some(heading.turned_left()),
+            (Steering::Relative, Direction::Right) => Some(heading.turned_right()),
+            (Steering::Relative, _) => None,
+        }
+    }
+}
+
+pub type ActionMap<T> = BTreeMap<Action, Vec<T>>;
+
+// what the keys of each local player and the buttons of the gamepads do,
+// all gamepads share the same buttons, and how each player steers during a game
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub keys: Vec<ActionMap<KeyCode>>,
+    pub buttons: ActionMap<GamepadButtonType>,
+    pub steering: Vec<Steering>,
+}
+
+impl Default for Bindings {
+    // the first player steers with the arrows, the second one with WASD
+    fn default() -> Self {
+        use KeyCode::*;
+        use GamepadButtonType::*;
+        Bindings {
+            keys: vec![
+                BTreeMap::from([
+                    (Action::TurnUp, vec![Up]), (Action::TurnDown, vec![Down]),
+                    (Action::TurnLeft, vec![Left]), (Action::TurnRight, vec![Right]),
+                    (Action::Pause, vec![P]), (Action::Confirm, vec![Space, Return]), (Action::Back, vec![Back, Escape]),
+                ]),
+                BTreeMap::from([
+                    (Action::TurnUp, vec![W]), (Action::TurnDown, vec![S]),
+                    (Action::TurnLeft, vec![A]), (Action::TurnRight, vec![D]),
+                ]),
+            ],
+            buttons: BTreeMap::from([
+                (Action::TurnUp, vec![DPadUp]), (Action::TurnDown, vec![DPadDown]),
+                (Action::TurnLeft, vec![DPadLeft]), (Action::TurnRight, vec![DPadRight]),
+                (Action::Pause, vec![Start]), (Action::Confirm, vec![South]), (Action::Back, vec![East, Select]),
+            ]),
+            steering: vec![Steering::Absolute; LOCAL_PLAYERS],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn steering(&self, player: usize) -> Steering {
+        self.steering.get(player).copied().unwrap_or_default()
+    }
+
+    pub fn keys(&self, player: usize, action: Action) -> &[KeyCode] {
+        self.keys.get(player)
+            .and_then(|keys| keys.get(&action))
+            .map_or(&[], |keys| keys.as_slice())
+    }
+
+    pub fn buttons(&self, action: Action) -> &[GamepadButtonType] {
+        self.buttons.get(&action).map_or(&[], |buttons| buttons.as_slice())
+    }
+
+    // the player and the action a key is bound to
+    pub fn key_action(&self, key: KeyCode) -> Option<(usize, Action)> {
+        self.keys.iter().enumerate().find_map(|(player, keys)| {
+            keys.iter()
+                .find(|(_, bound)| bound.contains(&key))
+                .map(|(action, _)| (player, *action))
+        })
+    }
+
+    pub fn button_action(&self, button: GamepadButtonType) -> Option<Action> {
+        self.buttons.iter()
+            .find(|(_, bound)| bound.contains(&button))
+            .map(|(action, _)| *action)
+    }
+
+    // a key does one thing only, so one that is in use has to be cleared first
+    pub fn bind_key(&mut self, player: usize, action: Action, key: KeyCode) -> Result<(), String> {
+        match self.key_action(key) {
+            Some(bound) if bound != (player, action) => Err(format!(
+                "{} is already used for {} of player {}", key_name(key), bound.1.name(), bound.0 + 1)),
+            _ => {
+                self.keys[player].insert(action, vec![key]);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn bind_button(&mut self, action: Action, button: GamepadButtonType) -> Result<(), String> {
+        match self.button_action(button) {
+            Some(bound) if bound != action => Err(format!(
+                "{} is already used for {}", button_name(button), bound.name())),
+            _ => {
+                self.buttons.insert(action, vec![button]);
+                Ok(())
+            }
+        }
+    }
+
+    // the first player needs every key, to be able to play and to get through the menus
+    pub fn clear_key(&mut self, player: usize, action: Action) -> Result<(), String> {
+        if player == 0 {
+            return Err(format!("The first player needs a key for {}", action.name()));
+        }
+        self.keys[player].remove(&action);
+        Ok(())
+    }
+
+    pub fn clear_button(&mut self, action: Action) {
+        self.buttons.remove(&action);
+    }
+
+    // bindings loaded from a file could have been changed by hand
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.len() != LOCAL_PLAYERS {
+            return Err(format!("there are keys for {} players instead of {}", self.keys.len(), LOCAL_PLAYERS));
+        }
+        if self.steering.len() != LOCAL_PLAYERS {
+            return Err(format!("there is steering for {} players instead of {}", self.steering.len(), LOCAL_PLAYERS));
+        }
+        if let Some(action) = Action::ALL.into_iter().find(|action| self.keys(0, *action).is_empty()) {
+            return Err(format!("the first player has no key for {}", action.name()));
+        }
+        let keys: Vec<KeyCode> = self.keys.iter().flat_map(|keys| keys.values().flatten().copied()).collect();
+        if let Some(key) = keys.iter().enumerate().find_map(|(i, key)| keys[..i].contains(key).then_some(key)) {
+            return Err(format!("{} is bound more than once", key_name(*key)));
+        }
+        let buttons: Vec<GamepadButtonType> = self.buttons.values().flatten().copied().collect();
+        if let Some(button) = buttons.iter().enumerate().find_map(|(i, button)| buttons[..i].contains(button).then_some(button)) {
+            return Err(format!("{} is bound more than once", button_name(*button)));
+        }
+        Ok(())
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    match key {
+        KeyCode::Back => "Backspace".to_string(),
+        KeyCode::Return => "Enter".to_string(),
+        key => format!("{:?}", key).trim_start_matches("Key").to_string(),
+    }
+}
+
+pub fn button_name(button: GamepadButtonType) -> String {
+    format!("{:?}", button)
+}
+
+// the gamepads in the order they were connected,
+// the first one steers the first snake, the second one the second snake
+#[derive(Resource, Default, Debug)]
+pub struct GamepadAssignment {
+    pub gamepads: Vec<Gamepad>,
+}
+
+impl GamepadAssignment {
+    pub fn player(&self, gamepad: Gamepad) -> Option<usize> {
+        self.gamepads.iter().position(|assigned| *assigned == gamepad)
+    }
+}
+
+// the input events are sent before the systems of the game run
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameInputSet;
+
+impl Plugin for GameInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<GameInput>()
+            .init_resource::<Bindings>()
+            .init_resource::<GamepadAssignment>()
+            .configure_set(GameInputSet
+                .in_base_set(CoreSet::PreUpdate)
+                .after(InputSystem))
+            .add_systems((keyboard_input_system, assign_gamepads_system, gamepad_input_system)
+                .chain()
+                .in_set(GameInputSet))
+            .add_system(clear_input_system
+                .in_base_set(CoreSet::StateTransitions)
+                .after(apply_state_transition::<AppState>));
+    }
+}
+
+// a state gets the input from when it was entered,
+// the input that ended the state before it is not for it
+fn clear_input_system(state: Res<State<AppState>>,
+                      mut events: ResMut<Events<GameInput>>) {
+    if state.is_changed() {
+        events.clear();
+    }
+}
+
+fn keyboard_input_system(keyboard_input: Res<Input<KeyCode>>,
+                         bindings: Res<Bindings>,
+                         mut input: EventWriter<GameInput>) {
+    for (player, keys) in bindings.keys.iter().enumerate() {
+        for (action, keys) in keys {
+            if keyboard_input.any_just_pressed(keys.iter().copied()) {
+                input.send(action.input(player));
+            }
+        }
+    }
+}
+
+// a gamepad keeps its player while it stays connected,
+// and a new one takes the first player without a gamepad
+fn assign_gamepads_system(gamepads: Res<Gamepads>,
+                          mut assignment: ResMut<GamepadAssignment>) {
+    if !gamepads.is_changed() {
+        return;
+    }
+    assignment.gamepads.retain(|gamepad| gamepads.contains(*gamepad));
+    for gamepad in gamepads.iter() {
+        if assignment.player(gamepad).is_none() {
+            debug!("Gamepad {} steers player {}", gamepad.id, assignment.gamepads.len() + 1);
+            assignment.gamepads.push(gamepad);
+        }
+    }
+}
+
+fn gamepad_input_system(assignment: Res<GamepadAssignment>,
+                        bindings: Res<Bindings>,
+                        buttons: Res<Input<GamepadButton>>,
+                        axes: Res<Axis<GamepadAxis>>,
+                        mut sticks: Local<HashMap<Gamepad, Direction>>,
+                        mut input: EventWriter<GameInput>) {
+    for (player, gamepad) in assignment.gamepads.iter().enumerate() {
+        for (action, bound) in &bindings.buttons {
+            if bound.iter().any(|button| buttons.just_pressed(GamepadButton::new(*gamepad, *button))) {
+                input.send(action.input(player));
+            }
+        }
+
+        let axis = |axis| axes.get(GamepadAxis::new(*gamepad, axis)).unwrap_or(0.0);
+        let stick = Vec2::new(axis(GamepadAxisType::LeftStickX), axis(GamepadAxisType::LeftStickY));
+        let held = sticks.get(gamepad).copied();
+        match stick_direction(stick, held) {
+            Some(direction) if held != Some(direction) => {
+                input.send(GameInput::Turn { player, direction });
+                sticks.insert(*gamepad, direction);
+            }
+            Some(_) => {}
+            None => {
+                sticks.remove(gamepad);
+            }
+        }
+    }
+}
+
+// the direction the stick is pushed in, or none inside the deadzone
+pub fn stick_direction(stick: Vec2, held: Option<Direction>) -> Option<Direction> {
+    let strongest = stick.abs().max_element();
+    if let Some(direction) = held {
+        let along = match direction {
+            Direction::Left => -stick.x,
+            Direction::Right => stick.x,
+            Direction::Down => -stick.y,
+            Direction::Up => stick.y,
+        };
+        if along >= STICK_DEADZONE || (along >= STICK_RELEASE && strongest < STICK_DEADZONE) {
+            return Some(direction);
+        }
+    }
+    if strongest < STICK_DEADZONE {
+        None
+    }
+    else if stick.x.abs() > stick.y.abs() {
+        Some(if stick.x > 0.0 { Direction::Right } else { Direction::Left })
+    }
+    else {
+        Some(if stick.y > 0.0 { Direction::Up } else { Direction::Down })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::input::ButtonState;
+    use bevy::input::gamepad::{GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent, GamepadEvent, GamepadInfo};
+    use bevy::input::keyboard::KeyboardInput;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugin(bevy::input::InputPlugin)
+            .add_state::<AppState>()
+            .add_plugin(GameInputPlugin);
+        app.update();
+        app
+    }
+
+    // the game input of a frame with these events sent
+    fn frame<E: Event>(app: &mut App, events: impl IntoIterator<Item = E>) -> Vec<GameInput> {
+        for event in events {
+            app.world.send_event(event);
+        }
+        app.update();
+        app.world.resource_mut::<Events<GameInput>>().drain().collect()
+    }
+
+    fn key(key_code: KeyCode, state: ButtonState) -> KeyboardInput {
+        KeyboardInput { scan_code: 0, key_code: Some(key_code), state }
+    }
+
+    #[test]
+    fn keys_are_sent_on_as_game_input() {
+        let mut app = app();
+        assert_eq!(frame(&mut app, [key(KeyCode::Up, ButtonState::Pressed), key(KeyCode::A, ButtonState::Pressed)]),
+                   [GameInput::Turn { player: 0, direction: Direction::Up }, GameInput::Turn { player: 1, direction: Direction::Left }]);
+        // a key held down is only sent once
+        assert_eq!(frame::<KeyboardInput>(&mut app, []), []);
+        assert_eq!(frame(&mut app, [key(KeyCode::Up, ButtonState::Released), key(KeyCode::A, ButtonState::Released)]), []);
+        assert_eq!(frame(&mut app, [key(KeyCode::Return, ButtonState::Pressed)]), [GameInput::Confirm]);
+        assert_eq!(frame(&mut app, [key(KeyCode::P, ButtonState::Pressed)]), [GameInput::Pause]);
+        assert_eq!(frame(&mut app, [key(KeyCode::Escape, ButtonState::Pressed)]), [GameInput::Back]);
+        assert_eq!(frame(&mut app, [key(KeyCode::Q, ButtonState::Pressed)]), []);
+    }
+
+    #[test]
+    fn rebound_keys_are_used() {
+        let mut app = app();
+        app.world.resource_mut::<Bindings>().bind_key(0, Action::Pause, KeyCode::Q).unwrap();
+        assert_eq!(frame(&mut app, [key(KeyCode::Q, ButtonState::Pressed)]), [GameInput::Pause]);
+        assert_eq!(frame(&mut app, [key(KeyCode::P, ButtonState::Pressed)]), []);
+    }
+
+    #[test]
+    fn gamepads_steer_in_the_order_they_connect() {
+        let mut app = app();
+        let connect = |id| -> GamepadEvent {
+            GamepadConnectionEvent::new(Gamepad::new(id), GamepadConnection::Connected(GamepadInfo { name: "pad".to_string() })).into()
+        };
+        let press = |id, button| -> GamepadEvent { GamepadButtonChangedEvent::new(Gamepad::new(id), button, 1.0).into() };
+        // in frames of their own, the ones connecting in the same frame come in no set order
+        frame(&mut app, [connect(3)]);
+        frame(&mut app, [connect(1)]);
+        assert_eq!(frame(&mut app, [press(1, GamepadButtonType::DPadRight)]),
+                   [GameInput::Turn { player: 1, direction: Direction::Right }]);
+        assert_eq!(frame(&mut app, [press(3, GamepadButtonType::South)]), [GameInput::Confirm]);
+    }
+
+    #[test]
+    fn stick_keeps_its_direction_until_let_back() {
+        assert_eq!(stick_direction(Vec2::new(0.4, 0.0), None), None);
+        assert_eq!(stick_direction(Vec2::new(0.6, 0.2), None), Some(Direction::Right));
+        assert_eq!(stick_direction(Vec2::new(0.0, -0.9), None), Some(Direction::Down));
+        // on a diagonal the held direction stays
+        assert_eq!(stick_direction(Vec2::new(0.6, 0.62), Some(Direction::Right)), Some(Direction::Right));
+        assert_eq!(stick_direction(Vec2::new(0.35, 0.0), Some(Direction::Right)), Some(Direction::Right));
+        assert_eq!(stick_direction(Vec2::new(0.2, 0.0), Some(Direction::Right)), None);
+    }
+}
@@ -0,0 +1,251 @@
+use bevy::prelude::*;
+
+use crate::common::AppState;
+use crate::daily;
+use crate::gameplay::{GameMode, GameSetup};
+use crate::level::{Level, LEVEL_CHOICES};
+
+// how a run of the game is set up without recompiling: from the query of the page
+// on wasm, like ?seed=42&speed=2&level=pillars&mode=versus,
+// and from the same options on the command line on native builds
+
+pub const USAGE: &str = "\
+Usage: snake [options]
+
+With any of the options the splash screen is skipped.
+
+Options:
+  --seed <n>       seed of the local games (default random)
+  --speed <x>      how fast the local games run, 1 is the normal speed
+  --level <name>   level of the local games: open, pillars, bars, portals, patrols,
+                   random for a new generated one every game, or a generated one
+                   like maze-42 (default the one chosen in the settings)
+  --mode <mode>    single, versus for two players, time-attack, daily, or online for the lobby
+  --help           show this help";
+
+const MIN_SPEED: f32 = 0.1;
+const MAX_SPEED: f32 = 10.0;
+
+pub struct LaunchPlugin;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LaunchMode {
+    #[default]
+    Single,
+    Versus,
+    TimeAttack,
+    Daily,
+    Online,
+}
+
+impl LaunchMode {
+    pub const ALL: [LaunchMode; 5] = [LaunchMode::Single, LaunchMode::Versus, LaunchMode::TimeAttack, LaunchMode::Daily, LaunchMode::Online];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LaunchMode::Single => "single",
+            LaunchMode::Versus => "versus",
+            LaunchMode::TimeAttack => "time-attack",
+            LaunchMode::Daily => "daily",
+            LaunchMode::Online => "online",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LaunchMode> {
+        LaunchMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+// anything not given is as if the game was started without options
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct LaunchConfig {
+    pub seed: Option<u64>,
+    pub speed: Option<f32>,
+    // open, random or maze-42 for example
+    pub level: Option<String>,
+    pub mode: Option<LaunchMode>,
+}
+
+impl LaunchConfig {
+    pub fn parse(options: impl IntoIterator<Item = (String, String)>) -> Result<LaunchConfig, String> {
+        let mut config = LaunchConfig::default();
+        for (name, value) in options {
+            match name.as_str() {
+                "seed" => config.seed = Some(value.parse().map_err(|_| format!("seed needs a number, got {}", value))?),
+                "speed" => {
+                    let speed: f32 = value.parse().map_err(|_| format!("speed needs a number, got {}", value))?;
+                    if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+                        return Err(format!("speed must be from {} to {}, not {}", MIN_SPEED, MAX_SPEED, value));
+                    }
+                    config.speed = Some(speed);
+                }
+                "level" => {
+                    if Level::choose(&value, 0).is_none() {
+                        return Err(format!("unknown level {}, the levels are {}, or a generated one like maze-42",
+                                           value, LEVEL_CHOICES.join(", ")));
+                    }
+                    config.level = Some(value);
+                }
+                "mode" => config.mode = Some(LaunchMode::from_name(&value).ok_or_else(|| format!(
+                    "unknown mode {}, the modes are single, versus, time-attack, daily and online", value))?),
+                _ => return Err(format!("unknown option: {}", name)),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<LaunchConfig, String> {
+        let mut options = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--help" {
+                return Err(USAGE.to_string());
+            }
+            let Some(name) = arg.strip_prefix("--") else {
+                return Err(format!("unknown option: {}\n\n{}", arg, USAGE));
+            };
+            let value = args.next().ok_or(format!("{} needs a value", arg))?;
+            options.push((name.to_string(), value));
+        }
+        LaunchConfig::parse(options).map_err(|error| format!("{}\n\n{}", error, USAGE))
+    }
+
+    // the query of a page's address, with or without the question mark
+    pub fn from_query(query: &str) -> Result<LaunchConfig, String> {
+        let options = query.trim_start_matches('?')
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((percent_decode(name)?, percent_decode(value)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        LaunchConfig::parse(options)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == LaunchConfig::default()
+    }
+
+    pub fn level(&self, seed: u64) -> Option<Level> {
+        self.level.as_ref().and_then(|choice| Level::choose(choice, seed))
+    }
+
+    pub fn players(&self) -> usize {
+        if self.mode == Some(LaunchMode::Versus) { 2 } else { 1 }
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        match self.mode {
+            Some(LaunchMode::TimeAttack) => GameMode::TimeAttack,
+            Some(LaunchMode::Daily) => GameMode::Daily { day: daily::today() },
+            _ => GameMode::Classic,
+        }
+    }
+
+    // where the game goes after the splash screen
+    pub fn first_state(&self) -> AppState {
+        if self.mode == Some(LaunchMode::Online) { AppState::Lobby } else { AppState::Gameplay }
+    }
+}
+
+// the way browsers write the names and values of a query, + is a space
+// and %XX is a byte of the UTF-8 text
+fn percent_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.bytes();
+    while let Some(byte) = rest.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = rest.by_ref().take(2).collect();
+                let code = std::str::from_utf8(&hex).ok()
+                    .filter(|hex| hex.len() == 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("bad % escape in {}", text))?;
+                bytes.push(code);
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8 text", text))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use super::LaunchConfig;
+
+    pub fn load() -> LaunchConfig {
+        match LaunchConfig::from_args(std::env::args().skip(1)) {
+            Ok(config) => config,
+            // asking for the help is not a mistake
+            Err(message) if message == super::USAGE => {
+                println!("{}", message);
+                std::process::exit(0);
+            }
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use super::LaunchConfig;
+
+    // the game is played anyway when the address has a mistake in it
+    pub fn load() -> LaunchConfig {
+        let query = web_sys::window()
+            .and_then(|window| window.location().search().ok())
+            .unwrap_or_default();
+        LaunchConfig::from_query(&query).unwrap_or_else(|error| {
+            bevy::log::error!("Ignoring the options in the address: {}", error);
+            LaunchConfig::default()
+        })
+    }
+}
+
+impl Plugin for LaunchPlugin {
+    fn build(&self, app: &mut App) {
+        let config = platform::load();
+        if config.mode.is_some() {
+            app
+                .insert_resource(GameSetup { players: config.players() })
+                .insert_resource(config.game_mode());
+        }
+        app
+            .insert_resource(config)
+            .add_startup_system(log_launch_system);
+    }
+}
+
+// the plugin is built before there is a logger
+fn log_launch_system(config: Res<LaunchConfig>) {
+    if !config.is_empty() {
+        debug!("Launched with {:?}", config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_values_are_percent_decoded() {
+        let config = LaunchConfig::from_query("?seed=%34%32&level=maze%2D7&mode=time-attack").unwrap();
+        assert_eq!(config.seed, Some(42));
+        assert_eq!(config.level.as_deref(), Some("maze-7"));
+        assert_eq!(config.mode, Some(LaunchMode::TimeAttack));
+        assert_eq!(LaunchConfig::from_query("%6Dode=daily").unwrap().mode, Some(LaunchMode::Daily));
+    }
+
+    #[test]
+    fn spaces_and_utf8_are_decoded() {
+        assert_eq!(percent_decode("my+level%20two").unwrap(), "my level two");
+        assert_eq!(percent_decode("k%C3%A4%C3%A4rme").unwrap(), "käärme");
+        assert!(percent_decode("%C3").is_err());
+        assert!(percent_decode("100%").is_err());
+        assert!(percent_decode("%zz").is_err());
+    }
+}
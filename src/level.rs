@@ -0,0 +1,572 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Direction, GridPosition, GRID_HEIGHT, GRID_WIDTH};
+use crate::generator;
+use crate::simulation::start_position;
+use crate::terrain::{Conveyor, Ice, Mud, Tile};
+
+// the walls of a level, inside the border that every level has
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Level {
+    pub name: String,
+    pub walls: Vec<GridPosition>,
+    // where a snake playing alone starts, in the middle when there is none
+    pub start: Option<(GridPosition, Direction)>,
+    // when there are any, food and bombs only appear on these
+    pub food_spawns: Vec<GridPosition>,
+    pub bomb_spawns: Vec<GridPosition>,
+    // a snake going into one of a pair comes out of the other, heading the same way
+    pub portals: Vec<(GridPosition, GridPosition)>,
+    pub terrain: Vec<(GridPosition, Terrain)>,
+    pub enemies: Vec<EnemyKind>,
+}
+
+// the enemies of a level, a snake running into one or having one run into its head dies
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    // walks its path from the first place to the last and back again, over and over,
+    // every place of the path next to the one before it
+    Patrol(Vec<GridPosition>),
+    // heads for the closest snake from where it starts, at half the speed of the snakes
+    Chaser(GridPosition),
+}
+
+impl EnemyKind {
+    pub fn start(&self) -> GridPosition {
+        match self {
+            EnemyKind::Patrol(path) => path[0],
+            EnemyKind::Chaser(start) => *start,
+        }
+    }
+
+    // the places it can be on, as far as the level can tell
+    pub fn places(&self) -> &[GridPosition] {
+        match self {
+            EnemyKind::Patrol(path) => path,
+            EnemyKind::Chaser(start) => std::slice::from_ref(start),
+        }
+    }
+}
+
+// places that change how the snakes move over them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    // a snake can not turn while its head is on ice
+    Ice,
+    // a snake coming onto a conveyor is pushed a place further, the way it runs
+    Conveyor(Direction),
+    // a snake with its head in mud moves every other tick
+    Mud,
+}
+
+impl Terrain {
+    pub fn name(self) -> &'static str {
+        match self {
+            Terrain::Ice => "ice",
+            Terrain::Conveyor(_) => "conveyor",
+            Terrain::Mud => "mud",
+        }
+    }
+
+    // as it is drawn on the map
+    pub fn cell(self) -> char {
+        match self {
+            Terrain::Ice => '~',
+            Terrain::Conveyor(Direction::Left) => '<',
+            Terrain::Conveyor(Direction::Right) => '>',
+            Terrain::Conveyor(Direction::Up) => '^',
+            Terrain::Conveyor(Direction::Down) => 'v',
+            Terrain::Mud => '%',
+        }
+    }
+
+    pub fn from_cell(cell: char) -> Option<Terrain> {
+        [Terrain::Ice, Terrain::Mud].into_iter()
+            .chain(Direction::ALL.map(Terrain::Conveyor))
+            .find(|terrain| terrain.cell() == cell)
+    }
+
+    // the tile of the terrain, with its look and the movement hooks the simulation calls
+    pub fn with_tile<T>(self, f: impl FnOnce(&dyn Tile) -> T) -> T {
+        match self {
+            Terrain::Ice => f(&Ice {}),
+            Terrain::Conveyor(direction) => f(&Conveyor { direction }),
+            Terrain::Mud => f(&Mud {}),
+        }
+    }
+
+    pub fn lets_turn(self) -> bool {
+        self.with_tile(|tile| tile.lets_turn())
+    }
+
+    pub fn holds(self, held: bool) -> bool {
+        self.with_tile(|tile| tile.holds(held))
+    }
+
+    pub fn push(self) -> Option<Direction> {
+        self.with_tile(|tile| tile.push())
+    }
+}
+
+pub const BUILTIN_LEVELS: [&str; 5] = ["open", "pillars", "bars", "portals", "patrols"];
+
+// a level generated from the seed of the game, a different one every game
+pub const RANDOM_LEVEL: &str = "random";
+
+// what the games can be played on
+pub const LEVEL_CHOICES: [&str; 6] = ["open", "pillars", "bars", "portals", "patrols", RANDOM_LEVEL];
+
+// the level files made in the editor are saved here, as <name>.json
+pub const LEVEL_DIRECTORY: &str = "levels";
+
+// more places than this that can not be reached are not all listed
+const MAX_LISTED: usize = 5;
+
+// an enemy starting this many places from the snake or closer gives it no chance
+const SAFE_DISTANCE: u32 = 3;
+
+// the portals of a level file are numbered, so there can be this many pairs
+pub const MAX_PORTALS: usize = 9;
+
+// a level file draws the level as a map, like the puzzles do: # is a wall,
+// H where the snake starts, * where food appears, B where bombs appear,
+// the two places of a portal have the same number, 1 to 9, the terrain
+// is ~ for ice, % for mud and < > ^ v for conveyors running that way,
+// and P is where a patroller starts and C where a chaser starts
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    name: String,
+    // where the snake heads from H, left when not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    direction: Option<Direction>,
+    map: Vec<String>,
+    // the paths of the patrollers, in the order they are on the map from the top left,
+    // as the directions they walk in from where they start: l, r, u and d
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    routes: Vec<String>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level {
+            name: BUILTIN_LEVELS[0].to_string(),
+            walls: Vec::new(),
+            start: None,
+            food_spawns: Vec::new(),
+            bomb_spawns: Vec::new(),
+            portals: Vec::new(),
+            terrain: Vec::new(),
+            enemies: Vec::new(),
+        }
+    }
+}
+
+impl Level {
+    // the walls, portals and enemies are kept clear of the rows the snakes start on
+    pub fn builtin(name: &str) -> Option<Level> {
+        let mut level = Level { name: name.to_string(), ..Level::default() };
+        match name {
+            "open" => {}
+            "pillars" => level.walls = blocks(&[(6, 5, 2, 2), (22, 5, 2, 2), (6, 14, 2, 2), (22, 14, 2, 2)]),
+            "bars" => level.walls = blocks(&[(5, 6, 8, 1), (18, 6, 8, 1), (5, 14, 8, 1), (18, 14, 8, 1)]),
+            "portals" => {
+                level.walls = blocks(&[(14, 5, 2, 3), (14, 13, 2, 3)]);
+                level.portals = vec![
+                    (GridPosition::new(3, 6), GridPosition::new(27, 14)),
+                    (GridPosition::new(3, 14), GridPosition::new(27, 6)),
+                ];
+            }
+            "patrols" => level.enemies = vec![
+                EnemyKind::Patrol((4..=26).map(|x| GridPosition::new(x, 6)).collect()),
+                EnemyKind::Patrol((4..=26).rev().map(|x| GridPosition::new(x, 14)).collect()),
+                EnemyKind::Chaser(GridPosition::new(28, 18)),
+            ],
+            _ => return None,
+        }
+        Some(level)
+    }
+
+    // a builtin level, or a generated one by its name, like maze-42
+    pub fn named(name: &str) -> Option<Level> {
+        Level::builtin(name).or_else(|| {
+            let (kind, seed) = name.rsplit_once('-')?;
+            generator::generate(kind, seed.parse().ok()?)
+        })
+    }
+
+    // the level chosen for a game, the random one comes from the seed of the game
+    pub fn choose(choice: &str, seed: u64) -> Option<Level> {
+        if choice == RANDOM_LEVEL { Some(generator::random(seed)) } else { Level::named(choice) }
+    }
+
+    pub fn file_name(name: &str) -> String {
+        format!("{}/{}.json", LEVEL_DIRECTORY, name)
+    }
+
+    pub fn from_file(data: &[u8]) -> Result<Level, String> {
+        let file: LevelFile = serde_json::from_slice(data).map_err(|error| error.to_string())?;
+        let mut level = Level { name: file.name, ..Level::default() };
+        let mut start = None;
+        let mut portals: [Vec<GridPosition>; MAX_PORTALS] = Default::default();
+        let mut patrollers = Vec::new();
+        for (position, cell) in read_map(&file.map)? {
+            match cell {
+                '.' => {}
+                '1'..='9' => portals[cell as usize - '1' as usize].push(position),
+                '#' => level.walls.push(position),
+                '*' => level.food_spawns.push(position),
+                'B' => level.bomb_spawns.push(position),
+                'H' if start.is_none() => start = Some(position),
+                'H' => return Err("there can only be one H".to_string()),
+                'P' => patrollers.push(position),
+                'C' => level.enemies.push(EnemyKind::Chaser(position)),
+                _ => match Terrain::from_cell(cell) {
+                    Some(terrain) => level.terrain.push((position, terrain)),
+                    None => return Err(format!("unknown cell {:?} at {}", cell, position)),
+                },
+            }
+        }
+        level.start = start.map(|position| (position, file.direction.unwrap_or(Direction::Left)));
+        for (number, places) in (1..).zip(portals) {
+            match places[..] {
+                [] => {}
+                [first, second] => level.portals.push((first, second)),
+                [place] => return Err(format!("the portal {} at {} has no other end", number, place)),
+                _ => return Err(format!("the portal {} is in {} places, not two", number, places.len())),
+            }
+        }
+        if patrollers.len() != file.routes.len() {
+            return Err(format!("there are {} patrollers and {} routes", patrollers.len(), file.routes.len()));
+        }
+        patrollers.sort_by_key(|position| (std::cmp::Reverse(position.y), position.x));
+        for (start, route) in patrollers.into_iter().zip(&file.routes) {
+            let mut path = vec![start];
+            for letter in route.chars() {
+                let direction = Direction::ALL.into_iter()
+                    .find(|direction| direction.name().starts_with(letter))
+                    .ok_or(format!("unknown direction {:?} in the route {:?}", letter, route))?;
+                let mut next = *path.last().expect("a path starts somewhere");
+                next.move_position(direction, 1);
+                path.push(next);
+            }
+            level.enemies.push(EnemyKind::Patrol(path));
+        }
+        Ok(level)
+    }
+
+    // the whole grid inside the border is drawn
+    pub fn to_file(&self) -> Vec<u8> {
+        let map = (1..GRID_HEIGHT).rev()
+            .map(|y| (1..GRID_WIDTH).map(|x| self.cell(GridPosition::new(x, y))).collect())
+            .collect();
+        let mut patrols: Vec<&Vec<GridPosition>> = self.enemies.iter()
+            .filter_map(|enemy| match enemy {
+                EnemyKind::Patrol(path) => Some(path),
+                EnemyKind::Chaser(_) => None,
+            })
+            .collect();
+        patrols.sort_by_key(|path| (std::cmp::Reverse(path[0].y), path[0].x));
+        let routes = patrols.into_iter().map(|path| route(path)).collect();
+        let file = LevelFile { name: self.name.clone(), direction: self.start.map(|(_, direction)| direction), map, routes };
+        serde_json::to_vec_pretty(&file).expect("a level can always be serialized")
+    }
+
+    // what is on a place, as it is drawn on the map
+    pub fn cell(&self, position: GridPosition) -> char {
+        if self.walls.contains(&position) {
+            '#'
+        }
+        else if self.start.is_some_and(|(start, _)| start == position) {
+            'H'
+        }
+        else if self.food_spawns.contains(&position) {
+            '*'
+        }
+        else if self.bomb_spawns.contains(&position) {
+            'B'
+        }
+        else if let Some(enemy) = self.enemies.iter().find(|enemy| enemy.start() == position) {
+            match enemy {
+                EnemyKind::Patrol(_) => 'P',
+                EnemyKind::Chaser(_) => 'C',
+            }
+        }
+        else if let Some(terrain) = self.terrain_at(position) {
+            terrain.cell()
+        }
+        else if let Some(index) = self.portals.iter().position(|(first, second)| *first == position || *second == position) {
+            char::from_digit(index as u32 + 1, 10).unwrap_or('.')
+        }
+        else {
+            '.'
+        }
+    }
+
+    // where the other end of a portal is
+    pub fn portal_exit(&self, position: GridPosition) -> Option<GridPosition> {
+        portal_exit(&self.portals, position)
+    }
+
+    pub fn terrain_at(&self, position: GridPosition) -> Option<Terrain> {
+        self.terrain.iter().find(|(place, _)| *place == position).map(|(_, terrain)| *terrain)
+    }
+
+    // a step to the next place, which is not next to the last one when it goes through a portal
+    pub fn step(&self, position: GridPosition, direction: Direction) -> GridPosition {
+        let mut next = position;
+        next.move_position(direction, 1);
+        self.portal_exit(next).unwrap_or(next)
+    }
+
+    // what would make the level unfair or unplayable, the way the single player game plays it
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let is_wall = |position: GridPosition| !generator::is_inside(position) || self.walls.contains(&position);
+
+        let (start, direction) = self.start.unwrap_or_else(|| start_position(0, 1));
+        let ahead = self.step(start, direction);
+        if is_wall(start) {
+            problems.push(format!("the snake starts on a wall at {}", start));
+        }
+        else if is_wall(ahead) {
+            problems.push(format!("the snake starts facing a wall at {}", ahead));
+        }
+
+        let unreachable = generator::unreachable(self);
+        if !unreachable.is_empty() {
+            let listed: Vec<String> = unreachable.iter().take(MAX_LISTED).map(ToString::to_string).collect();
+            let more = if unreachable.len() > MAX_LISTED { format!(" and {} more", unreachable.len() - MAX_LISTED) } else { String::new() };
+            problems.push(format!("{} places can not be reached from the start: {}{}",
+                                  unreachable.len(), listed.join(", "), more));
+        }
+
+        for (kind, spawns) in [("food", &self.food_spawns), ("bomb", &self.bomb_spawns)] {
+            for (index, spawn) in spawns.iter().enumerate() {
+                if spawns[..index].contains(spawn) {
+                    problems.push(format!("the {} spawn at {} is there twice", kind, spawn));
+                }
+                else if is_wall(*spawn) {
+                    problems.push(format!("the {} spawn at {} is on a wall", kind, spawn));
+                }
+                else if *spawn == start {
+                    problems.push(format!("the {} spawn at {} is where the snake starts", kind, spawn));
+                }
+                else if unreachable.contains(spawn) {
+                    problems.push(format!("the {} spawn at {} can not be reached", kind, spawn));
+                }
+            }
+        }
+
+        let ends: Vec<GridPosition> = self.portals.iter().flat_map(|(first, second)| [*first, *second]).collect();
+        if self.portals.len() > MAX_PORTALS {
+            problems.push(format!("there are {} portals, {} at most can be saved", self.portals.len(), MAX_PORTALS));
+        }
+        for (index, end) in ends.iter().enumerate() {
+            if ends[..index].contains(end) {
+                problems.push(format!("there is more than one portal at {}", end));
+            }
+            else if is_wall(*end) {
+                problems.push(format!("the portal at {} is on a wall", end));
+            }
+            else if *end == start {
+                problems.push(format!("the portal at {} is where the snake starts", end));
+            }
+            else if self.food_spawns.contains(end) || self.bomb_spawns.contains(end) {
+                problems.push(format!("the portal at {} is on a spawn", end));
+            }
+        }
+        for (index, (place, terrain)) in self.terrain.iter().enumerate() {
+            if self.terrain[..index].iter().any(|(other, _)| other == place) {
+                problems.push(format!("there is more than one terrain at {}", place));
+            }
+            else if is_wall(*place) {
+                problems.push(format!("the {} at {} is on a wall", terrain.name(), place));
+            }
+            else if *place == start {
+                problems.push(format!("the {} at {} is where the snake starts", terrain.name(), place));
+            }
+            else if ends.contains(place) {
+                problems.push(format!("the {} at {} is on a portal", terrain.name(), place));
+            }
+            else if let Terrain::Conveyor(direction) = terrain {
+                if is_wall(self.step(*place, *direction)) {
+                    problems.push(format!("the conveyor at {} pushes the snake into a wall", place));
+                }
+            }
+        }
+        for (index, enemy) in self.enemies.iter().enumerate() {
+            let name = match enemy {
+                EnemyKind::Patrol(_) => "patroller",
+                EnemyKind::Chaser(_) => "chaser",
+            };
+            let start_at = enemy.start();
+            if let Some(place) = enemy.places().iter().find(|place| is_wall(**place) || ends.contains(*place)) {
+                problems.push(format!("the {} from {} gets onto a wall or a portal at {}", name, start_at, place));
+            }
+            else if self.enemies[..index].iter().any(|other| other.start() == start_at) {
+                problems.push(format!("there is more than one enemy at {}", start_at));
+            }
+            else if start_at.x.abs_diff(start.x) + start_at.y.abs_diff(start.y) <= SAFE_DISTANCE {
+                problems.push(format!("the {} at {} starts too close to the snake", name, start_at));
+            }
+        }
+        if self.food_spawns.is_empty() && !generator::inside().any(|position| position != start && !is_wall(position)) {
+            problems.push("there is no room for food".to_string());
+        }
+        problems
+    }
+}
+
+// the directions from every place of a path to the next one, as the first letters of their names
+fn route(path: &[GridPosition]) -> String {
+    path.windows(2)
+        .filter_map(|step| {
+            Direction::ALL.into_iter().find(|direction| {
+                let mut next = step[0];
+                next.move_position(*direction, 1);
+                next == step[1]
+            })
+        })
+        .map(|direction| direction.name().chars().next().expect("directions have names"))
+        .collect()
+}
+
+pub(crate) fn portal_exit(portals: &[(GridPosition, GridPosition)], position: GridPosition) -> Option<GridPosition> {
+    portals.iter().find_map(|(first, second)| {
+        if *first == position { Some(*second) } else if *second == position { Some(*first) } else { None }
+    })
+}
+
+// the cells of a map drawn a character a cell, its first row at the top,
+// the map is put in the middle of the grid and everything around it is wall
+pub(crate) fn read_map(map: &[String]) -> Result<Vec<(GridPosition, char)>, String> {
+    let height = map.len();
+    let width = map.first().map_or(0, |row| row.chars().count());
+    if width == 0 || map.iter().any(|row| row.chars().count() != width) {
+        return Err("the rows of the map must all be as long".to_string());
+    }
+    // inside the border of the grid
+    let (inner_width, inner_height) = (GRID_WIDTH as usize - 1, GRID_HEIGHT as usize - 1);
+    if width > inner_width || height > inner_height {
+        return Err(format!("the map can be {} wide and {} high at most, not {} by {}",
+                           inner_width, inner_height, width, height));
+    }
+    let left = 1 + (inner_width - width) / 2;
+    let top = inner_height - (inner_height - height) / 2;
+    let bottom = top + 1 - height;
+
+    let mut cells = Vec::new();
+    for x in 1..GRID_WIDTH as usize {
+        for y in 1..GRID_HEIGHT as usize {
+            let position = GridPosition::new(x as u32, y as u32);
+            let cell = if (left..left + width).contains(&x) && (bottom..=top).contains(&y) {
+                map[top - y].chars().nth(x - left).expect("the rows are as wide as the map")
+            }
+            else {
+                '#'
+            };
+            cells.push((position, cell));
+        }
+    }
+    Ok(cells)
+}
+
+// the level chosen for the local games
+#[derive(Resource, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelSettings {
+    pub selected: String,
+}
+
+impl Default for LevelSettings {
+    fn default() -> Self {
+        LevelSettings { selected: Level::default().name }
+    }
+}
+
+impl LevelSettings {
+    pub fn level(&self, seed: u64) -> Level {
+        Level::choose(&self.selected, seed).unwrap_or_default()
+    }
+
+    // a generated level written into the settings goes back to the first choice
+    pub fn select_next(&mut self) {
+        let index = LEVEL_CHOICES.iter().position(|name| *name == self.selected).map_or(0, |index| index + 1);
+        self.selected = LEVEL_CHOICES[index % LEVEL_CHOICES.len()].to_string();
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match Level::choose(&self.selected, 0) {
+            Some(_) => Ok(()),
+            None => Err(format!("unknown level {}", self.selected)),
+        }
+    }
+}
+
+// x, y, width, height
+fn blocks(blocks: &[(u32, u32, u32, u32)]) -> Vec<GridPosition> {
+    blocks.iter()
+        .flat_map(|(x, y, width, height)| {
+            (*x..x + width).flat_map(move |x| (*y..y + height).map(move |y| GridPosition::new(x, y)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: u32, y: u32) -> GridPosition {
+        GridPosition::new(x, y)
+    }
+
+    // the snake playing alone starts in the middle, heading left
+    fn start() -> GridPosition {
+        start_position(0, 1).0
+    }
+
+    #[test]
+    fn the_builtin_levels_have_no_problems() {
+        for name in BUILTIN_LEVELS {
+            assert_eq!(Level::builtin(name).unwrap().problems(), Vec::<String>::new(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn a_start_facing_a_wall() {
+        let ahead = at(start().x - 1, start().y);
+        let level = Level { walls: vec![ahead], ..Level::default() };
+        assert_eq!(level.problems(), [format!("the snake starts facing a wall at {}", ahead)]);
+    }
+
+    #[test]
+    fn a_pocket_that_can_not_be_reached() {
+        let level = Level { walls: vec![at(4, 5), at(6, 5), at(5, 4), at(5, 6)], ..Level::default() };
+        assert_eq!(level.problems(), [format!("1 places can not be reached from the start: {}", at(5, 5))]);
+    }
+
+    #[test]
+    fn a_spawn_on_a_wall() {
+        let level = Level { walls: vec![at(5, 5)], bomb_spawns: vec![at(5, 5)], ..Level::default() };
+        assert_eq!(level.problems(), [format!("the bomb spawn at {} is on a wall", at(5, 5))]);
+    }
+
+    #[test]
+    fn a_conveyor_into_a_wall() {
+        let level = Level {
+            walls: vec![at(4, 5)],
+            terrain: vec![(at(5, 5), Terrain::Conveyor(Direction::Left))],
+            ..Level::default()
+        };
+        assert_eq!(level.problems(), [format!("the conveyor at {} pushes the snake into a wall", at(5, 5))]);
+    }
+
+    #[test]
+    fn an_enemy_next_to_the_start() {
+        let next = at(start().x, start().y + 1);
+        let level = Level { enemies: vec![EnemyKind::Chaser(next)], ..Level::default() };
+        assert_eq!(level.problems(), [format!("the chaser at {} starts too close to the snake", next)]);
+    }
+}
@@ -0,0 +1,48 @@
+// Snake - library
+//
+// shared by the game and the headless tools in src/bin
+
+// bevy queries get long types by nature
+#![allow(clippy::type_complexity)]
+
+pub mod common;
+pub mod simulation;
+pub mod level;
+pub mod generator;
+pub mod difficulty;
+pub mod bot;
+pub mod arena;
+pub mod replay;
+pub mod storage;
+pub mod checks;
+pub mod highscores;
+pub mod net;
+pub mod connection;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod input;
+pub mod touch;
+pub mod settings;
+pub mod embed;
+pub mod launch;
+pub mod snake;
+pub mod food;
+pub mod bomb;
+pub mod clock;
+pub mod enemy;
+pub mod wall;
+pub mod portal;
+pub mod terrain;
+pub mod splashscreen;
+pub mod gameplay;
+pub mod replayscreen;
+pub mod ghost;
+pub mod timeattack;
+pub mod campaign;
+pub mod daily;
+pub mod puzzle;
+pub mod editor;
+pub mod hud;
+pub mod online;
+pub mod lobby;
+pub mod spectator;
@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+use bevy::time::{Timer, TimerMode};
+use bevy::window::ReceivedCharacter;
+
+use crate::common::{AppState, Direction};
+use crate::connection::{Connection, default_server_url};
+use crate::input::GameInput;
+use crate::level::{Level, LEVEL_CHOICES};
+use crate::net::{ClientMessage, NetGame, RoomInfo, RoomSettings, Seat, ServerMessage, ROOM_CODE_LENGTH};
+use crate::online::{OnlineGame, ServerConnection};
+use crate::simulation::MAX_PLAYERS;
+
+// the choices the first player in a room cycles through
+const FOOD_SPAWN_TICKS: [u64; 4] = [10, 25, 50, 100];
+const BOMB_SPAWN_TICKS: [u64; 4] = [50, 135, 250, 500];
+
+const REFRESH_SECONDS: f32 = 2.0;
+
+pub struct LobbyPlugin;
+
+// the room the client is in, as the server last told it
+struct RoomView {
+    code: String,
+    settings: RoomSettings,
+    seats: Vec<Seat>,
+    spectators: Vec<String>,
+    you: Option<usize>,
+}
+
+impl RoomView {
+    fn is_host(&self) -> bool {
+        self.you == Some(0)
+    }
+
+    fn is_ready(&self) -> bool {
+        self.you.is_some_and(|you| self.seats[you].ready)
+    }
+}
+
+#[derive(Resource)]
+struct LobbyScreen {
+    url: String,
+    status: String,
+    rooms: Vec<RoomInfo>,
+    selected: usize,
+    // the room code being typed
+    code: Option<String>,
+    room: Option<RoomView>,
+    refresh: Timer,
+}
+
+#[derive(Component)]
+struct LobbyText;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(open_lobby_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(spawn_lobby_system.in_schedule(OnEnter(AppState::Lobby)))
+            .add_systems((lobby_network_system, lobby_input_system, lobby_refresh_system, lobby_text_system)
+                .chain()
+                .in_set(OnUpdate(AppState::Lobby)))
+            .add_system(despawn_lobby_system.in_schedule(OnExit(AppState::Lobby)));
+    }
+}
+
+fn open_lobby_input_system(mut state: ResMut<NextState<AppState>>,
+                           keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::O) {
+        state.set(AppState::Lobby);
+    }
+}
+
+fn spawn_lobby_system(world: &mut World) {
+    let url = default_server_url();
+    debug!("Connecting to {}", url);
+    let mut connection = Connection::open(&url);
+    connection.send(&ClientMessage::ListRooms);
+    world.insert_non_send_resource(ServerConnection(connection));
+    world.insert_resource(LobbyScreen {
+        status: format!("Connected to {}", url),
+        url,
+        rooms: Vec::new(),
+        selected: 0,
+        code: None,
+        room: None,
+        refresh: Timer::from_seconds(REFRESH_SECONDS, TimerMode::Repeating),
+    });
+
+    let font = world.resource::<AssetServer>().load("FiraSans-Bold.ttf");
+    world.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 26.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(40.0),
+                left: Val::Px(50.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(LobbyText);
+}
+
+fn lobby_network_system(mut commands: Commands,
+                        mut state: ResMut<NextState<AppState>>,
+                        mut lobby: ResMut<LobbyScreen>,
+                        mut connection: NonSendMut<ServerConnection>) {
+    while let Some(message) = connection.0.receive() {
+        match message {
+            Ok(ServerMessage::Rooms { rooms }) => {
+                lobby.selected = lobby.selected.min(rooms.len().saturating_sub(1));
+                lobby.rooms = rooms;
+            }
+            Ok(ServerMessage::Room { code, settings, seats, spectators, you }) => {
+                lobby.room = Some(RoomView { code, settings, seats, spectators, you });
+            }
+            // the messages after this one are for the game
+            Ok(ServerMessage::Start { seed, rules, level, names, you }) => {
+                match you {
+                    Some(you) => debug!("Online game started with seed {}, playing as {}", seed, names[you]),
+                    None => debug!("Watching an online game with seed {}", seed),
+                }
+                let level = Level::named(&level).unwrap_or_default();
+                commands.insert_resource(OnlineGame::new(NetGame::new(seed, rules, level, names, you)));
+                state.set(AppState::Online);
+                break;
+            }
+            Ok(ServerMessage::Error { message }) => lobby.status = message,
+            Ok(_) => {}
+            Err(message) => {
+                lobby.status = format!("{}  -  backspace to go back", message);
+                lobby.room = None;
+                break;
+            }
+        }
+    }
+}
+
+// the first player moves the selection, the letters are only on the keyboard
+fn lobby_input_system(mut commands: Commands,
+                      mut state: ResMut<NextState<AppState>>,
+                      keyboard_input: Res<Input<KeyCode>>,
+                      mut input: EventReader<GameInput>,
+                      mut characters: EventReader<ReceivedCharacter>,
+                      mut lobby: ResMut<LobbyScreen>,
+                      mut connection: NonSendMut<ServerConnection>) {
+    // read on every frame, so the letter of the key that opens the code entry is not left in it
+    let typed: Vec<char> = characters.iter().map(|event| event.char).collect();
+    let input: Vec<GameInput> = input.iter().copied().collect();
+    let confirm = input.contains(&GameInput::Confirm);
+    let back = input.contains(&GameInput::Back);
+
+    if let Some(room) = &lobby.room {
+        if let Some(message) = room_input(room, &keyboard_input, confirm) {
+            connection.0.send(&message);
+        }
+        if back {
+            lobby.room = None;
+            connection.0.send(&ClientMessage::LeaveRoom);
+            connection.0.send(&ClientMessage::ListRooms);
+        }
+        return;
+    }
+
+    if let Some(code) = &mut lobby.code {
+        for letter in typed.iter().filter(|letter| letter.is_ascii_alphabetic()) {
+            if code.len() < ROOM_CODE_LENGTH {
+                code.push(letter.to_ascii_uppercase());
+            }
+        }
+        let spectate = keyboard_input.just_pressed(KeyCode::Tab);
+        if confirm || spectate {
+            let code = code.clone();
+            connection.0.send(&ClientMessage::JoinRoom { code, name: String::new(), spectate });
+            lobby.code = None;
+        }
+        else if back && code.pop().is_none() {
+            lobby.code = None;
+        }
+        return;
+    }
+
+    if back {
+        commands.add(|world: &mut World| {
+            world.remove_non_send_resource::<ServerConnection>();
+        });
+        state.set(AppState::GameOver);
+        return;
+    }
+    if input.contains(&GameInput::Turn { player: 0, direction: Direction::Up }) {
+        lobby.selected = lobby.selected.saturating_sub(1);
+    }
+    if input.contains(&GameInput::Turn { player: 0, direction: Direction::Down }) && lobby.selected + 1 < lobby.rooms.len() {
+        lobby.selected += 1;
+    }
+    let spectate = keyboard_input.just_pressed(KeyCode::S);
+    if confirm || spectate {
+        if let Some(room) = lobby.rooms.get(lobby.selected) {
+            connection.0.send(&ClientMessage::JoinRoom { code: room.code.clone(), name: String::new(), spectate });
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::C) {
+        connection.0.send(&ClientMessage::CreateRoom { name: String::new(), settings: RoomSettings::default() });
+    }
+    if keyboard_input.just_pressed(KeyCode::Q) {
+        connection.0.send(&ClientMessage::Join { name: String::new() });
+    }
+    if keyboard_input.just_pressed(KeyCode::J) {
+        lobby.code = Some(String::new());
+    }
+    if keyboard_input.just_pressed(KeyCode::R) {
+        connection.0.send(&ClientMessage::ListRooms);
+    }
+}
+
+fn lobby_refresh_system(time: Res<Time>,
+                        mut lobby: ResMut<LobbyScreen>,
+                        mut connection: NonSendMut<ServerConnection>) {
+    // the timer alone does not need the text redrawn
+    let lobby = lobby.bypass_change_detection();
+    if lobby.room.is_none() && lobby.refresh.tick(time.delta()).just_finished() {
+        connection.0.send(&ClientMessage::ListRooms);
+    }
+}
+
+// confirming gets ready, and the first player changes the settings
+fn room_input(room: &RoomView, keyboard_input: &Input<KeyCode>, confirm: bool) -> Option<ClientMessage> {
+    if confirm && room.you.is_some() {
+        return Some(ClientMessage::Ready { ready: !room.is_ready() });
+    }
+    if !room.is_host() {
+        return None;
+    }
+    let mut settings = room.settings.clone();
+    if keyboard_input.just_pressed(KeyCode::L) {
+        settings.level = cycle(&LEVEL_CHOICES, settings.level.as_str()).to_string();
+    }
+    if keyboard_input.just_pressed(KeyCode::P) {
+        let players: Vec<usize> = (room.seats.len().max(1)..=MAX_PLAYERS).collect();
+        settings.players = cycle(&players, settings.players);
+    }
+    if keyboard_input.just_pressed(KeyCode::F) {
+        settings.rules.food_spawn_ticks = cycle(&FOOD_SPAWN_TICKS, settings.rules.food_spawn_ticks);
+    }
+    if keyboard_input.just_pressed(KeyCode::B) {
+        settings.rules.bomb_spawn_ticks = cycle(&BOMB_SPAWN_TICKS, settings.rules.bomb_spawn_ticks);
+    }
+    (settings != room.settings).then_some(ClientMessage::ChangeSettings { settings })
+}
+
+// the choice after the current one, or the first
+fn cycle<T: Copy + PartialEq + PartialOrd>(choices: &[T], current: T) -> T {
+    match choices.iter().position(|choice| *choice == current) {
+        Some(index) => choices[(index + 1) % choices.len()],
+        None => choices.iter().copied().find(|choice| *choice > current).unwrap_or(choices[0]),
+    }
+}
+
+fn lobby_text_system(lobby: Res<LobbyScreen>,
+                     mut query: Query<&mut Text, With<LobbyText>>) {
+    if !lobby.is_changed() {
+        return;
+    }
+    let mut lines = vec![format!("Online lobby - {}", lobby.url), lobby.status.clone(), String::new()];
+    if let Some(room) = &lobby.room {
+        let settings = &room.settings;
+        lines.push(format!("Room {} - level {}, food every {} ticks, bombs every {} ticks",
+                           room.code, settings.level, settings.rules.food_spawn_ticks, settings.rules.bomb_spawn_ticks));
+        lines.push(String::new());
+        for seat in 0..settings.players {
+            lines.push(match room.seats.get(seat) {
+                Some(member) => format!("  {}. {}{}{}", seat + 1, member.name,
+                                        if room.you == Some(seat) { " (you)" } else { "" },
+                                        if member.ready { " - ready" } else { "" }),
+                None => format!("  {}. free", seat + 1),
+            });
+        }
+        lines.push(format!("Spectators ({}/{}): {}", room.spectators.len(), settings.spectators,
+                           room.spectators.join(", ")));
+        lines.push(String::new());
+        if room.you.is_some() {
+            lines.push("Space or Enter to get ready, the game starts when everybody is".to_string());
+        }
+        else {
+            lines.push("Watching, the game is shown when it starts".to_string());
+        }
+        if room.is_host() {
+            lines.push("L to change the level, P the players, F the food and B the bombs".to_string());
+        }
+        lines.push("Backspace to leave the room".to_string());
+    }
+    else if let Some(code) = &lobby.code {
+        lines.push(format!("Room code: {}_", code));
+        lines.push(String::new());
+        lines.push("Enter to join, Tab to watch, Backspace to delete".to_string());
+    }
+    else {
+        if lobby.rooms.is_empty() {
+            lines.push("No rooms yet".to_string());
+        }
+        for (index, room) in lobby.rooms.iter().enumerate() {
+            lines.push(format!("{} {}  {}  {}/{} players  {} watching{}",
+                               if index == lobby.selected { ">" } else { " " },
+                               room.code, room.level, room.players, room.seats, room.spectators,
+                               if room.playing { "  playing" } else { "" }));
+        }
+        lines.push(String::new());
+        lines.push("Up and down to choose, Enter to join, S to watch".to_string());
+        lines.push("C to create a room, Q for a quick match, J to type a room code".to_string());
+        lines.push("R to refresh, Backspace to go back".to_string());
+    }
+    for mut text in &mut query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn despawn_lobby_system(mut commands: Commands,
+                        query: Query<Entity, With<LobbyText>>) {
+    commands.remove_resource::<LobbyScreen>();
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
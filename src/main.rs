@@ -1,28 +1,31 @@
 // Snake - main
 
-#[doc = include_str!("../README.md")]
+#![doc = include_str!("../README.md")]
+
+// bevy queries get long types by nature
+#![allow(clippy::type_complexity)]
 
 use bevy::prelude::*;
 use bevy::app::AppExit;
 use bevy::log::LogPlugin;
 use bevy::window::PrimaryWindow;
 
-mod snake;
-mod food;
-mod bomb;
-mod common;
-mod wall;
-mod splashscreen;
-mod gameplay;
-
-use common::AppState;
-use common::{WINDOW_WIDTH, WINDOW_HEIGHT};
-use common::{BackgroundImage, Text};
-use wall::Wall;
+use snake::{splashscreen, gameplay, replayscreen, ghost, timeattack, campaign, daily, puzzle, editor, hud, online, lobby, spectator, settings, embed, launch};
+use snake::common::AppState;
+use snake::common::{WINDOW_WIDTH, WINDOW_HEIGHT};
+use snake::common::{BackgroundImage, Text};
+use snake::gameplay::{GameMode, GameSetup, GameSimulation, MAX_LOCAL_PLAYERS};
+use snake::input::{Action, Bindings, GameInput, GameInputPlugin, key_name};
+use snake::touch::TouchPlugin;
+use snake::wall::Wall;
+use snake::portal::Portal;
+use snake::terrain::{Conveyor, Ice, Mud};
 
 fn main() {
     App::new()
         .add_state::<AppState>()
+        // before the window is opened, so that a mistake in the options only prints the usage
+        .add_plugin(launch::LaunchPlugin)
         .add_plugins(DefaultPlugins.set(
             LogPlugin {
                 filter: "error,wgpu_core=error,wgpu_hal=error,snake=debug".into(),
@@ -38,13 +41,31 @@ fn main() {
                     ..default()
                 })
             )
+        .add_plugin(GameInputPlugin)
+        .add_plugin(TouchPlugin)
+        .add_plugin(settings::SettingsPlugin)
         .add_plugin(splashscreen::SplashScreenPlugin)
         .add_plugin(gameplay::GameplayPlugin)
+        .add_plugin(replayscreen::ReplayScreenPlugin)
+        .add_plugin(ghost::GhostPlugin)
+        .add_plugin(timeattack::TimeAttackPlugin)
+        .add_plugin(campaign::CampaignPlugin)
+        .add_plugin(daily::DailyPlugin)
+        .add_plugin(puzzle::PuzzlePlugin)
+        .add_plugin(editor::EditorPlugin)
+        .add_plugin(hud::HudPlugin)
+        .add_plugin(online::OnlinePlugin)
+        .add_plugin(lobby::LobbyPlugin)
+        .add_plugin(spectator::SpectatorPlugin)
+        .add_plugin(embed::EmbedPlugin)
         .add_startup_system(setup_system)
-        .add_system(exit_system)
+        // going back from the menu quits the game
+        .add_system(exit_system.in_set(OnUpdate(AppState::GameOver)))
         .add_system(game_over_system.in_schedule(OnEnter(AppState::GameOver)))
         .add_system(game_over_input_system.in_set(OnUpdate(AppState::GameOver)))
+        // the screens after a game clear away what was left of it
         .add_system(despawn_game_over_system.in_schedule(OnExit(AppState::GameOver)))
+        .add_system(despawn_game_over_system.in_schedule(OnExit(AppState::LevelComplete)))
         .run();
 }
 
@@ -58,22 +79,65 @@ fn setup_system(mut commands: Commands,
     commands.spawn(Camera2dBundle::default());
 }
 
-fn exit_system(keyboard_input: Res<Input<KeyCode>>,
-    mut exit: EventWriter<AppExit>) {
-    if keyboard_input.pressed(KeyCode::Escape) {
+fn exit_system(mut input: EventReader<GameInput>,
+               mut exit: EventWriter<AppExit>) {
+    if input.iter().any(|event| *event == GameInput::Back) {
         exit.send(AppExit);
     }
 }
 
+// continuing plays the same way again
 fn game_over_input_system(mut state: ResMut<NextState<AppState>>,
-                          keyboard_input: Res<Input<KeyCode>>) {
-    if keyboard_input.pressed(KeyCode::Space) {
+                          mut setup: ResMut<GameSetup>,
+                          mut mode: ResMut<GameMode>,
+                          keyboard_input: Res<Input<KeyCode>>,
+                          mut input: EventReader<GameInput>) {
+    let player_keys = [KeyCode::Key1, KeyCode::Key2];
+    if let Some(players) = player_keys.iter().take(MAX_LOCAL_PLAYERS).position(|key| keyboard_input.just_pressed(*key)) {
+        setup.players = players + 1;
+        *mode = GameMode::Classic;
+        state.set(AppState::Gameplay);
+    }
+    if keyboard_input.just_pressed(KeyCode::T) {
+        setup.players = 1;
+        *mode = GameMode::TimeAttack;
+        state.set(AppState::Gameplay);
+    }
+    if input.iter().any(|event| *event == GameInput::Confirm) {
         state.set(AppState::Gameplay);
     }
 }
 
 fn game_over_system(mut commands: Commands,
-                    asset_server: Res<AssetServer>) {
+                    asset_server: Res<AssetServer>,
+                    bindings: Res<Bindings>,
+                    simulation: Option<Res<GameSimulation>>) {
+    if let Some(simulation) = simulation.filter(|simulation| simulation.players() > 1) {
+        let winner = match simulation.winner() {
+            Some(player) => format!("Player {} wins", player + 1),
+            None => "Draw".to_string(),
+        };
+        commands.spawn(
+            TextBundle::from_section(
+                winner,
+                TextStyle {
+                    font: asset_server.load("FiraSans-Bold.ttf"),
+                    font_size: 60.0,
+                    color: Color::GRAY,
+                }
+            )
+            .with_text_alignment(TextAlignment::Center)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Percent(65.0),
+                    right: Val::Px(250.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        ).insert(Text);
+    }
     commands.spawn(
         TextBundle::from_section(
             "Game over",
@@ -96,7 +160,7 @@ fn game_over_system(mut commands: Commands,
     ).insert(Text);
     commands.spawn(
         TextBundle::from_section(
-            "Press space to continue",
+            format!("Press {} or tap to continue", key_name(bindings.keys(0, Action::Confirm)[0]).to_lowercase()),
             TextStyle {
                 font: asset_server.load("FiraSans-Bold.ttf"),
                 font_size: 45.0,
@@ -114,10 +178,70 @@ fn game_over_system(mut commands: Commands,
             ..default()
         }),
     ).insert(Text);
+    commands.spawn(
+        TextBundle::from_section(
+            "R to watch the replay, G to race your best",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 30.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Percent(33.0),
+                right: Val::Px(250.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
+    commands.spawn(
+        TextBundle::from_section(
+            "1 or 2 to play with one or two snakes, T against the clock, L the campaign,\nD the daily challenge, Z the puzzles, O online",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 30.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Percent(27.0),
+                right: Val::Px(250.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
+    commands.spawn(
+        TextBundle::from_section(
+            "C to change the controls and the difficulty, E to make levels",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 30.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Percent(21.0),
+                right: Val::Px(250.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
 }
 
 fn despawn_game_over_system(mut commands: Commands,
-                            query: Query<Entity, Or<(&Wall, &BackgroundImage, &Text)>>) {
+                            query: Query<Entity, Or<(&Wall, &Portal, &Ice, &Conveyor, &Mud, &BackgroundImage, &Text)>>) {
     debug!("Running despawn game over system");
     for entity in query.iter() {
         commands.entity(entity).despawn();
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::Direction;
+use crate::level::Level;
+use crate::simulation::{GameEvent, Rules, Simulation};
+
+// online play is lockstep: the server runs the game and tells the clients
+// which turns were made on every tick, and as the simulation is deterministic
+// the clients can run the very same game from the seed and those turns
+//
+// the games are played in rooms, a room has a code to join it with,
+// seats for the players and slots for spectators, and the first player
+// in the room decides its settings. A game starts when every seat
+// is taken and all the players are ready. A game can also be played
+// somewhere else and streamed to a room, with the server checking its ticks
+// and passing them on to the spectators.
+//
+// the messages are sent as JSON in WebSocket text frames
+
+pub const DEFAULT_PORT: u16 = 9000;
+pub const ROOM_CODE_LENGTH: usize = 4;
+pub const MAX_SPECTATORS: usize = 256;
+pub const MAX_NAME_LENGTH: usize = 20;
+
+// how far the client shows its own snake ahead of the server,
+// more than this and it waits for the server to catch up
+pub const MAX_PREDICTION_TICKS: u64 = 3;
+
+// a turn the server has not confirmed in this many ticks was not accepted
+const STALE_TURN_TICKS: u64 = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomSettings {
+    pub level: String,
+    pub players: usize,
+    pub spectators: usize,
+    pub rules: Rules,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        RoomSettings {
+            level: Level::default().name,
+            players: 2,
+            spectators: 8,
+            rules: Rules::default(),
+        }
+    }
+}
+
+// a room in the room list
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub code: String,
+    pub level: String,
+    pub players: usize,
+    pub seats: usize,
+    pub spectators: usize,
+    pub playing: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seat {
+    pub name: String,
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // quick match, a seat in any room that has one free, or in a new room
+    Join { name: String },
+    ListRooms,
+    CreateRoom { name: String, settings: RoomSettings },
+    JoinRoom { code: String, name: String, spectate: bool },
+    LeaveRoom,
+    Ready { ready: bool },
+    // only the first player in the room can change the settings
+    ChangeSettings { settings: RoomSettings },
+    Turn { direction: Direction },
+    // a game played somewhere else, like a bot tournament, streamed for spectators to watch:
+    // the first one makes a room for the stream, and each one after it starts the next game there
+    Stream { name: String, settings: RoomSettings, seed: u64, names: Vec<String> },
+    // the turns made on a tick of the streamed game, the tick is the one before stepping
+    StreamTick { tick: u64, turns: Vec<(usize, Direction)> },
+    // the streamed game ended before any snake won, like a bot running out of time
+    StreamEnd,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Rooms { rooms: Vec<RoomInfo> },
+    // the room the client is in, sent whenever it changes, you is the seat of the client
+    Room { code: String, settings: RoomSettings, seats: Vec<Seat>, spectators: Vec<String>, you: Option<usize> },
+    // spectators have no seat
+    Start { seed: u64, rules: Rules, level: String, names: Vec<String>, you: Option<usize> },
+    // every turn made so far in a game that was already running, with the tick it is on
+    Catchup { tick: u64, turns: Vec<(u64, usize, Direction)> },
+    // the turns made on a tick, the tick is the one the simulation was on before stepping
+    Tick { tick: u64, turns: Vec<(usize, Direction)> },
+    End { winner: Option<usize> },
+    Error { message: String },
+}
+
+impl ClientMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("client messages serialize")
+    }
+}
+
+impl ServerMessage {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("server messages serialize")
+    }
+}
+
+// the client side of an online game
+//
+// the confirmed simulation only ever moves on the ticks from the server,
+// the predicted one runs a few ticks ahead of it with the player's own turns
+// applied right away, and everybody else assumed to keep going straight
+#[derive(Clone)]
+pub struct NetGame {
+    // none for a spectator
+    player: Option<usize>,
+    names: Vec<String>,
+    confirmed: Simulation,
+    predicted: Simulation,
+    // own turns sent to the server but not yet seen in a tick, with the predicted tick they were made on
+    pending: VecDeque<(u64, Direction)>,
+    winner: Option<Option<usize>>,
+}
+
+impl NetGame {
+    pub fn new(seed: u64, rules: Rules, level: Level, names: Vec<String>, player: Option<usize>) -> NetGame {
+        let confirmed = Simulation::with_level(seed, rules, level, names.len());
+        NetGame {
+            player,
+            names,
+            predicted: confirmed.clone(),
+            confirmed,
+            pending: VecDeque::new(),
+            winner: None,
+        }
+    }
+
+    pub fn player(&self) -> Option<usize> {
+        self.player
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn confirmed(&self) -> &Simulation {
+        &self.confirmed
+    }
+
+    pub fn predicted(&self) -> &Simulation {
+        &self.predicted
+    }
+
+    // the winner once the server has ended the game
+    pub fn winner(&self) -> Option<Option<usize>> {
+        self.winner
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    // a turn by the local player, returns the message to send when it is worth sending
+    pub fn turn(&mut self, direction: Direction) -> Option<ClientMessage> {
+        let player = self.player?;
+        let snake = self.predicted.snake(player);
+        if !snake.is_alive() || snake.next_turn ||
+            direction == snake.direction || direction == snake.direction.opposite() {
+            return None;
+        }
+        self.predicted.turn(player, direction);
+        self.pending.push_back((self.predicted.tick(), direction));
+        Some(ClientMessage::Turn { direction })
+    }
+
+    // a tick from the server, the events are those of the confirmed game
+    pub fn confirm(&mut self, tick: u64, turns: &[(usize, Direction)]) -> Result<Vec<GameEvent>, String> {
+        if tick != self.confirmed.tick() {
+            return Err(format!("out of sync, got tick {} on tick {}", tick, self.confirmed.tick()));
+        }
+        for (player, direction) in turns {
+            if *player >= self.confirmed.players() {
+                return Err(format!("turn by unknown player {}", player));
+            }
+            self.confirmed.turn(*player, *direction);
+            if Some(*player) == self.player {
+                self.pending.pop_front();
+            }
+        }
+        let events = self.confirmed.step();
+        let confirmed_tick = self.confirmed.tick();
+        self.pending.retain(|(tick, _)| tick + STALE_TURN_TICKS >= confirmed_tick);
+        Ok(events)
+    }
+
+    // bring a game joined late up to the tick the server is on
+    pub fn catch_up(&mut self, tick: u64, turns: &[(u64, usize, Direction)]) -> Result<(), String> {
+        let mut turns = turns.iter().peekable();
+        while self.confirmed.tick() < tick {
+            let mut tick_turns = Vec::new();
+            while let Some((_, player, direction)) = turns.next_if(|(turn_tick, _, _)| *turn_tick == self.confirmed.tick()) {
+                tick_turns.push((*player, *direction));
+            }
+            self.confirm(self.confirmed.tick(), &tick_turns)?;
+        }
+        self.predicted = self.confirmed.clone();
+        Ok(())
+    }
+
+    pub fn finish(&mut self, winner: Option<usize>) {
+        self.winner = Some(winner);
+    }
+
+    // run the predicted game from the confirmed one up to the given tick,
+    // but never more than MAX_PREDICTION_TICKS ahead, spectators have
+    // no turns to predict and see the confirmed game
+    pub fn predict(&mut self, tick: u64) {
+        let ahead = if self.player.is_some() { MAX_PREDICTION_TICKS } else { 0 };
+        let target = tick.min(self.confirmed.tick() + ahead);
+        self.predicted = self.confirmed.clone();
+        let mut pending = self.pending.iter().peekable();
+        loop {
+            // one turn per tick, like the keyboard
+            if let Some((_, direction)) = pending.next_if(|(tick, _)| *tick <= self.predicted.tick()) {
+                if let Some(player) = self.player {
+                    self.predicted.turn(player, *direction);
+                }
+            }
+            if self.predicted.tick() >= target || self.predicted.is_over() {
+                break;
+            }
+            self.predicted.step();
+        }
+    }
+}
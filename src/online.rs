@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+
+use crate::common::AppState;
+use crate::connection::Connection;
+use crate::gameplay::{GameClock, GameSimulation, GameplaySet, step_simulation_system};
+use crate::input::{Bindings, GameInput};
+use crate::level::Level;
+use crate::net::{NetGame, ServerMessage, MAX_PREDICTION_TICKS};
+use crate::simulation::GameEvent;
+
+pub struct OnlinePlugin;
+
+// the connection to the game server, not Send on wasm
+pub struct ServerConnection(pub Connection);
+
+// the online game being played, the gameplay systems show its predicted simulation
+#[derive(Resource)]
+pub struct OnlineGame {
+    pub game: NetGame,
+    // the tick the local clock is on, the prediction runs up to it
+    local_tick: u64,
+}
+
+impl OnlineGame {
+    pub fn new(game: NetGame) -> OnlineGame {
+        let local_tick = game.confirmed().tick();
+        OnlineGame { game, local_tick }
+    }
+}
+
+impl Plugin for OnlinePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_system(online_input_system
+                .before(online_step_system)
+                .in_set(OnUpdate(AppState::Online)))
+            .add_system(online_step_system
+                .before(step_simulation_system)
+                .in_set(GameplaySet)
+                .in_set(OnUpdate(AppState::Online)))
+            .add_system(end_online_game_system.in_schedule(OnExit(AppState::Online)))
+            .add_system(spawn_watching_text_system.in_schedule(OnEnter(AppState::GameOver)))
+            .add_system(watch_next_game_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(disconnect_system.in_schedule(OnEnter(AppState::Gameplay)))
+            .add_system(disconnect_system.in_schedule(OnEnter(AppState::Replay)));
+    }
+}
+
+// every player's input steers the local snake, the way that player steers
+fn online_input_system(mut input: EventReader<GameInput>,
+                       bindings: Res<Bindings>,
+                       mut online: ResMut<OnlineGame>,
+                       mut connection: NonSendMut<ServerConnection>) {
+    let Some(you) = online.game.player() else {
+        return;
+    };
+    for event in input.iter() {
+        if let GameInput::Turn { player, direction } = event {
+            let heading = online.game.predicted().snake(you).direction;
+            let Some(direction) = bindings.steering(*player).direction(*direction, heading) else {
+                continue;
+            };
+            if let Some(message) = online.game.turn(direction) {
+                connection.0.send(&message);
+            }
+        }
+    }
+}
+
+// the confirmed game moves with the ticks from the server and the predicted one
+// with the local clock, the predicted one is what is shown
+fn online_step_system(time: Res<Time>,
+                      mut state: ResMut<NextState<AppState>>,
+                      mut clock: ResMut<GameClock>,
+                      mut online: ResMut<OnlineGame>,
+                      mut simulation: ResMut<GameSimulation>,
+                      mut connection: NonSendMut<ServerConnection>,
+                      mut events: EventWriter<GameEvent>) {
+    while let Some(message) = connection.0.receive() {
+        match message {
+            Ok(ServerMessage::Tick { tick, turns }) => match online.game.confirm(tick, &turns) {
+                Ok(step_events) => events.send_batch(step_events),
+                Err(error) => {
+                    error!("Online game failed: {}", error);
+                    state.set(AppState::GameOver);
+                }
+            },
+            Ok(ServerMessage::Catchup { tick, turns }) => {
+                if let Err(error) = online.game.catch_up(tick, &turns) {
+                    error!("Could not catch up with the online game: {}", error);
+                    state.set(AppState::GameOver);
+                }
+            }
+            Ok(ServerMessage::End { winner }) => {
+                online.game.finish(winner);
+                // a game stopped before it was over has no deaths to end it,
+                // and a spectator leaves the messages of the next game for later
+                if !online.game.confirmed().is_over() {
+                    state.set(AppState::GameOver);
+                    break;
+                }
+            }
+            Ok(message) => debug!("Unexpected message during the game: {:?}", message),
+            Err(error) => {
+                if !online.game.is_finished() {
+                    error!("Lost the connection to the server: {}", error);
+                    state.set(AppState::GameOver);
+                }
+                break;
+            }
+        }
+    }
+
+    // the local clock stays between the server and the furthest it may predict
+    let confirmed = online.game.confirmed().tick();
+    online.local_tick = (online.local_tick + clock.update(time.delta()) as u64)
+        .clamp(confirmed, confirmed + MAX_PREDICTION_TICKS);
+    let local_tick = online.local_tick;
+    online.game.predict(local_tick);
+    simulation.0.clone_from(online.game.predicted());
+}
+
+// spectators stay in the room to watch the next game
+fn end_online_game_system(mut commands: Commands,
+                          online: Res<OnlineGame>) {
+    commands.remove_resource::<OnlineGame>();
+    if online.game.player().is_some() {
+        commands.add(|world: &mut World| {
+            world.remove_non_send_resource::<ServerConnection>();
+        });
+    }
+}
+
+fn spawn_watching_text_system(mut commands: Commands,
+                              asset_server: Res<AssetServer>,
+                              connection: Option<NonSend<ServerConnection>>) {
+    if connection.is_none() {
+        return;
+    }
+    commands.spawn(
+        TextBundle::from_section(
+            "Watching, the next game is shown when it starts",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 30.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Percent(20.0),
+                right: Val::Px(250.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(crate::common::Text);
+}
+
+fn watch_next_game_system(mut commands: Commands,
+                          mut state: ResMut<NextState<AppState>>,
+                          connection: Option<NonSendMut<ServerConnection>>) {
+    let Some(mut connection) = connection else {
+        return;
+    };
+    while let Some(message) = connection.0.receive() {
+        match message {
+            Ok(ServerMessage::Start { seed, rules, level, names, you }) => {
+                debug!("Watching the next online game with seed {}", seed);
+                let level = Level::named(&level).unwrap_or_default();
+                commands.insert_resource(OnlineGame::new(NetGame::new(seed, rules, level, names, you)));
+                state.set(AppState::Online);
+                break;
+            }
+            Ok(ServerMessage::Error { message }) | Err(message) => {
+                error!("Stopped watching: {}", message);
+                commands.add(|world: &mut World| {
+                    world.remove_non_send_resource::<ServerConnection>();
+                });
+                break;
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+fn disconnect_system(mut commands: Commands) {
+    commands.add(|world: &mut World| {
+        world.remove_non_send_resource::<ServerConnection>();
+    });
+}
@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+
+use crate::common::GRID_SIZE;
+
+// both ends of a portal are drawn in the same colour, a different one for every pair
+#[derive(Component, Default)]
+pub struct Portal {}
+
+impl Portal {
+    pub fn new() -> Portal {
+        Portal {}
+    }
+
+    pub fn color(index: usize) -> Color {
+        Color::hsla((index * 40 % 360) as f32, 0.8, 0.5, 0.9)
+    }
+
+    // a ring of the colour of the pair around a dark middle, at the middle of a place
+    pub fn sprites(color: Color) -> [SpriteBundle; 2] {
+        [
+            SpriteBundle {
+                sprite: Sprite { color, custom_size: Some(Vec2::splat(GRID_SIZE)), ..default() },
+                ..default()
+            },
+            SpriteBundle {
+                sprite: Sprite { color: Color::rgb(0.1, 0.1, 0.15), custom_size: Some(Vec2::splat(GRID_SIZE / 2.0)), ..default() },
+                transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                ..default()
+            },
+        ]
+    }
+}
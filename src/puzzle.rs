@@ -0,0 +1,505 @@
+use std::sync::OnceLock;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::spawn_text;
+use crate::common::{AppState, Direction, GridPosition, ScreenPosition};
+use crate::gameplay::{GameClock, GameMode, GameSetup, GameSimulation, step_simulation_system};
+use crate::input::{Bindings, GameInput};
+use crate::level::{Level, read_map};
+use crate::simulation::{Rules, Simulation};
+use crate::storage;
+
+// the puzzles are played alone and turn by turn: the snake only moves when a direction
+// is pressed, and a puzzle is solved by eating all the food and then reaching the exit
+//
+// the puzzles are drawn in assets/puzzles.json, which is built into the game:
+// # is a wall, * food, E the exit, H the head of the snake and o its body,
+// and everything around a puzzle is wall
+
+pub const PUZZLE_PROGRESS: &str = "puzzles.json";
+
+// all the food of the puzzles is apples
+const FOOD_KIND: usize = 0;
+
+pub struct PuzzlePlugin;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    pub name: String,
+    pub level: Level,
+    // the head first
+    pub snake: Vec<GridPosition>,
+    pub direction: Direction,
+    pub foods: Vec<GridPosition>,
+    pub exit: GridPosition,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PuzzleMap {
+    name: String,
+    map: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Puzzles {
+    puzzles: Vec<PuzzleMap>,
+}
+
+impl Puzzle {
+    pub fn parse(name: &str, map: &[String]) -> Result<Puzzle, String> {
+        let mut walls = Vec::new();
+        let mut foods = Vec::new();
+        let mut body = Vec::new();
+        let mut head = None;
+        let mut exit = None;
+        for (position, cell) in read_map(map)? {
+            match cell {
+                '.' => {}
+                '#' => walls.push(position),
+                '*' => foods.push(position),
+                'o' => body.push(position),
+                'H' if head.is_none() => head = Some(position),
+                'E' if exit.is_none() => exit = Some(position),
+                'H' | 'E' => return Err(format!("there can only be one {}", cell)),
+                _ => return Err(format!("unknown cell {:?} at {}", cell, position)),
+            }
+        }
+        let head = head.ok_or("there is no head")?;
+        let exit = exit.ok_or("there is no exit")?;
+        if foods.is_empty() {
+            return Err("there is no food".to_string());
+        }
+
+        // the body is followed from the head, a piece next to the last one at a time
+        let mut snake = vec![head];
+        while !body.is_empty() {
+            let last = snake[snake.len() - 1];
+            let next: Vec<usize> = (0..body.len())
+                .filter(|index| heading(body[*index], last).is_some())
+                .collect();
+            let [index] = next[..] else {
+                return Err("the body of the snake must be a single line from the head".to_string());
+            };
+            snake.push(body.swap_remove(index));
+        }
+        let direction = snake.get(1)
+            .and_then(|neck| heading(*neck, head))
+            .ok_or("the snake needs a body to know where it is heading")?;
+
+        Ok(Puzzle {
+            name: name.to_string(),
+            level: Level { name: name.to_string(), walls, ..Level::default() },
+            snake,
+            direction,
+            foods,
+            exit,
+        })
+    }
+
+    pub fn place(&self, simulation: &mut Simulation) {
+        simulation.place_snake(0, &self.snake, self.direction);
+        for position in &self.foods {
+            simulation.place_food(FOOD_KIND, *position);
+        }
+    }
+}
+
+// the direction from a position to the one next to it
+fn heading(from: GridPosition, to: GridPosition) -> Option<Direction> {
+    Direction::ALL.into_iter().find(|direction| {
+        let mut position = from;
+        position.move_position(*direction, 1);
+        position == to
+    })
+}
+
+pub fn puzzles() -> &'static [Puzzle] {
+    static PUZZLES: OnceLock<Vec<Puzzle>> = OnceLock::new();
+    PUZZLES.get_or_init(|| {
+        let puzzles: Puzzles = serde_json::from_str(include_str!("../assets/puzzles.json"))
+            .unwrap_or_else(|error| panic!("the puzzles can not be read: {}", error));
+        assert!(!puzzles.puzzles.is_empty(), "there are no puzzles");
+        puzzles.puzzles.iter()
+            .map(|puzzle| Puzzle::parse(&puzzle.name, &puzzle.map)
+                .unwrap_or_else(|error| panic!("the puzzle {} is wrong: {}", puzzle.name, error)))
+            .collect()
+    })
+}
+
+// nothing appears on its own in the puzzles
+pub fn rules() -> Rules {
+    Rules { food_spawn_ticks: 0, bomb_spawn_ticks: 0, ..Rules::default() }
+}
+
+// how many puzzles have been solved, the next one is unlocked
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PuzzleProgress {
+    pub solved: usize,
+}
+
+impl PuzzleProgress {
+    // the puzzles start over once they have all been solved
+    pub fn next_puzzle(&self) -> usize {
+        if self.solved < puzzles().len() { self.solved } else { 0 }
+    }
+}
+
+// the puzzle being played: the games before each move made, to undo them,
+// and the games undone, to make the moves again
+#[derive(Resource, Default)]
+pub struct PuzzleRun {
+    undo: Vec<Simulation>,
+    redo: Vec<Simulation>,
+    pub status: String,
+}
+
+impl PuzzleRun {
+    // the move is made on the next step of the game, returns whether it can be made at all
+    pub fn make_move(&mut self, simulation: &mut Simulation, direction: Direction) -> bool {
+        if !can_move(simulation, direction) {
+            return false;
+        }
+        self.undo.push(simulation.clone());
+        self.redo.clear();
+        simulation.turn(0, direction);
+        true
+    }
+
+    pub fn undo(&mut self, simulation: &mut Simulation) {
+        if let Some(game) = self.undo.pop() {
+            let undone = std::mem::replace(simulation, game);
+            self.redo.push(undone);
+        }
+    }
+
+    pub fn redo(&mut self, simulation: &mut Simulation) {
+        if let Some(game) = self.redo.pop() {
+            let redone = std::mem::replace(simulation, game);
+            self.undo.push(redone);
+        }
+    }
+
+    pub fn moves(&self) -> usize {
+        self.undo.len()
+    }
+}
+
+// how many moves the last puzzle solved took
+#[derive(Resource)]
+struct Solved {
+    moves: usize,
+}
+
+#[derive(Component)]
+struct Exit;
+
+impl Exit {
+    const COLOR: Color = Color::rgb(0.9, 0.8, 0.2);
+    const SIZE: f32 = 24.0;
+    // under the food and the snake
+    const Z_DEPTH: f32 = 40.0;
+}
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(load_progress())
+            .add_system(start_puzzle_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(start_run_system.in_schedule(OnEnter(AppState::Gameplay)))
+            .add_system(move_system
+                .before(step_simulation_system)
+                .run_if(resource_exists::<PuzzleRun>())
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_system(solved_system
+                .after(step_simulation_system)
+                .run_if(resource_exists::<PuzzleRun>())
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_system(end_run_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(puzzle_game_over_system.in_schedule(OnEnter(AppState::GameOver)))
+            .add_system(spawn_solved_system.in_schedule(OnEnter(AppState::LevelComplete)))
+            .add_system(solved_input_system.in_set(OnUpdate(AppState::LevelComplete)));
+    }
+}
+
+fn load_progress() -> PuzzleProgress {
+    let progress = storage::load(PUZZLE_PROGRESS)
+        .and_then(|data| serde_json::from_slice::<PuzzleProgress>(&data).map_err(std::io::Error::from));
+    match progress {
+        Ok(progress) => progress,
+        Err(error) => {
+            debug!("No puzzle progress loaded: {}", error);
+            PuzzleProgress::default()
+        }
+    }
+}
+
+fn start_puzzle_input_system(mut state: ResMut<NextState<AppState>>,
+                             mut setup: ResMut<GameSetup>,
+                             mut mode: ResMut<GameMode>,
+                             progress: Res<PuzzleProgress>,
+                             keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::Z) {
+        setup.players = 1;
+        *mode = GameMode::Puzzle { level: progress.next_puzzle() };
+        state.set(AppState::Gameplay);
+    }
+}
+
+fn start_run_system(mut commands: Commands,
+                    mode: Res<GameMode>) {
+    let GameMode::Puzzle { level } = *mode else {
+        return;
+    };
+    let puzzle = &puzzles()[level];
+    debug!("Playing puzzle {}: {}", level + 1, puzzle.name);
+    commands.insert_resource(PuzzleRun::default());
+
+    let screen_pos = ScreenPosition::from(puzzle.exit);
+    commands.spawn(
+        SpriteBundle {
+            sprite: Sprite {
+                color: Exit::COLOR,
+                ..default()
+            },
+            transform: Transform {
+                scale: Vec3::new(Exit::SIZE, Exit::SIZE, 1.0),
+                translation: Vec3::new(screen_pos.x, screen_pos.y, Exit::Z_DEPTH),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Exit);
+}
+
+// one move a frame, moves into the walls or the snake itself are not made,
+// so a puzzle can not be lost, only undone
+fn move_system(mut state: ResMut<NextState<AppState>>,
+               bindings: Res<Bindings>,
+               keyboard_input: Res<Input<KeyCode>>,
+               mut input: EventReader<GameInput>,
+               mut simulation: ResMut<GameSimulation>,
+               mut clock: ResMut<GameClock>,
+               mut run: ResMut<PuzzleRun>) {
+    let mut moved = false;
+    if keyboard_input.just_pressed(KeyCode::U) {
+        run.undo(&mut simulation.0);
+        moved = true;
+    }
+    else if keyboard_input.just_pressed(KeyCode::Y) {
+        run.redo(&mut simulation.0);
+        moved = true;
+    }
+
+    for event in input.iter() {
+        match event {
+            GameInput::Back => state.set(AppState::GameOver),
+            GameInput::Turn { player: 0, direction } if !moved => {
+                let heading = simulation.snake(0).direction;
+                // relative steering goes ahead with up
+                let Some(direction) = bindings.steering(0).direction(*direction, heading)
+                    .or((*direction == Direction::Up).then_some(heading)) else {
+                    continue;
+                };
+                if run.make_move(&mut simulation.0, direction) {
+                    clock.step_once();
+                    moved = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn can_move(simulation: &Simulation, direction: Direction) -> bool {
+    let snake = simulation.snake(0);
+    let mut next = snake.head();
+    next.move_position(direction, 1);
+    direction != snake.direction.opposite() && !simulation.is_blocked(next)
+}
+
+fn solved_system(mut commands: Commands,
+                 mut state: ResMut<NextState<AppState>>,
+                 mode: Res<GameMode>,
+                 simulation: Res<GameSimulation>,
+                 mut progress: ResMut<PuzzleProgress>,
+                 mut run: ResMut<PuzzleRun>) {
+    let GameMode::Puzzle { level } = *mode else {
+        return;
+    };
+    let stuck = !Direction::ALL.into_iter().any(|direction| can_move(&simulation, direction));
+    run.status = format!("Moves: {}    Food left: {}", run.moves(), simulation.foods().len());
+    if stuck {
+        run.status += "    Stuck, U to undo";
+    }
+
+    if simulation.foods().is_empty() && simulation.snake(0).head() == puzzles()[level].exit {
+        debug!("Puzzle {} solved in {} moves", level + 1, run.moves());
+        commands.insert_resource(Solved { moves: run.moves() });
+        if progress.solved <= level {
+            progress.solved = level + 1;
+            let data = serde_json::to_vec_pretty(&*progress).expect("the progress can always be serialized");
+            if let Err(error) = storage::save(PUZZLE_PROGRESS, &data) {
+                error!("Failed to save the puzzle progress: {}", error);
+            }
+        }
+        state.set(AppState::LevelComplete);
+    }
+}
+
+fn end_run_system(mut commands: Commands,
+                  query: Query<Entity, With<Exit>>) {
+    commands.remove_resource::<PuzzleRun>();
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+// the puzzle given up is tried again by continuing
+fn puzzle_game_over_system(mut commands: Commands,
+                           asset_server: Res<AssetServer>,
+                           mode: Res<GameMode>) {
+    if let GameMode::Puzzle { level } = *mode {
+        spawn_text(&mut commands, &asset_server,
+                   format!("Puzzle {} of {}: {}\nEat all the food, then find the exit",
+                           level + 1, puzzles().len(), puzzles()[level].name),
+                   22.0, Val::Px(10.0));
+    }
+}
+
+fn spawn_solved_system(mut commands: Commands,
+                       asset_server: Res<AssetServer>,
+                       mode: Res<GameMode>,
+                       solved: Option<Res<Solved>>) {
+    let GameMode::Puzzle { level } = *mode else {
+        return;
+    };
+    let moves = solved.map_or(0, |solved| solved.moves);
+    spawn_text(&mut commands, &asset_server,
+               format!("Puzzle {} solved in {} moves", level + 1, moves), 60.0, Val::Percent(25.0));
+    let next = match puzzles().get(level + 1) {
+        Some(next) => format!("Continue to puzzle {}: {}", level + 2, next.name),
+        None => "That was the last puzzle, they are all solved\nContinue to get back to the menu".to_string(),
+    };
+    spawn_text(&mut commands, &asset_server, next, 30.0, Val::Percent(45.0));
+}
+
+fn solved_input_system(mut state: ResMut<NextState<AppState>>,
+                       mut mode: ResMut<GameMode>,
+                       mut input: EventReader<GameInput>) {
+    let GameMode::Puzzle { level } = *mode else {
+        return;
+    };
+    for event in input.iter() {
+        match event {
+            GameInput::Confirm if level + 1 < puzzles().len() => {
+                *mode = GameMode::Puzzle { level: level + 1 };
+                state.set(AppState::Gameplay);
+            }
+            GameInput::Confirm | GameInput::Back => {
+                *mode = GameMode::Classic;
+                state.set(AppState::GameOver);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use super::*;
+
+    fn start(puzzle: &Puzzle) -> Simulation {
+        let mut simulation = Simulation::with_level(0, rules(), puzzle.level.clone(), 1);
+        puzzle.place(&mut simulation);
+        simulation
+    }
+
+    fn is_solved(puzzle: &Puzzle, simulation: &Simulation) -> bool {
+        simulation.foods().is_empty() && simulation.snake(0).head() == puzzle.exit
+    }
+
+    // the fewest moves that solve the puzzle, trying every move the game lets be made
+    fn solve(puzzle: &Puzzle) -> Option<usize> {
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from([(start(puzzle), 0)]);
+        while let Some((simulation, moves)) = queue.pop_front() {
+            if is_solved(puzzle, &simulation) {
+                return Some(moves);
+            }
+            for direction in Direction::ALL {
+                let mut next = simulation.clone();
+                if !PuzzleRun::default().make_move(&mut next, direction) {
+                    continue;
+                }
+                next.step();
+                let body: Vec<GridPosition> = next.snake(0).body.iter().copied().collect();
+                let foods: Vec<GridPosition> = next.foods().iter().map(|food| food.position).collect();
+                if next.snake(0).is_alive() && seen.insert((body, foods)) {
+                    queue.push_back((next, moves + 1));
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn every_puzzle_can_be_solved() {
+        for puzzle in puzzles() {
+            assert!(solve(puzzle).is_some(), "the puzzle {} can not be solved", puzzle.name);
+        }
+    }
+
+    #[test]
+    fn broken_puzzles_are_rejected() {
+        let parse = |map: &[&str]| Puzzle::parse("broken", &map.iter().map(ToString::to_string).collect::<Vec<_>>());
+        assert!(parse(&["ooH.*.E"]).is_ok());
+        assert!(parse(&["oo..*.E"]).is_err());
+        assert!(parse(&["ooH.*.H"]).is_err());
+        assert!(parse(&["ooH...E"]).is_err());
+        assert!(parse(&["ooH.*.."]).is_err());
+        assert!(parse(&["o.H.*.E"]).is_err());
+        assert!(parse(&["ooH.*.E", ".o....."]).is_err());
+        assert!(parse(&["ooH.*.E", "..."]).is_err());
+        assert!(parse(&["ooH.?.E"]).is_err());
+    }
+
+    #[test]
+    fn moves_can_be_undone_and_made_again() {
+        let puzzle = &puzzles()[0];
+        let mut simulation = start(puzzle);
+        let mut run = PuzzleRun::default();
+        let heads = |simulation: &Simulation| simulation.snake(0).head();
+        let first = heads(&simulation);
+
+        assert!(!run.make_move(&mut simulation, puzzle.direction.opposite()));
+        for _ in 0..2 {
+            assert!(run.make_move(&mut simulation, puzzle.direction));
+            simulation.step();
+        }
+        let second = heads(&simulation);
+        assert_eq!(run.moves(), 2);
+
+        run.undo(&mut simulation);
+        run.undo(&mut simulation);
+        run.undo(&mut simulation);
+        assert_eq!((heads(&simulation), run.moves()), (first, 0));
+        run.redo(&mut simulation);
+        run.redo(&mut simulation);
+        assert_eq!((heads(&simulation), run.moves()), (second, 2));
+
+        // a new move forgets the ones undone
+        run.undo(&mut simulation);
+        assert!(run.make_move(&mut simulation, puzzle.direction));
+        simulation.step();
+        run.redo(&mut simulation);
+        assert_eq!(run.moves(), 2);
+        assert_eq!(heads(&simulation), second);
+    }
+}
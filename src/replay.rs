@@ -0,0 +1,385 @@
+use std::io::{Error, ErrorKind};
+
+use crate::common::Direction;
+use crate::level::Level;
+use crate::simulation::{DeathCause, GameEvent, Rules, Simulation, MAX_PLAYERS};
+
+// a game is fully determined by its seed, rules and the turns made,
+// so that is all a replay needs to store
+//
+// file format, numbers are LEB128 varints unless said otherwise:
+//   "SNKR", version (byte), seed (8 bytes, little endian),
+//   food spawn ticks, bomb spawn ticks, bomb fuse ticks, clock spawn ticks
+//   (spawn ticks of 0 mean none of it spawns),
+//   level name (length and UTF-8 bytes), player count,
+//   turn count, for every turn: ticks since the previous turn, player and direction (byte),
+//   result: ticks, for every player: score, length, death cause (byte)
+//
+// the rules are stored as they were played, files with other versions are not read
+
+const MAGIC: &[u8; 4] = b"SNKR";
+const VERSION: u8 = 1;
+
+// over a month of play, anything longer is not a real game
+const MAX_TICKS: u64 = 16_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerResult {
+    pub score: u32,
+    pub length: u32,
+    pub death: Option<DeathCause>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayResult {
+    pub ticks: u64,
+    pub players: Vec<PlayerResult>,
+}
+
+impl ReplayResult {
+    pub fn of(simulation: &Simulation) -> ReplayResult {
+        ReplayResult {
+            ticks: simulation.tick(),
+            players: simulation.snakes().iter()
+                .map(|snake| PlayerResult {
+                    score: snake.score,
+                    length: snake.len() as u32,
+                    death: snake.death,
+                })
+                .collect(),
+        }
+    }
+
+    // the score of the first player, the only one in a single player game
+    pub fn score(&self) -> u32 {
+        self.players.first().map_or(0, |player| player.score)
+    }
+}
+
+impl std::fmt::Display for ReplayResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ticks", self.ticks)?;
+        for (index, player) in self.players.iter().enumerate() {
+            if self.players.len() > 1 {
+                write!(f, ", player {}:", index + 1)?;
+            }
+            else {
+                write!(f, ",")?;
+            }
+            write!(f, " score {}, length {}, death: {}",
+                   player.score, player.length,
+                   player.death.map_or("none", |cause| cause.name()))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    pub level: String,
+    pub players: usize,
+    pub turns: Vec<(u64, usize, Direction)>,
+    pub result: ReplayResult,
+}
+
+impl Replay {
+    pub fn record(simulation: &Simulation) -> Replay {
+        Replay {
+            seed: simulation.seed(),
+            rules: simulation.rules().clone(),
+            level: simulation.level().name.clone(),
+            players: simulation.players(),
+            turns: simulation.turns().to_vec(),
+            result: ReplayResult::of(simulation),
+        }
+    }
+
+    pub fn new_simulation(&self) -> Simulation {
+        let level = Level::named(&self.level).unwrap_or_default();
+        Simulation::with_level(self.seed, self.rules.clone(), level, self.players)
+    }
+
+    // play the whole game through without showing it
+    pub fn simulate(&self) -> Simulation {
+        let mut simulation = self.new_simulation();
+        let mut playback = Playback::new(self);
+        while !simulation.is_over() && simulation.tick() < self.result.ticks {
+            playback.step(&mut simulation);
+        }
+        simulation
+    }
+
+    // play the game again and check that it ends the way the replay says it did
+    pub fn verify(&self) -> Result<(), String> {
+        let result = ReplayResult::of(&self.simulate());
+        if result == self.result {
+            Ok(())
+        }
+        else {
+            Err(format!("expected {}, got {}", self.result, result))
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        write_varint(&mut data, self.rules.food_spawn_ticks);
+        write_varint(&mut data, self.rules.bomb_spawn_ticks);
+        write_varint(&mut data, self.rules.bomb_fuse_ticks as u64);
+        write_varint(&mut data, self.rules.clock_spawn_ticks);
+        write_varint(&mut data, self.level.len() as u64);
+        data.extend_from_slice(self.level.as_bytes());
+        write_varint(&mut data, self.players as u64);
+
+        write_varint(&mut data, self.turns.len() as u64);
+        let mut previous = 0;
+        for (tick, player, direction) in &self.turns {
+            write_varint(&mut data, tick - previous);
+            write_varint(&mut data, *player as u64);
+            data.push(direction_code(*direction));
+            previous = *tick;
+        }
+
+        write_varint(&mut data, self.result.ticks);
+        for player in &self.result.players {
+            write_varint(&mut data, player.score as u64);
+            write_varint(&mut data, player.length as u64);
+            data.push(death_code(player.death));
+        }
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> std::io::Result<Replay> {
+        let mut reader = Reader { data, position: 0 };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported replay version {}", version)));
+        }
+        let seed = u64::from_le_bytes(reader.bytes(8)?.try_into().expect("eight bytes"));
+        let rules = Rules {
+            food_spawn_ticks: reader.varint()?,
+            bomb_spawn_ticks: reader.varint()?,
+            bomb_fuse_ticks: reader.varint()?.try_into().map_err(|_| invalid("bomb fuse is too long"))?,
+            clock_spawn_ticks: reader.varint()?,
+        };
+        let length = reader.varint()? as usize;
+        let level = String::from_utf8(reader.bytes(length)?.to_vec()).map_err(|_| invalid("level name is not UTF-8"))?;
+        if Level::named(&level).is_none() {
+            return Err(invalid(&format!("unknown level {}", level)));
+        }
+        let players = reader.varint()? as usize;
+        if !(1..=MAX_PLAYERS).contains(&players) {
+            return Err(invalid(&format!("unsupported player count {}", players)));
+        }
+
+        let count = reader.varint()?;
+        let mut turns = Vec::new();
+        let mut tick: u64 = 0;
+        for _ in 0..count {
+            tick = tick.checked_add(reader.varint()?).ok_or_else(|| invalid("turn is too late"))?;
+            let player = reader.varint()? as usize;
+            if player >= players {
+                return Err(invalid(&format!("turn by unknown player {}", player)));
+            }
+            let direction = direction_from_code(reader.byte()?)?;
+            turns.push((tick, player, direction));
+        }
+
+        let ticks = reader.varint()?;
+        let mut results = Vec::new();
+        for _ in 0..players {
+            results.push(PlayerResult {
+                score: reader.varint()? as u32,
+                length: reader.varint()? as u32,
+                death: death_from_code(reader.byte()?)?,
+            });
+        }
+        let result = ReplayResult { ticks, players: results };
+        if !reader.is_done() {
+            return Err(invalid("replay file has data after the result"));
+        }
+        if result.ticks > MAX_TICKS || tick > result.ticks {
+            return Err(invalid("replay is longer than a game can be"));
+        }
+        Ok(Replay { seed, rules, level, players, turns, result })
+    }
+}
+
+// feeds the recorded turns to a simulation at the ticks they were made on
+#[derive(Clone)]
+pub struct Playback {
+    turns: Vec<(u64, usize, Direction)>,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Playback {
+        Playback { turns: replay.turns.clone(), next: 0 }
+    }
+
+    pub fn step(&mut self, simulation: &mut Simulation) -> Vec<GameEvent> {
+        while let Some((tick, player, direction)) = self.turns.get(self.next) {
+            if *tick > simulation.tick() {
+                break;
+            }
+            if *tick == simulation.tick() {
+                simulation.turn(*player, *direction);
+            }
+            self.next += 1;
+        }
+        simulation.step()
+    }
+}
+
+fn direction_code(direction: Direction) -> u8 {
+    match direction {
+        Direction::Left => 0,
+        Direction::Right => 1,
+        Direction::Up => 2,
+        Direction::Down => 3,
+    }
+}
+
+fn direction_from_code(code: u8) -> std::io::Result<Direction> {
+    Direction::ALL.into_iter()
+        .find(|direction| direction_code(*direction) == code)
+        .ok_or_else(|| invalid(&format!("unknown direction {}", code)))
+}
+
+fn death_code(death: Option<DeathCause>) -> u8 {
+    match death {
+        None => 0,
+        Some(DeathCause::Wall) => 1,
+        Some(DeathCause::Body) => 2,
+        Some(DeathCause::Explosion) => 3,
+        Some(DeathCause::Snake) => 4,
+        Some(DeathCause::HeadOn) => 5,
+        Some(DeathCause::Enemy) => 6,
+    }
+}
+
+fn death_from_code(code: u8) -> std::io::Result<Option<DeathCause>> {
+    match code {
+        0 => Ok(None),
+        1 => Ok(Some(DeathCause::Wall)),
+        2 => Ok(Some(DeathCause::Body)),
+        3 => Ok(Some(DeathCause::Explosion)),
+        4 => Ok(Some(DeathCause::Snake)),
+        5 => Ok(Some(DeathCause::HeadOn)),
+        6 => Ok(Some(DeathCause::Enemy)),
+        _ => Err(invalid(&format!("unknown death cause {}", code))),
+    }
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            data.push(byte);
+            break;
+        }
+        data.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> std::io::Result<&[u8]> {
+        let end = self.position.checked_add(count)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "replay file is truncated"))?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn is_done(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn byte(&mut self) -> std::io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> std::io::Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("number is too long"))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played() -> Replay {
+        let rules = Rules { clock_spawn_ticks: 7, ..Rules::default() };
+        let mut simulation = Simulation::new(42, rules, 2);
+        for tick in 0..30 {
+            match tick % 6 {
+                0 => simulation.turn(0, Direction::Up),
+                3 => simulation.turn(0, Direction::Left),
+                _ => simulation.turn(1, if tick % 2 == 0 { Direction::Down } else { Direction::Right }),
+            }
+            simulation.step();
+        }
+        Replay::record(&simulation)
+    }
+
+    #[test]
+    fn decodes_what_was_encoded() {
+        let replay = played();
+        assert!(!replay.turns.is_empty());
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+        assert_eq!(replay.verify(), Ok(()));
+    }
+
+    #[test]
+    fn games_without_spawns_are_played_back_without_them() {
+        let rules = Rules { food_spawn_ticks: 0, bomb_spawn_ticks: 0, ..Rules::default() };
+        let mut simulation = Simulation::new(7, rules.clone(), 1);
+        for _ in 0..40 {
+            simulation.step();
+        }
+        let replay = Replay::decode(&Replay::record(&simulation).encode()).unwrap();
+        assert_eq!(replay.rules, rules);
+        assert_eq!(replay.verify(), Ok(()));
+        let mut playback = replay.new_simulation();
+        while playback.tick() < replay.result.ticks {
+            playback.step();
+            assert!(playback.foods().is_empty() && playback.bombs().is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let data = played().encode();
+        assert!(Replay::decode(&data[..data.len() - 1]).is_err());
+        assert!(Replay::decode(&[data.as_slice(), &[0]].concat()).is_err());
+        let mut other_version = data.clone();
+        other_version[MAGIC.len()] = VERSION + 1;
+        assert!(Replay::decode(&other_version).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::common::AppState;
+use crate::gameplay::{GameClock, GameMode, GameSimulation};
+use crate::replay::{Playback, Replay};
+use crate::storage;
+
+pub const LAST_REPLAY: &str = "last.snkr";
+const FAST_FORWARD_SPEED: f32 = 4.0;
+
+pub struct ReplayScreenPlugin;
+
+// the replay of the latest game, ready to be watched
+#[derive(Resource)]
+pub struct LastReplay(pub Replay);
+
+// the replay being shown, the gameplay systems take the turns from it
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub playback: Playback,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        let playback = Playback::new(&replay);
+        ReplayPlayback { replay, playback }
+    }
+}
+
+#[derive(Component)]
+struct ReplayText;
+
+impl Plugin for ReplayScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_startup_system(load_last_replay_system)
+            .add_system(record_replay_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(watch_replay_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(spawn_replay_text_system.in_schedule(OnEnter(AppState::Replay)))
+            .add_systems((replay_control_system, update_replay_text_system)
+                .chain()
+                .in_set(OnUpdate(AppState::Replay)))
+            .add_system(despawn_replay_system.in_schedule(OnExit(AppState::Replay)));
+    }
+}
+
+fn load_last_replay_system(mut commands: Commands) {
+    match storage::load(LAST_REPLAY).and_then(|data| Replay::decode(&data)) {
+        Ok(replay) => commands.insert_resource(LastReplay(replay)),
+        Err(error) => debug!("No earlier replay loaded: {}", error),
+    }
+}
+
+// the food of the puzzles is placed by hand, which replays can not do,
+// and the levels being made in the editor have no name to be found by
+fn record_replay_system(mut commands: Commands,
+                        mode: Res<GameMode>,
+                        simulation: Res<GameSimulation>) {
+    if let GameMode::Puzzle { .. } | GameMode::Editor = *mode {
+        return;
+    }
+    let replay = Replay::record(&simulation);
+    debug!("Recorded a replay of {} turns, {}", replay.turns.len(), replay.result);
+    if let Err(error) = storage::save(LAST_REPLAY, &replay.encode()) {
+        error!("Failed to save the replay: {}", error);
+    }
+    commands.insert_resource(LastReplay(replay));
+}
+
+fn watch_replay_input_system(mut commands: Commands,
+                             mut state: ResMut<NextState<AppState>>,
+                             keyboard_input: Res<Input<KeyCode>>,
+                             last_replay: Option<Res<LastReplay>>) {
+    if let Some(last_replay) = last_replay {
+        if keyboard_input.just_pressed(KeyCode::R) {
+            commands.insert_resource(ReplayPlayback::new(last_replay.0.clone()));
+            state.set(AppState::Replay);
+        }
+    }
+}
+
+fn spawn_replay_text_system(mut commands: Commands,
+                            asset_server: Res<AssetServer>) {
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(ReplayText);
+}
+
+fn replay_control_system(mut state: ResMut<NextState<AppState>>,
+                         mut clock: ResMut<GameClock>,
+                         keyboard_input: Res<Input<KeyCode>>,
+                         simulation: Res<GameSimulation>,
+                         playback: Res<ReplayPlayback>) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        clock.paused = !clock.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::F) {
+        clock.speed = if clock.speed > 1.0 { 1.0 } else { FAST_FORWARD_SPEED };
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) && clock.paused {
+        clock.step_once();
+    }
+
+    // a game that did not end in a death stops where the recording stopped
+    let finished = !simulation.is_over() && simulation.tick() >= playback.replay.result.ticks;
+    if keyboard_input.just_pressed(KeyCode::Return) || finished {
+        state.set(AppState::GameOver);
+    }
+}
+
+fn update_replay_text_system(clock: Res<GameClock>,
+                             simulation: Res<GameSimulation>,
+                             mut query: Query<&mut Text, With<ReplayText>>) {
+    let status = if clock.paused {
+        "paused".to_string()
+    }
+    else {
+        format!("x{}", clock.speed)
+    };
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "Replay, tick {} ({})  -  space: pause, F: fast forward, right: step, enter: stop",
+            simulation.tick(), status);
+    }
+}
+
+fn despawn_replay_system(mut commands: Commands,
+                         query: Query<Entity, With<ReplayText>>) {
+    debug!("Running despawn replay system");
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<ReplayPlayback>();
+}
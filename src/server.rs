@@ -0,0 +1,833 @@
+// the game server for online play
+//
+// clients connect with a WebSocket and pick a room from the lobby,
+// see net.rs for the messages. The server runs the games of all the rooms
+// at the normal tick rate and broadcasts the turns made on every tick
+// to the players and spectators of the room. Streamed games are stepped
+// by the ticks their streamer sends instead.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+
+use crate::common::Direction;
+use crate::level::Level;
+use crate::net::{ClientMessage, RoomInfo, RoomSettings, Seat, ServerMessage, MAX_NAME_LENGTH, MAX_SPECTATORS, ROOM_CODE_LENGTH};
+use crate::simulation::{Simulation, MAX_PLAYERS, TICK_MILLIS};
+
+// turns sent faster than the ticks wait for the next ones, up to this many
+const MAX_QUEUED_TURNS: usize = 3;
+// a client this many messages behind is too slow to keep up with the game and is disconnected
+const MAX_QUEUED_MESSAGES: usize = 256;
+// the messages of all the clients wait for the lobby here, the clients sending more wait their turn
+const MAX_QUEUED_EVENTS: usize = 1024;
+// no client message comes close to this
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+// the rules of the rooms stay within these, ten minutes at most between the spawns
+const MAX_SPAWN_TICKS: u64 = 600_000 / TICK_MILLIS;
+const MAX_FUSE_TICKS: u32 = (60_000 / TICK_MILLIS) as u32;
+// no I or O, they look like numbers
+const ROOM_CODE_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub address: String,
+    // the settings of the rooms made by quick match
+    pub settings: RoomSettings,
+    pub seed: Option<u64>,
+}
+
+enum Event {
+    Connected { id: u64, sender: Sender<ServerMessage> },
+    Message { id: u64, message: ClientMessage },
+    Disconnected { id: u64 },
+}
+
+pub async fn run(config: ServerConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(&config.address).await?;
+    println!("Listening on {}", listener.local_addr()?);
+    serve(listener, config).await
+}
+
+// the games on a listener already bound, the address of the config is not used
+pub async fn serve(listener: TcpListener, config: ServerConfig) -> std::io::Result<()> {
+    let (events, receiver) = channel(MAX_QUEUED_EVENTS);
+    tokio::spawn(run_lobby(Lobby::new(config), receiver));
+    let mut next_id = 0;
+    loop {
+        let (stream, address) = listener.accept().await?;
+        next_id += 1;
+        tokio::spawn(handle_connection(stream, address, next_id, events.clone()));
+    }
+}
+
+// passes the messages between one client and the lobby,
+// until either of them ends the connection
+async fn handle_connection(stream: TcpStream, address: SocketAddr, id: u64, events: Sender<Event>) {
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_BYTES),
+        max_frame_size: Some(MAX_MESSAGE_BYTES),
+        ..WebSocketConfig::default()
+    };
+    let socket = match tokio_tungstenite::accept_async_with_config(stream, Some(config)).await {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("{}: WebSocket handshake failed: {}", address, error);
+            return;
+        }
+    };
+    let (mut sink, mut stream) = socket.split();
+
+    let (sender, mut outgoing) = channel::<ServerMessage>(MAX_QUEUED_MESSAGES);
+    if events.send(Event::Connected { id, sender }).await.is_err() {
+        return;
+    }
+    // the lobby lets go of the client when it disconnects it
+    let mut writer = tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if sink.send(Message::Text(message.to_json())).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    loop {
+        let message = tokio::select! {
+            message = stream.next() => message,
+            _ = &mut writer => break,
+        };
+        match message {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                Ok(message) => {
+                    if events.send(Event::Message { id, message }).await.is_err() {
+                        break;
+                    }
+                }
+                Err(error) => eprintln!("{}: bad message: {}", address, error),
+            },
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(_)) => {}
+        }
+    }
+    let _ = events.send(Event::Disconnected { id }).await;
+}
+
+async fn run_lobby(mut lobby: Lobby, mut events: Receiver<Event>) {
+    let mut ticker = interval(Duration::from_millis(TICK_MILLIS));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let Some(event) = event else {
+                    return;
+                };
+                lobby.handle(event);
+            }
+            _ = ticker.tick() => lobby.tick(),
+        }
+        lobby.disconnect_lagging();
+    }
+}
+
+struct Client {
+    sender: Sender<ServerMessage>,
+    room: Option<String>,
+    // a message to it was dropped as it had too many waiting,
+    // so it can no longer follow the game
+    lagging: Cell<bool>,
+}
+
+impl Client {
+    fn send(&self, message: ServerMessage) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(message) {
+            self.lagging.set(true);
+        }
+    }
+}
+
+struct Member {
+    id: u64,
+    name: String,
+    ready: bool,
+}
+
+struct Game {
+    simulation: Simulation,
+    names: Vec<String>,
+    // the connections of the players, gone when a player has left,
+    // the snake plays on without them
+    players: Vec<Option<u64>>,
+    turns: Vec<VecDeque<Direction>>,
+}
+
+struct Room {
+    settings: RoomSettings,
+    seats: Vec<Member>,
+    spectators: Vec<Member>,
+    // the client streaming the games played in the room
+    streamer: Option<u64>,
+    game: Option<Game>,
+}
+
+impl Room {
+    fn is_empty(&self) -> bool {
+        self.seats.is_empty() && self.spectators.is_empty() && self.streamer.is_none()
+    }
+
+    fn has_free_seat(&self) -> bool {
+        self.streamer.is_none() && self.game.is_none() && self.seats.len() < self.settings.players
+    }
+
+    // a player who left a running game keeps the seat until it is over, but hears no more of it
+    fn has_left(&self, seat: usize) -> bool {
+        self.game.as_ref().is_some_and(|game| game.players.get(seat) == Some(&None))
+    }
+
+    fn seated(&self) -> impl Iterator<Item = (usize, &Member)> + '_ {
+        self.seats.iter().enumerate().filter(|(seat, _)| !self.has_left(*seat))
+    }
+
+    fn members(&self) -> impl Iterator<Item = u64> + '_ {
+        self.seated().map(|(_, member)| member.id)
+            .chain(self.spectators.iter().map(|member| member.id))
+            .chain(self.streamer)
+    }
+}
+
+struct Lobby {
+    config: ServerConfig,
+    clients: HashMap<u64, Client>,
+    // sorted by code for the room list
+    rooms: BTreeMap<String, Room>,
+}
+
+impl Lobby {
+    fn new(config: ServerConfig) -> Lobby {
+        Lobby { config, clients: HashMap::new(), rooms: BTreeMap::new() }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected { id, sender } => {
+                self.clients.insert(id, Client { sender, room: None, lagging: Cell::new(false) });
+            }
+            Event::Disconnected { id } => {
+                self.leave_room(id);
+                self.clients.remove(&id);
+            }
+            Event::Message { id, message } => {
+                if let Err(message) = self.handle_message(id, message) {
+                    self.send(id, ServerMessage::Error { message });
+                }
+            }
+        }
+    }
+
+    fn handle_message(&mut self, id: u64, message: ClientMessage) -> Result<(), String> {
+        match message {
+            ClientMessage::Join { name } => {
+                // checked before a room is made for it
+                validate_name(&name)?;
+                let code = match self.rooms.iter().find(|(_, room)| room.has_free_seat()) {
+                    Some((code, _)) => code.clone(),
+                    None => self.create_room(self.config.settings.clone()),
+                };
+                self.join_room(id, &code, name, false)?;
+                self.set_ready(id, true)
+            }
+            ClientMessage::ListRooms => {
+                let rooms = self.rooms.iter()
+                    .map(|(code, room)| RoomInfo {
+                        code: code.clone(),
+                        level: room.settings.level.clone(),
+                        players: room.seats.len(),
+                        seats: room.settings.players,
+                        spectators: room.spectators.len(),
+                        playing: room.game.is_some(),
+                    })
+                    .collect();
+                self.send(id, ServerMessage::Rooms { rooms });
+                Ok(())
+            }
+            ClientMessage::CreateRoom { name, settings } => {
+                validate(&settings)?;
+                let code = self.create_room(settings);
+                self.join_room(id, &code, name, false)
+            }
+            ClientMessage::JoinRoom { code, name, spectate } => {
+                self.join_room(id, &code.to_uppercase(), name, spectate)
+            }
+            ClientMessage::LeaveRoom => {
+                self.leave_room(id);
+                Ok(())
+            }
+            ClientMessage::Ready { ready } => self.set_ready(id, ready),
+            ClientMessage::ChangeSettings { settings } => {
+                validate(&settings)?;
+                let code = self.room_of(id).ok_or("you are not in a room")?;
+                let room = self.rooms.get_mut(&code).expect("clients are in rooms that exist");
+                if room.seats.first().map(|member| member.id) != Some(id) {
+                    return Err("only the first player can change the settings".to_string());
+                }
+                if room.game.is_some() {
+                    return Err("the game is already running".to_string());
+                }
+                if settings.players < room.seats.len() || settings.spectators < room.spectators.len() {
+                    return Err("there are more people in the room than that".to_string());
+                }
+                room.settings = settings;
+                for member in &mut room.seats {
+                    member.ready = false;
+                }
+                self.send_room(&code);
+                Ok(())
+            }
+            ClientMessage::Turn { direction } => {
+                let Some(code) = self.room_of(id) else {
+                    return Ok(());
+                };
+                if let Some(game) = self.rooms.get_mut(&code).and_then(|room| room.game.as_mut()) {
+                    if let Some(player) = game.players.iter().position(|player| *player == Some(id)) {
+                        if game.turns[player].len() < MAX_QUEUED_TURNS {
+                            game.turns[player].push_back(direction);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            ClientMessage::Stream { name, settings, seed, names } => {
+                let settings = RoomSettings { players: names.len(), ..settings };
+                validate(&settings)?;
+                for name in names.iter().chain([&name]) {
+                    validate_name(name)?;
+                }
+                let code = match self.room_of(id) {
+                    Some(code) if self.rooms[&code].streamer == Some(id) => code,
+                    _ => {
+                        self.leave_room(id);
+                        let code = self.create_room(settings.clone());
+                        println!("{} is streaming in room {}", name, code);
+                        self.rooms.get_mut(&code).expect("the room was just created").streamer = Some(id);
+                        if let Some(client) = self.clients.get_mut(&id) {
+                            client.room = Some(code.clone());
+                        }
+                        code
+                    }
+                };
+                // a game still going was given up on
+                if let Some(game) = &self.rooms[&code].game {
+                    let winner = game.simulation.winner();
+                    self.end_game(&code, winner);
+                }
+                self.rooms.get_mut(&code).expect("the stream has a room").settings = settings;
+                let players = vec![None; names.len()];
+                self.start_game(&code, seed, names, players);
+                Ok(())
+            }
+            ClientMessage::StreamTick { tick, turns } => {
+                let code = self.streamed_room(id)?;
+                let room = self.rooms.get_mut(&code).expect("streams are in rooms that exist");
+                let game = room.game.as_mut().ok_or("no game is being streamed")?;
+                let simulation = &mut game.simulation;
+                if tick != simulation.tick() {
+                    return Err(format!("the streamed game is on tick {}, not {}", simulation.tick(), tick));
+                }
+                for (player, direction) in &turns {
+                    if *player >= simulation.players() || simulation.snake(*player).next_turn {
+                        return Err(format!("player {} can not turn on tick {}", player, tick));
+                    }
+                    simulation.turn(*player, *direction);
+                    if !simulation.snake(*player).next_turn {
+                        return Err(format!("player {} can not turn {} on tick {}", player, direction.name(), tick));
+                    }
+                }
+                if let Some(winner) = step_game(&self.clients, room, turns) {
+                    self.end_game(&code, winner);
+                }
+                Ok(())
+            }
+            ClientMessage::StreamEnd => {
+                let code = self.streamed_room(id)?;
+                if let Some(game) = &self.rooms[&code].game {
+                    let winner = game.simulation.winner();
+                    self.end_game(&code, winner);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn create_room(&mut self, settings: RoomSettings) -> String {
+        let mut rng = rand::thread_rng();
+        let code = loop {
+            let code: String = (0..ROOM_CODE_LENGTH)
+                .map(|_| ROOM_CODE_LETTERS[rng.gen_range(0..ROOM_CODE_LETTERS.len())] as char)
+                .collect();
+            if !self.rooms.contains_key(&code) {
+                break code;
+            }
+        };
+        println!("Room {} created, {} players on {}", code, settings.players, settings.level);
+        self.rooms.insert(code.clone(), Room {
+            settings,
+            seats: Vec::new(),
+            spectators: Vec::new(),
+            streamer: None,
+            game: None,
+        });
+        code
+    }
+
+    fn join_room(&mut self, id: u64, code: &str, name: String, spectate: bool) -> Result<(), String> {
+        validate_name(&name)?;
+        let room = self.rooms.get(code).ok_or(format!("there is no room {}", code))?;
+        if spectate && room.spectators.len() >= room.settings.spectators {
+            return Err(format!("room {} has no room for more spectators", code));
+        }
+        if !spectate && !room.has_free_seat() {
+            return Err(format!("room {} has no free seats", code));
+        }
+        self.leave_room(id);
+
+        let room = self.rooms.get_mut(code).expect("the room was just there");
+        let name = match name.trim() {
+            "" if spectate => format!("Spectator {}", room.spectators.len() + 1),
+            "" => format!("Player {}", room.seats.len() + 1),
+            name => name.to_string(),
+        };
+        println!("{} joined room {}", name, code);
+        let member = Member { id, name, ready: false };
+        if spectate {
+            room.spectators.push(member);
+        }
+        else {
+            room.seats.push(member);
+        }
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(code.to_string());
+        }
+
+        // a spectator joining a running game gets all of it so far
+        if let Some(game) = &room.game {
+            let simulation = &game.simulation;
+            let start = ServerMessage::Start {
+                seed: simulation.seed(),
+                rules: simulation.rules().clone(),
+                level: simulation.level().name.clone(),
+                names: game.names.clone(),
+                you: None,
+            };
+            let catchup = ServerMessage::Catchup { tick: simulation.tick(), turns: simulation.turns().to_vec() };
+            self.send(id, start);
+            self.send(id, catchup);
+        }
+        self.send_room(code);
+        Ok(())
+    }
+
+    fn leave_room(&mut self, id: u64) {
+        let Some(code) = self.room_of(id) else {
+            return;
+        };
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = None;
+        }
+        let room = self.rooms.get_mut(&code).expect("clients are in rooms that exist");
+        room.spectators.retain(|member| member.id != id);
+        if room.streamer == Some(id) {
+            println!("The stream in room {} ended", code);
+            room.streamer = None;
+            self.end_game(&code, None);
+        }
+        else {
+            match &mut room.game {
+                // the seats stay as they are until the game is over
+                Some(game) if game.players.contains(&Some(id)) => {
+                    for player in game.players.iter_mut().filter(|player| **player == Some(id)) {
+                        *player = None;
+                    }
+                    if game.players.iter().all(|player| player.is_none()) {
+                        println!("Everybody left room {}, the game is stopped", code);
+                        self.end_game(&code, None);
+                    }
+                }
+                Some(_) => {}
+                None => room.seats.retain(|member| member.id != id),
+            }
+        }
+
+        let room = &self.rooms[&code];
+        if room.is_empty() {
+            println!("Room {} closed", code);
+            self.rooms.remove(&code);
+        }
+        else {
+            self.send_room(&code);
+        }
+    }
+
+    fn set_ready(&mut self, id: u64, ready: bool) -> Result<(), String> {
+        let code = self.room_of(id).ok_or("you are not in a room")?;
+        let room = self.rooms.get_mut(&code).expect("clients are in rooms that exist");
+        let member = room.seats.iter_mut().find(|member| member.id == id).ok_or("spectators do not play")?;
+        member.ready = ready;
+        if room.game.is_none() && room.seats.len() == room.settings.players &&
+            room.seats.iter().all(|member| member.ready) {
+            let seed = self.config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+            let names = room.seats.iter().map(|member| member.name.clone()).collect();
+            let players = room.seats.iter().map(|member| Some(member.id)).collect();
+            self.start_game(&code, seed, names, players);
+        }
+        else {
+            self.send_room(&code);
+        }
+        Ok(())
+    }
+
+    fn start_game(&mut self, code: &str, seed: u64, names: Vec<String>, players: Vec<Option<u64>>) {
+        let room = self.rooms.get_mut(code).expect("games start in rooms that exist");
+        let settings = &room.settings;
+        let level = Level::choose(&settings.level, seed).unwrap_or_default();
+        println!("Game started in room {} with seed {}: {}", code, seed, names.join(", "));
+        room.game = Some(Game {
+            simulation: Simulation::with_level(seed, settings.rules.clone(), level.clone(), names.len()),
+            names: names.clone(),
+            turns: vec![VecDeque::new(); players.len()],
+            players,
+        });
+
+        let room = &self.rooms[code];
+        let start = |you| ServerMessage::Start {
+            seed,
+            rules: room.settings.rules.clone(),
+            level: level.name.clone(),
+            names: names.clone(),
+            you,
+        };
+        for (you, member) in room.seats.iter().enumerate() {
+            self.send(member.id, start(Some(you)));
+        }
+        for member in &room.spectators {
+            self.send(member.id, start(None));
+        }
+        self.send_room(code);
+    }
+
+    fn tick(&mut self) {
+        let mut ended = Vec::new();
+        for (code, room) in &mut self.rooms {
+            if room.streamer.is_some() {
+                continue;
+            }
+            let Some(game) = &mut room.game else {
+                continue;
+            };
+            // only the turns the simulation takes are sent,
+            // the clients would not take the others either
+            let mut turns = Vec::new();
+            for (player, queue) in game.turns.iter_mut().enumerate() {
+                if let Some(direction) = queue.pop_front() {
+                    game.simulation.turn(player, direction);
+                    if game.simulation.snake(player).next_turn {
+                        turns.push((player, direction));
+                    }
+                }
+            }
+            if let Some(winner) = step_game(&self.clients, room, turns) {
+                ended.push((code.clone(), winner));
+            }
+        }
+        for (code, winner) in ended {
+            self.end_game(&code, winner);
+        }
+    }
+
+    // the players who left lose their seats, the others stay for the next game
+    fn end_game(&mut self, code: &str, winner: Option<usize>) {
+        let room = self.rooms.get_mut(code).expect("games end in rooms that exist");
+        let Some(game) = room.game.take() else {
+            return;
+        };
+        let scores: Vec<String> = game.names.iter()
+            .zip(game.simulation.snakes())
+            .map(|(name, snake)| format!("{} {}", name, snake.score))
+            .collect();
+        println!("Game over in room {} after {} ticks: {}", code, game.simulation.tick(), scores.join(", "));
+
+        room.seats = std::mem::take(&mut room.seats).into_iter()
+            .zip(&game.players)
+            .filter(|(_, player)| player.is_some())
+            .map(|(member, _)| member)
+            .collect();
+        for member in &mut room.seats {
+            member.ready = false;
+        }
+        let ids: Vec<u64> = room.members().collect();
+        for id in ids {
+            self.send(id, ServerMessage::End { winner });
+        }
+        self.send_room(code);
+    }
+
+    fn send_room(&self, code: &str) {
+        let Some(room) = self.rooms.get(code) else {
+            return;
+        };
+        let seats: Vec<Seat> = room.seats.iter()
+            .map(|member| Seat { name: member.name.clone(), ready: member.ready })
+            .collect();
+        let spectators: Vec<String> = room.spectators.iter().map(|member| member.name.clone()).collect();
+        for id in room.members() {
+            self.send(id, ServerMessage::Room {
+                code: code.to_string(),
+                settings: room.settings.clone(),
+                seats: seats.clone(),
+                spectators: spectators.clone(),
+                you: room.seated().find(|(_, member)| member.id == id).map(|(seat, _)| seat),
+            });
+        }
+    }
+
+    fn room_of(&self, id: u64) -> Option<String> {
+        self.clients.get(&id).and_then(|client| client.room.clone())
+    }
+
+    fn streamed_room(&self, id: u64) -> Result<String, String> {
+        self.room_of(id)
+            .filter(|code| self.rooms[code].streamer == Some(id))
+            .ok_or("you are not streaming a game".to_string())
+    }
+
+    fn send(&self, id: u64, message: ServerMessage) {
+        if let Some(client) = self.clients.get(&id) {
+            client.send(message);
+        }
+    }
+
+    // the clients too slow to keep up are let go of, which closes their connections
+    fn disconnect_lagging(&mut self) {
+        let lagging: Vec<u64> = self.clients.iter()
+            .filter(|(_, client)| client.lagging.get())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in lagging {
+            println!("Client {} is too slow, disconnecting it", id);
+            self.leave_room(id);
+            self.clients.remove(&id);
+        }
+    }
+}
+
+// steps the game in the room and sends the turns made on the tick to everybody in it,
+// returns the winner when the game is over
+fn step_game(clients: &HashMap<u64, Client>, room: &mut Room, turns: Vec<(usize, Direction)>) -> Option<Option<usize>> {
+    let simulation = &mut room.game.as_mut().expect("only running games are stepped").simulation;
+    let tick = simulation.tick();
+    simulation.step();
+    let over = simulation.is_over().then(|| simulation.winner());
+    let message = ServerMessage::Tick { tick, turns };
+    for id in room.members() {
+        if let Some(client) = clients.get(&id) {
+            client.send(message.clone());
+        }
+    }
+    over
+}
+
+pub fn validate(settings: &RoomSettings) -> Result<(), String> {
+    if Level::choose(&settings.level, 0).is_none() {
+        return Err(format!("unknown level {}", settings.level));
+    }
+    if !(1..=MAX_PLAYERS).contains(&settings.players) {
+        return Err(format!("a game has 1 to {} players", MAX_PLAYERS));
+    }
+    if settings.spectators > MAX_SPECTATORS {
+        return Err(format!("a room has at most {} spectators", MAX_SPECTATORS));
+    }
+    let rules = &settings.rules;
+    for (what, ticks) in [("food", rules.food_spawn_ticks), ("bomb", rules.bomb_spawn_ticks)] {
+        if !(1..=MAX_SPAWN_TICKS).contains(&ticks) {
+            return Err(format!("{} spawns must be 1 to {} ticks apart", what, MAX_SPAWN_TICKS));
+        }
+    }
+    if !(1..=MAX_FUSE_TICKS).contains(&rules.bomb_fuse_ticks) {
+        return Err(format!("a bomb fuse is 1 to {} ticks", MAX_FUSE_TICKS));
+    }
+    // the online games have no time limit, so the clocks are only allowed to be off
+    if rules.clock_spawn_ticks != 0 {
+        return Err("online games have no clocks".to_string());
+    }
+    Ok(())
+}
+
+// an empty name is given one by the server
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("a name is at most {} characters", MAX_NAME_LENGTH));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("a name can not have control characters".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::simulation::Rules;
+
+    type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>;
+
+    async fn start_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = ServerConfig { address: address.to_string(), settings: RoomSettings::default(), seed: Some(7) };
+        tokio::spawn(serve(listener, config));
+        address
+    }
+
+    async fn connect(address: SocketAddr) -> Socket {
+        tokio_tungstenite::connect_async(format!("ws://{}", address)).await.unwrap().0
+    }
+
+    async fn send(socket: &mut Socket, message: ClientMessage) {
+        socket.send(Message::Text(message.to_json())).await.unwrap();
+    }
+
+    // the next message the test is waiting for, skipping the room updates
+    async fn receive(socket: &mut Socket) -> ServerMessage {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await
+                .expect("the server answers")
+                .expect("the connection is open")
+                .unwrap();
+            if let Message::Text(text) = message {
+                match serde_json::from_str(&text).unwrap() {
+                    ServerMessage::Room { .. } => {}
+                    message => return message,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rules_are_checked() {
+        let with = |rules: Rules| RoomSettings { rules, ..RoomSettings::default() };
+        assert!(validate(&RoomSettings::default()).is_ok());
+        assert!(validate(&with(Rules { food_spawn_ticks: 0, ..Rules::default() })).is_err());
+        assert!(validate(&with(Rules { bomb_spawn_ticks: MAX_SPAWN_TICKS + 1, ..Rules::default() })).is_err());
+        assert!(validate(&with(Rules { bomb_fuse_ticks: 0, ..Rules::default() })).is_err());
+        assert!(validate(&with(Rules { bomb_fuse_ticks: u32::MAX, ..Rules::default() })).is_err());
+        assert!(validate(&with(Rules { clock_spawn_ticks: 10, ..Rules::default() })).is_err());
+        assert!(validate(&RoomSettings { players: MAX_PLAYERS + 1, ..RoomSettings::default() }).is_err());
+        assert!(validate(&RoomSettings { level: "nowhere".to_string(), ..RoomSettings::default() }).is_err());
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert!(validate_name("").is_ok());
+        assert!(validate_name(&"x".repeat(MAX_NAME_LENGTH)).is_ok());
+        assert!(validate_name(&"x".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(validate_name("a\nb").is_err());
+    }
+
+    #[test]
+    fn lagging_clients_are_disconnected() {
+        let mut lobby = Lobby::new(ServerConfig { address: String::new(), settings: RoomSettings::default(), seed: None });
+        let (sender, _receiver) = channel(1);
+        lobby.handle(Event::Connected { id: 1, sender });
+        lobby.handle(Event::Message { id: 1, message: ClientMessage::ListRooms });
+        lobby.disconnect_lagging();
+        assert!(lobby.clients.contains_key(&1));
+        lobby.handle(Event::Message { id: 1, message: ClientMessage::ListRooms });
+        lobby.disconnect_lagging();
+        assert!(!lobby.clients.contains_key(&1));
+    }
+
+    #[test]
+    fn players_who_left_a_game_hear_no_more_of_it() {
+        let mut lobby = Lobby::new(ServerConfig { address: String::new(), settings: RoomSettings::default(), seed: Some(7) });
+        let mut receivers = Vec::new();
+        for id in 1..=2 {
+            let (sender, receiver) = channel(MAX_QUEUED_MESSAGES);
+            receivers.push(receiver);
+            lobby.handle(Event::Connected { id, sender });
+            lobby.handle(Event::Message { id, message: ClientMessage::Join { name: format!("Player {}", id) } });
+        }
+        let code = lobby.room_of(1).unwrap();
+        assert!(lobby.rooms[&code].game.is_some());
+
+        lobby.handle(Event::Message { id: 1, message: ClientMessage::LeaveRoom });
+        lobby.handle(Event::Message { id: 1, message: ClientMessage::CreateRoom { name: "Alone".to_string(), settings: RoomSettings::default() } });
+        while receivers[0].try_recv().is_ok() {}
+        lobby.tick();
+        lobby.handle(Event::Message { id: 2, message: ClientMessage::LeaveRoom });
+
+        let mut heard = Vec::new();
+        while let Ok(message) = receivers[0].try_recv() {
+            heard.push(message);
+        }
+        assert!(heard.iter().all(|message| matches!(message, ServerMessage::Room { .. })), "{:?}", heard);
+        assert!(!lobby.rooms.contains_key(&code));
+        let room = &lobby.rooms[&lobby.room_of(1).unwrap()];
+        assert_eq!(room.seats.iter().map(|member| member.id).collect::<Vec<_>>(), [1]);
+    }
+
+    #[tokio::test]
+    async fn two_clients_play_a_game() {
+        let address = start_server().await;
+        let mut first = connect(address).await;
+        let mut second = connect(address).await;
+        send(&mut first, ClientMessage::Join { name: "First".to_string() }).await;
+        send(&mut second, ClientMessage::Join { name: "Second".to_string() }).await;
+
+        let names = vec!["First".to_string(), "Second".to_string()];
+        for (you, socket) in [&mut first, &mut second].into_iter().enumerate() {
+            match receive(socket).await {
+                ServerMessage::Start { seed, names: started, you: seat, .. } => {
+                    assert_eq!((seed, started, seat), (7, names.clone(), Some(you)));
+                }
+                message => panic!("expected the game to start, got {:?}", message),
+            }
+        }
+
+        // both see the turn of the first player on the same tick
+        send(&mut first, ClientMessage::Turn { direction: Direction::Up }).await;
+        let mut ticks = Vec::new();
+        for socket in [&mut first, &mut second] {
+            let tick = loop {
+                match receive(socket).await {
+                    ServerMessage::Tick { tick, turns } if !turns.is_empty() => {
+                        assert_eq!(turns, [(0, Direction::Up)]);
+                        break tick;
+                    }
+                    ServerMessage::Tick { .. } => {}
+                    message => panic!("expected a tick, got {:?}", message),
+                }
+            };
+            ticks.push(tick);
+        }
+        assert_eq!(ticks[0], ticks[1]);
+
+        // a bad name gets no room made for it
+        let mut third = connect(address).await;
+        send(&mut third, ClientMessage::Join { name: "x".repeat(MAX_NAME_LENGTH + 1) }).await;
+        assert!(matches!(receive(&mut third).await, ServerMessage::Error { .. }));
+        send(&mut third, ClientMessage::ListRooms).await;
+        match receive(&mut third).await {
+            ServerMessage::Rooms { rooms } => assert_eq!(rooms.len(), 1),
+            message => panic!("expected the rooms, got {:?}", message),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{AppState, Direction};
+use crate::difficulty::DifficultySettings;
+use crate::input::{Action, Bindings, GameInput, LOCAL_PLAYERS, button_name, key_name};
+use crate::level::LevelSettings;
+use crate::storage;
+
+pub const SETTINGS: &str = "settings.json";
+
+// the columns of the screen are the keys of each player and then the gamepad buttons
+const GAMEPAD_COLUMN: usize = LOCAL_PLAYERS;
+// and the rows after the actions choose the steering, the difficulty and the level and reset everything
+const STEERING_ROW: usize = Action::ALL.len();
+const DIFFICULTY_ROW: usize = STEERING_ROW + 1;
+const LEVEL_ROW: usize = DIFFICULTY_ROW + 1;
+const RESET_ROW: usize = LEVEL_ROW + 1;
+
+pub struct SettingsPlugin;
+
+// everything the player has chosen, saved together in one file,
+// anything missing from the file is left as it is by default
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SavedSettings {
+    bindings: Bindings,
+    difficulty: DifficultySettings,
+    level: LevelSettings,
+}
+
+#[derive(Resource, Default)]
+struct SettingsScreen {
+    row: usize,
+    column: usize,
+    // the next key or button pressed is bound to the selected action
+    listening: bool,
+    status: String,
+}
+
+#[derive(Component)]
+struct SettingsText;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = load_settings();
+        app
+            .insert_resource(settings.bindings)
+            .insert_resource(settings.difficulty)
+            .insert_resource(settings.level)
+            .add_system(open_settings_input_system.in_set(OnUpdate(AppState::GameOver)))
+            .add_system(spawn_settings_system.in_schedule(OnEnter(AppState::Settings)))
+            .add_systems((settings_input_system, settings_text_system)
+                .chain()
+                .in_set(OnUpdate(AppState::Settings)))
+            .add_systems((save_settings_system, despawn_settings_system)
+                .in_schedule(OnExit(AppState::Settings)));
+    }
+}
+
+fn load_settings() -> SavedSettings {
+    let settings = storage::load(SETTINGS)
+        .and_then(|data| serde_json::from_slice::<SavedSettings>(&data).map_err(std::io::Error::from));
+    let mut settings = match settings {
+        Ok(settings) => settings,
+        Err(error) => {
+            debug!("No earlier settings loaded: {}", error);
+            SavedSettings::default()
+        }
+    };
+    if let Err(error) = settings.bindings.validate() {
+        warn!("The saved controls are not used, {}", error);
+        settings.bindings = Bindings::default();
+    }
+    if let Err(error) = settings.difficulty.validate() {
+        warn!("The saved difficulty is not used, {}", error);
+        settings.difficulty = DifficultySettings::default();
+    }
+    if let Err(error) = settings.level.validate() {
+        warn!("The saved level is not used, {}", error);
+        settings.level = LevelSettings::default();
+    }
+    settings
+}
+
+fn save_settings_system(bindings: Res<Bindings>,
+                        difficulty: Res<DifficultySettings>,
+                        level: Res<LevelSettings>) {
+    let settings = SavedSettings { bindings: bindings.clone(), difficulty: difficulty.clone(), level: level.clone() };
+    let data = serde_json::to_vec_pretty(&settings).expect("settings can always be serialized");
+    if let Err(error) = storage::save(SETTINGS, &data) {
+        error!("Failed to save the settings: {}", error);
+    }
+}
+
+fn open_settings_input_system(mut state: ResMut<NextState<AppState>>,
+                              keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::C) {
+        state.set(AppState::Settings);
+    }
+}
+
+fn spawn_settings_system(mut commands: Commands,
+                         asset_server: Res<AssetServer>) {
+    commands.init_resource::<SettingsScreen>();
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 22.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(40.0),
+                left: Val::Px(50.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(SettingsText);
+}
+
+// the first player moves the selection and delete clears what is selected,
+// escape cancels a key being listened for, so it is only bound to back by default
+fn settings_input_system(mut state: ResMut<NextState<AppState>>,
+                         keyboard_input: Res<Input<KeyCode>>,
+                         buttons: Res<Input<GamepadButton>>,
+                         mut input: EventReader<GameInput>,
+                         mut bindings: ResMut<Bindings>,
+                         (mut difficulty, mut level): (ResMut<DifficultySettings>, ResMut<LevelSettings>),
+                         mut screen: ResMut<SettingsScreen>) {
+    let input: Vec<GameInput> = input.iter().copied().collect();
+
+    if screen.listening {
+        let action = Action::ALL[screen.row];
+        let result = if keyboard_input.just_pressed(KeyCode::Escape) {
+            Err("Not changed".to_string())
+        }
+        else if screen.column == GAMEPAD_COLUMN {
+            match buttons.get_just_pressed().next() {
+                Some(button) => bindings.bind_button(action, button.button_type),
+                None => return,
+            }
+        }
+        else {
+            match keyboard_input.get_just_pressed().next() {
+                Some(key) => bindings.bind_key(screen.column, action, *key),
+                None => return,
+            }
+        };
+        screen.listening = false;
+        screen.status = result.err().unwrap_or_default();
+        return;
+    }
+
+    if input.contains(&GameInput::Back) {
+        state.set(AppState::GameOver);
+        return;
+    }
+    for event in &input {
+        match event {
+            GameInput::Turn { player: 0, direction } => match direction {
+                Direction::Up => screen.row = screen.row.saturating_sub(1),
+                Direction::Down => screen.row = (screen.row + 1).min(RESET_ROW),
+                Direction::Left => screen.column = screen.column.saturating_sub(1),
+                Direction::Right => screen.column = (screen.column + 1).min(GAMEPAD_COLUMN),
+            },
+            GameInput::Confirm if screen.row == STEERING_ROW => {
+                if let Some(steering) = bindings.steering.get_mut(screen.column) {
+                    *steering = steering.toggled();
+                }
+            }
+            GameInput::Confirm if screen.row == DIFFICULTY_ROW => difficulty.select_next(),
+            GameInput::Confirm if screen.row == LEVEL_ROW => level.select_next(),
+            GameInput::Confirm if screen.row == RESET_ROW => {
+                *bindings = Bindings::default();
+                *difficulty = DifficultySettings::default();
+                *level = LevelSettings::default();
+                screen.status = "Reset to the defaults".to_string();
+            }
+            GameInput::Confirm => {
+                screen.listening = true;
+                screen.status.clear();
+            }
+            _ => {}
+        }
+    }
+    // the gamepads steer the way their player does
+    if screen.row >= STEERING_ROW && screen.column == GAMEPAD_COLUMN {
+        screen.column -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Delete) && screen.row < STEERING_ROW {
+        let action = Action::ALL[screen.row];
+        let result = if screen.column == GAMEPAD_COLUMN {
+            bindings.clear_button(action);
+            Ok(())
+        }
+        else {
+            bindings.clear_key(screen.column, action)
+        };
+        screen.status = result.err().unwrap_or_default();
+    }
+}
+
+fn settings_text_system(bindings: Res<Bindings>,
+                        difficulty: Res<DifficultySettings>,
+                        level: Res<LevelSettings>,
+                        screen: Res<SettingsScreen>,
+                        mut query: Query<&mut Text, With<SettingsText>>) {
+    if !bindings.is_changed() && !difficulty.is_changed() && !level.is_changed() && !screen.is_changed() {
+        return;
+    }
+    let cell = |row: usize, column: usize, value: String| {
+        if (row, column) != (screen.row, screen.column) {
+            value
+        }
+        else if !screen.listening {
+            format!("[{}]", value)
+        }
+        else if column == GAMEPAD_COLUMN {
+            "[press a button]".to_string()
+        }
+        else {
+            "[press a key]".to_string()
+        }
+    };
+
+    let mut lines = vec!["Settings".to_string(), screen.status.clone(), String::new()];
+    for (row, action) in Action::ALL.into_iter().enumerate() {
+        let mut columns: Vec<String> = (0..LOCAL_PLAYERS)
+            .map(|player| {
+                let keys: Vec<String> = bindings.keys(player, action).iter().map(|key| key_name(*key)).collect();
+                format!("player {}: {}", player + 1, if keys.is_empty() { "-".to_string() } else { keys.join(", ") })
+            })
+            .collect();
+        let buttons: Vec<String> = bindings.buttons(action).iter().map(|button| button_name(*button)).collect();
+        columns.push(format!("gamepad: {}", if buttons.is_empty() { "-".to_string() } else { buttons.join(", ") }));
+        let columns: Vec<String> = columns.into_iter()
+            .enumerate()
+            .map(|(column, value)| cell(row, column, value))
+            .collect();
+        lines.push(format!("{}  -  {}", action.name(), columns.join("    ")));
+    }
+    let steering: Vec<String> = (0..LOCAL_PLAYERS)
+        .map(|player| cell(STEERING_ROW, player, format!("player {}: {}", player + 1, bindings.steering(player).name())))
+        .collect();
+    lines.push(format!("Steering  -  {}", steering.join("    ")));
+    let selected = if screen.row == DIFFICULTY_ROW { format!("[{}]", difficulty.selected) } else { difficulty.selected.clone() };
+    lines.push(format!("Difficulty  -  {}", selected));
+    lines.extend(difficulty.difficulty().describe().lines().map(|line| format!("    {}", line)));
+    let selected = if screen.row == LEVEL_ROW { format!("[{}]", level.selected) } else { level.selected.clone() };
+    lines.push(format!("Level  -  {}", selected));
+    lines.push(String::new());
+    lines.push(if screen.row == RESET_ROW { "[Reset to the defaults]" } else { "Reset to the defaults" }.to_string());
+    lines.push(String::new());
+    let keys = |action| bindings.keys(0, action).iter().map(|key| key_name(*key)).collect::<Vec<_>>().join(" or ");
+    let turns: Vec<String> = Action::ALL[..4].iter().map(|action| keys(*action)).collect();
+    lines.push(format!("{} choose, {} changes, Delete clears", turns.join("/"), keys(Action::Confirm)));
+    lines.push(format!("{} to go back, the settings are saved", keys(Action::Back)));
+    for mut text in &mut query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+fn despawn_settings_system(mut commands: Commands,
+                           query: Query<Entity, With<SettingsText>>) {
+    commands.remove_resource::<SettingsScreen>();
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
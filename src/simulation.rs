@@ -0,0 +1,907 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{Direction, GridPosition};
+use crate::common::{GRID_WIDTH, GRID_HEIGHT};
+use crate::food::FOODS;
+use crate::level::{EnemyKind, Level, Terrain};
+
+// the game rules without any rendering, so that the same game
+// can be run by the Bevy app and by the headless tools
+//
+// all randomness comes from the seed and time is counted in ticks,
+// so a game is fully determined by its seed and the turns made on each tick
+
+pub const TICK_MILLIS: u64 = 200;
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeathCause {
+    Wall,
+    Body,
+    Snake,
+    HeadOn,
+    Explosion,
+    Enemy,
+}
+
+impl DeathCause {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "wall",
+            DeathCause::Body => "body",
+            DeathCause::Snake => "snake",
+            DeathCause::HeadOn => "head-on",
+            DeathCause::Explosion => "explosion",
+            DeathCause::Enemy => "enemy",
+        }
+    }
+}
+
+impl std::fmt::Display for DeathCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    // no food or bombs appear on their own when these are 0, as in the puzzles
+    pub food_spawn_ticks: u64,
+    pub bomb_spawn_ticks: u64,
+    pub bomb_fuse_ticks: u32,
+    // clocks give more time in the modes that have a time limit, there are none when this is 0
+    #[serde(default)]
+    pub clock_spawn_ticks: u64,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            food_spawn_ticks: 5000 / TICK_MILLIS,
+            bomb_spawn_ticks: 27000 / TICK_MILLIS,
+            bomb_fuse_ticks: (5000 / TICK_MILLIS) as u32,
+            clock_spawn_ticks: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Snake {
+    pub direction: Direction,
+    pub next_turn: bool,
+    // head is the first piece
+    pub body: VecDeque<GridPosition>,
+    pub score: u32,
+    pub death: Option<DeathCause>,
+    pub death_tick: u64,
+    growth: u32,
+    // waiting a tick in mud
+    stuck: bool,
+}
+
+impl Snake {
+    fn new(position: GridPosition, direction: Direction) -> Self {
+        Snake {
+            direction,
+            next_turn: false,
+            body: VecDeque::from([position]),
+            score: 0,
+            death: None,
+            death_tick: 0,
+            growth: 0,
+            stuck: false,
+        }
+    }
+
+    pub fn head(&self) -> GridPosition {
+        self.body[0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.body.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.body.is_empty()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.death.is_none()
+    }
+
+    // only one turn per tick, and never straight back into the body
+    pub fn turn(&mut self, direction: Direction) {
+        if !self.next_turn && direction != self.direction && direction.opposite() != self.direction {
+            self.direction = direction;
+            self.next_turn = true;
+        }
+    }
+}
+
+// a single snake starts in the middle, more snakes
+// start on opposite sides of the grid heading past each other
+pub fn start_position(player: usize, players: usize) -> (GridPosition, Direction) {
+    if players == 1 {
+        return (GridPosition::new(GRID_WIDTH / 2, GRID_HEIGHT / 2), Direction::Left);
+    }
+    match player {
+        0 => (GridPosition::new(GRID_WIDTH * 2 / 3, GRID_HEIGHT * 2 / 5), Direction::Left),
+        1 => (GridPosition::new(GRID_WIDTH / 3, GRID_HEIGHT * 3 / 5), Direction::Right),
+        2 => (GridPosition::new(GRID_WIDTH * 2 / 3, GRID_HEIGHT * 4 / 5), Direction::Left),
+        _ => (GridPosition::new(GRID_WIDTH / 3, GRID_HEIGHT / 5), Direction::Right),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoodItem {
+    pub id: u32,
+    pub kind: usize,
+    pub position: GridPosition,
+}
+
+impl FoodItem {
+    pub fn value(&self) -> u32 {
+        FOODS[self.kind].1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BombItem {
+    pub id: u32,
+    pub position: GridPosition,
+    pub fuse: u32,
+}
+
+// there is one clock on the grid at most, it stays until it is picked up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClockItem {
+    pub id: u32,
+    pub position: GridPosition,
+}
+
+// an enemy of the level, the ones caught in an explosion are gone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnemyItem {
+    pub id: u32,
+    pub position: GridPosition,
+    // which of the enemies of the level it is
+    pub enemy: usize,
+    // how far a patroller has walked along its path, there and back
+    steps: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameEvent {
+    FoodEaten { player: usize, position: GridPosition, value: u32 },
+    BombDefused { player: usize, position: GridPosition },
+    ClockPicked { player: usize, position: GridPosition },
+    BombExploded { position: GridPosition },
+    EnemyKilled { position: GridPosition },
+    SnakeDied { player: usize, cause: DeathCause },
+}
+
+#[derive(Clone)]
+pub struct Simulation {
+    seed: u64,
+    rng: StdRng,
+    rules: Rules,
+    level: Level,
+    tick: u64,
+    walls: HashSet<GridPosition>,
+    snakes: Vec<Snake>,
+    foods: Vec<FoodItem>,
+    bombs: Vec<BombItem>,
+    clocks: Vec<ClockItem>,
+    enemies: Vec<EnemyItem>,
+    next_id: u32,
+    // every turn made, with the tick it was made on and the player making it
+    turns: Vec<(u64, usize, Direction)>,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, rules: Rules, players: usize) -> Self {
+        Simulation::with_level(seed, rules, Level::default(), players)
+    }
+
+    pub fn with_level(seed: u64, rules: Rules, level: Level, players: usize) -> Self {
+        let mut walls: HashSet<GridPosition> = level.walls.iter().copied().collect();
+        for x in 0..=GRID_WIDTH {
+            for y in [0, GRID_HEIGHT] {
+                walls.insert(GridPosition::new(x, y));
+            }
+        }
+        for y in 0..GRID_HEIGHT {
+            for x in [0, GRID_WIDTH] {
+                walls.insert(GridPosition::new(x, y));
+            }
+        }
+
+        let players = players.clamp(1, MAX_PLAYERS);
+        let snakes = (0..players)
+            .map(|player| {
+                let (position, direction) = match level.start {
+                    Some(start) if players == 1 => start,
+                    _ => start_position(player, players),
+                };
+                Snake::new(position, direction)
+            })
+            .collect();
+
+        let mut simulation = Simulation {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            rules,
+            level,
+            tick: 0,
+            walls,
+            snakes,
+            foods: Vec::new(),
+            bombs: Vec::new(),
+            clocks: Vec::new(),
+            enemies: Vec::new(),
+            next_id: 0,
+            turns: Vec::new(),
+        };
+        for enemy in 0..simulation.level.enemies.len() {
+            let id = simulation.next_id();
+            let position = simulation.level.enemies[enemy].start();
+            simulation.enemies.push(EnemyItem { id, position, enemy, steps: 0 });
+        }
+        simulation
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn walls(&self) -> impl Iterator<Item = &GridPosition> {
+        self.walls.iter()
+    }
+
+    pub fn players(&self) -> usize {
+        self.snakes.len()
+    }
+
+    pub fn snakes(&self) -> &[Snake] {
+        &self.snakes
+    }
+
+    pub fn snake(&self, player: usize) -> &Snake {
+        &self.snakes[player]
+    }
+
+    pub fn foods(&self) -> &[FoodItem] {
+        &self.foods
+    }
+
+    pub fn bombs(&self) -> &[BombItem] {
+        &self.bombs
+    }
+
+    pub fn clocks(&self) -> &[ClockItem] {
+        &self.clocks
+    }
+
+    pub fn enemies(&self) -> &[EnemyItem] {
+        &self.enemies
+    }
+
+    // a single snake plays until it dies,
+    // with more snakes the game ends when there is one left
+    pub fn is_over(&self) -> bool {
+        let alive = self.snakes.iter().filter(|snake| snake.is_alive()).count();
+        alive == 0 || (self.snakes.len() > 1 && alive == 1)
+    }
+
+    // the last snake alive, or when the rest died on the same tick,
+    // the one of them with the highest score, no winner on a draw
+    pub fn winner(&self) -> Option<usize> {
+        if self.snakes.len() < 2 || !self.is_over() {
+            return None;
+        }
+        if let Some(player) = self.snakes.iter().position(|snake| snake.is_alive()) {
+            return Some(player);
+        }
+        let last: Vec<usize> = (0..self.snakes.len())
+            .filter(|player| self.snakes[*player].death_tick == self.tick)
+            .collect();
+        let best = last.iter().map(|player| self.snakes[*player].score).max()?;
+        match last.iter().filter(|player| self.snakes[**player].score == best).collect::<Vec<_>>()[..] {
+            [player] => Some(*player),
+            _ => None,
+        }
+    }
+
+    // levels made by hand put the snake and the food where they are drawn
+    pub fn place_snake(&mut self, player: usize, body: &[GridPosition], direction: Direction) {
+        let snake = &mut self.snakes[player];
+        snake.body = body.iter().copied().collect();
+        snake.direction = direction;
+    }
+
+    pub fn place_food(&mut self, kind: usize, position: GridPosition) {
+        let id = self.next_id();
+        self.foods.push(FoodItem { id, kind, position });
+    }
+
+    pub fn turns(&self) -> &[(u64, usize, Direction)] {
+        &self.turns
+    }
+
+    // a snake can not turn where the terrain does not let it, like on ice
+    pub fn turn(&mut self, player: usize, direction: Direction) {
+        if let Some(snake) = self.snakes.get_mut(player) {
+            if snake.is_alive() && self.level.terrain_at(snake.head()).is_none_or(Terrain::lets_turn) {
+                snake.turn(direction);
+            }
+        }
+    }
+
+    // walls, living snakes and enemies are deadly to move into
+    pub fn is_blocked(&self, position: GridPosition) -> bool {
+        self.walls.contains(&position) ||
+            self.snakes.iter().any(|snake| snake.is_alive() && snake.body.contains(&position)) ||
+            self.enemies.iter().any(|enemy| enemy.position == position)
+    }
+
+    // advance the game by one tick and return what happened on it
+    pub fn step(&mut self) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if self.is_over() {
+            return events;
+        }
+        for (player, snake) in self.snakes.iter().enumerate() {
+            if snake.is_alive() && snake.next_turn {
+                self.turns.push((self.tick, player, snake.direction));
+            }
+        }
+        self.tick += 1;
+
+        // the snakes move the way they are heading, but one in mud only every other tick
+        let level = &self.level;
+        let moves: Vec<Option<Direction>> = self.snakes.iter_mut()
+            .map(|snake| {
+                snake.next_turn = false;
+                if !snake.is_alive() {
+                    return None;
+                }
+                snake.stuck = level.terrain_at(snake.head()).is_some_and(|terrain| terrain.holds(snake.stuck));
+                (!snake.stuck).then_some(snake.direction)
+            })
+            .collect();
+        self.advance(&moves, &mut events);
+
+        // and the ones that came onto a conveyor are pushed a place further
+        let pushes: Vec<Option<Direction>> = self.snakes.iter().zip(&moves)
+            .map(|(snake, moved)| moved.filter(|_| snake.is_alive())
+                .and_then(|_| self.level.terrain_at(snake.head())?.push()))
+            .collect();
+        if !self.is_over() && pushes.iter().any(Option::is_some) {
+            self.advance(&pushes, &mut events);
+        }
+        if !self.is_over() {
+            self.move_enemies(&mut events);
+        }
+
+        self.update_bombs(&mut events);
+        if self.is_over() {
+            return events;
+        }
+
+        if self.rules.food_spawn_ticks > 0 && self.tick.is_multiple_of(self.rules.food_spawn_ticks) {
+            self.spawn_food();
+        }
+        if self.rules.bomb_spawn_ticks > 0 && self.tick.is_multiple_of(self.rules.bomb_spawn_ticks) {
+            self.spawn_bomb();
+        }
+        if self.rules.clock_spawn_ticks > 0 && self.tick.is_multiple_of(self.rules.clock_spawn_ticks) && self.clocks.is_empty() {
+            self.spawn_clock();
+        }
+        events
+    }
+
+    // move the snakes a place the way given, the ones with none stay where they are,
+    // a snake going into a portal comes out of the other end and the body follows it through
+    fn advance(&mut self, moves: &[Option<Direction>], events: &mut Vec<GameEvent>) {
+        let heads: Vec<Option<GridPosition>> = self.snakes.iter().zip(moves)
+            .map(|(snake, direction)| direction.map(|direction| self.level.step(snake.head(), direction)))
+            .collect();
+
+        let deaths: Vec<(usize, DeathCause)> = (0..self.snakes.len())
+            .filter_map(|player| self.collision(player, &heads).map(|cause| (player, cause)))
+            .collect();
+        for (player, cause) in deaths {
+            self.die(player, cause, events);
+        }
+
+        // the new head takes the place of the tail, unless the snake is growing
+        for (snake, head) in self.snakes.iter_mut().zip(heads) {
+            let Some(head) = head else {
+                continue;
+            };
+            if !snake.is_alive() {
+                continue;
+            }
+            snake.body.push_front(head);
+            if snake.growth > 0 {
+                snake.growth -= 1;
+            }
+            else {
+                snake.body.pop_back();
+            }
+        }
+
+        for (player, snake) in self.snakes.iter_mut().enumerate() {
+            if !snake.is_alive() {
+                continue;
+            }
+            let head = snake.head();
+            if let Some(index) = self.foods.iter().position(|food| food.position == head) {
+                let food = self.foods.remove(index);
+                snake.score += food.value();
+                snake.growth += food.value();
+                events.push(GameEvent::FoodEaten { player, position: head, value: food.value() });
+            }
+            if let Some(index) = self.bombs.iter().position(|bomb| bomb.position == head) {
+                self.bombs.remove(index);
+                events.push(GameEvent::BombDefused { player, position: head });
+            }
+            if let Some(index) = self.clocks.iter().position(|clock| clock.position == head) {
+                self.clocks.remove(index);
+                events.push(GameEvent::ClockPicked { player, position: head });
+            }
+        }
+    }
+
+    // what a snake runs into when all the living snakes move to their new heads,
+    // two snakes meeting head first both die
+    fn collision(&self, player: usize, heads: &[Option<GridPosition>]) -> Option<DeathCause> {
+        let head = heads[player]?;
+        // the snakes that stay where they are, like one stuck in mud, can still be run into
+        let others = || (0..self.snakes.len()).filter(move |other| *other != player && self.snakes[*other].is_alive());
+        if self.walls.contains(&head) {
+            Some(DeathCause::Wall)
+        }
+        else if self.enemies.iter().any(|enemy| enemy.position == head) {
+            Some(DeathCause::Enemy)
+        }
+        else if others().filter(|other| heads[*other].is_some()).any(|other| {
+            heads[other] == Some(head) ||
+                (heads[other] == Some(self.snakes[player].head()) && self.snakes[other].head() == head)
+        }) {
+            Some(DeathCause::HeadOn)
+        }
+        else if self.snakes[player].body.contains(&head) {
+            Some(DeathCause::Body)
+        }
+        else if others().any(|other| self.snakes[other].body.contains(&head)) {
+            Some(DeathCause::Snake)
+        }
+        else {
+            None
+        }
+    }
+
+    // the patrollers take a step along their paths on every tick and the chasers on every other,
+    // an enemy waits while its way is blocked, or goes through the head of a snake, killing it
+    fn move_enemies(&mut self, events: &mut Vec<GameEvent>) {
+        for index in 0..self.enemies.len() {
+            let enemy = self.enemies[index];
+            let next = match &self.level.enemies[enemy.enemy] {
+                EnemyKind::Patrol(path) => path[back_and_forth(enemy.steps + 1, path.len())],
+                EnemyKind::Chaser(_) if self.tick.is_multiple_of(2) => self.chase(enemy.position),
+                EnemyKind::Chaser(_) => continue,
+            };
+            let bitten = (0..self.snakes.len())
+                .find(|player| self.snakes[*player].is_alive() && self.snakes[*player].head() == next);
+            if let Some(player) = bitten {
+                self.die(player, DeathCause::Enemy, events);
+            }
+            else if self.is_blocked(next) || self.level.portal_exit(next).is_some() {
+                continue;
+            }
+            let enemy = &mut self.enemies[index];
+            enemy.position = next;
+            enemy.steps += 1;
+        }
+    }
+
+    // a step towards the head of the closest living snake, the longer way first,
+    // and the shorter one when that is blocked
+    fn chase(&self, position: GridPosition) -> GridPosition {
+        let distance = |head: &GridPosition| head.x.abs_diff(position.x) + head.y.abs_diff(position.y);
+        let Some(target) = self.snakes.iter().filter(|snake| snake.is_alive()).map(Snake::head).min_by_key(distance) else {
+            return position;
+        };
+        let horizontal = (target.x != position.x).then_some(if target.x < position.x { Direction::Left } else { Direction::Right });
+        let vertical = (target.y != position.y).then_some(if target.y < position.y { Direction::Down } else { Direction::Up });
+        let ways = if target.x.abs_diff(position.x) >= target.y.abs_diff(position.y) { [horizontal, vertical] } else { [vertical, horizontal] };
+        ways.into_iter()
+            .flatten()
+            .map(|direction| {
+                let mut next = position;
+                next.move_position(direction, 1);
+                next
+            })
+            .find(|next| *next == target || !(self.is_blocked(*next) || self.level.portal_exit(*next).is_some()))
+            .unwrap_or(position)
+    }
+
+    fn update_bombs(&mut self, events: &mut Vec<GameEvent>) {
+        let mut exploded = Vec::new();
+        self.bombs.retain_mut(|bomb| {
+            bomb.fuse = bomb.fuse.saturating_sub(1);
+            if bomb.fuse == 0 {
+                exploded.push(bomb.position);
+            }
+            bomb.fuse > 0
+        });
+
+        for position in exploded {
+            events.push(GameEvent::BombExploded { position });
+            self.enemies.retain(|enemy| {
+                let caught = is_in_blast(enemy.position, position);
+                if caught {
+                    events.push(GameEvent::EnemyKilled { position: enemy.position });
+                }
+                !caught
+            });
+            for player in 0..self.snakes.len() {
+                let snake = &self.snakes[player];
+                if snake.is_alive() && snake.body.iter().any(|piece| is_in_blast(*piece, position)) {
+                    self.die(player, DeathCause::Explosion, events);
+                }
+            }
+        }
+    }
+
+    fn spawn_food(&mut self) {
+        let free = self.free_positions(&self.level.food_spawns);
+        if let Some(position) = free.choose(&mut self.rng).copied() {
+            let kind = self.rng.gen_range(0..FOODS.len());
+            let id = self.next_id();
+            self.foods.push(FoodItem { id, kind, position });
+        }
+    }
+
+    fn spawn_bomb(&mut self) {
+        let free = self.free_positions(&self.level.bomb_spawns);
+        if let Some(position) = free.choose(&mut self.rng).copied() {
+            let id = self.next_id();
+            self.bombs.push(BombItem { id, position, fuse: self.rules.bomb_fuse_ticks });
+        }
+    }
+
+    fn spawn_clock(&mut self) {
+        let free = self.free_positions(&[]);
+        if let Some(position) = free.choose(&mut self.rng).copied() {
+            let id = self.next_id();
+            self.clocks.push(ClockItem { id, position });
+        }
+    }
+
+    // anywhere when there are no spawns given, but never on a portal
+    fn free_positions(&self, spawns: &[GridPosition]) -> Vec<GridPosition> {
+        let mut free = Vec::new();
+        for x in 1..GRID_WIDTH {
+            for y in 1..GRID_HEIGHT {
+                let position = GridPosition::new(x, y);
+                if (spawns.is_empty() || spawns.contains(&position)) &&
+                    !self.walls.contains(&position) &&
+                    self.level.portal_exit(position).is_none() &&
+                    !self.snakes.iter().any(|snake| snake.body.contains(&position)) &&
+                    !self.foods.iter().any(|food| food.position == position) &&
+                    !self.bombs.iter().any(|bomb| bomb.position == position) &&
+                    !self.clocks.iter().any(|clock| clock.position == position) &&
+                    !self.enemies.iter().any(|enemy| enemy.position == position) {
+                    free.push(position);
+                }
+            }
+        }
+        free
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn die(&mut self, player: usize, cause: DeathCause, events: &mut Vec<GameEvent>) {
+        let snake = &mut self.snakes[player];
+        snake.death = Some(cause);
+        snake.death_tick = self.tick;
+        events.push(GameEvent::SnakeDied { player, cause });
+    }
+}
+
+// where along a path of this many places it is after the steps, going there and back again
+fn back_and_forth(steps: usize, places: usize) -> usize {
+    if places < 2 {
+        return 0;
+    }
+    let step = steps % (2 * (places - 1));
+    if step < places { step } else { 2 * (places - 1) - step }
+}
+
+pub fn is_in_blast(position: GridPosition, bomb_position: GridPosition) -> bool {
+    (position.x as i32 - bomb_position.x as i32).abs() <= 1 &&
+        (position.y as i32 - bomb_position.y as i32).abs() <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // nothing appears on its own, so only the snakes move
+    fn quiet() -> Rules {
+        Rules { food_spawn_ticks: 0, bomb_spawn_ticks: 0, ..Rules::default() }
+    }
+
+    fn at(x: u32, y: u32) -> GridPosition {
+        GridPosition::new(x, y)
+    }
+
+    fn run_until_over(simulation: &mut Simulation) {
+        for _ in 0..100 {
+            if simulation.is_over() {
+                return;
+            }
+            simulation.step();
+        }
+        panic!("the game did not end");
+    }
+
+    #[test]
+    fn same_seed_and_turns_give_the_same_game() {
+        let play = || {
+            let mut simulation = Simulation::new(7, Rules { food_spawn_ticks: 2, bomb_spawn_ticks: 3, ..Rules::default() }, 2);
+            for tick in 0..40 {
+                if tick % 5 == 0 {
+                    simulation.turn(0, if tick % 10 == 0 { Direction::Up } else { Direction::Left });
+                    simulation.turn(1, if tick % 10 == 0 { Direction::Down } else { Direction::Right });
+                }
+                simulation.step();
+            }
+            simulation
+        };
+        let (first, second) = (play(), play());
+        assert_eq!(first.tick(), second.tick());
+        assert_eq!(first.foods(), second.foods());
+        assert_eq!(first.bombs(), second.bombs());
+        assert_eq!(first.turns(), second.turns());
+        for (a, b) in first.snakes().iter().zip(second.snakes()) {
+            assert_eq!(a.body, b.body);
+            assert_eq!((a.score, a.death, a.death_tick), (b.score, b.death, b.death_tick));
+        }
+    }
+
+    #[test]
+    fn running_into_a_wall() {
+        let mut simulation = Simulation::new(1, quiet(), 1);
+        run_until_over(&mut simulation);
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Wall));
+        assert_eq!(simulation.snake(0).head(), at(1, GRID_HEIGHT / 2));
+    }
+
+    #[test]
+    fn running_into_itself() {
+        let mut simulation = Simulation::new(1, quiet(), 1);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10), at(11, 11), at(10, 11), at(9, 11)], Direction::Left);
+        simulation.turn(0, Direction::Up);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Body));
+    }
+
+    #[test]
+    fn going_through_a_portal() {
+        let level = Level { portals: vec![(at(9, 10), at(20, 15))], ..Level::default() };
+        let mut simulation = Simulation::with_level(1, quiet(), level, 1);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10), at(12, 10)], Direction::Left);
+        let mut bodies = Vec::new();
+        for _ in 0..3 {
+            simulation.step();
+            bodies.push(simulation.snake(0).body.iter().copied().collect::<Vec<_>>());
+        }
+        // the head comes out of the other end going the same way, and the body follows it through
+        assert_eq!(bodies, [
+            [at(20, 15), at(10, 10), at(11, 10)],
+            [at(19, 15), at(20, 15), at(10, 10)],
+            [at(18, 15), at(19, 15), at(20, 15)],
+        ]);
+        assert_eq!(simulation.snake(0).direction, Direction::Left);
+        assert!(simulation.snake(0).is_alive());
+    }
+
+    #[test]
+    fn running_into_itself_through_a_portal() {
+        let level = Level { portals: vec![(at(9, 10), at(12, 10))], ..Level::default() };
+        let mut simulation = Simulation::with_level(1, quiet(), level, 1);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10), at(11, 11), at(12, 11), at(12, 10), at(13, 10)], Direction::Left);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Body));
+    }
+
+    #[test]
+    fn running_into_another_snake() {
+        let mut simulation = Simulation::new(1, quiet(), 2);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10)], Direction::Left);
+        simulation.place_snake(1, &[at(9, 12), at(9, 11), at(9, 10), at(9, 9)], Direction::Up);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Snake));
+        assert!(simulation.snake(1).is_alive());
+        assert_eq!(simulation.winner(), Some(1));
+    }
+
+    #[test]
+    fn terrain_changes_how_the_snakes_move() {
+        let heads = |position, terrain| {
+            let level = Level { terrain: vec![(position, terrain)], ..Level::default() };
+            let mut simulation = Simulation::with_level(1, quiet(), level, 1);
+            simulation.place_snake(0, &[at(10, 10), at(11, 10)], Direction::Left);
+            simulation.turn(0, Direction::Up);
+            (0..3).map(|_| {
+                simulation.step();
+                simulation.snake(0).head()
+            }).collect::<Vec<_>>()
+        };
+        assert_eq!(heads(at(10, 10), Terrain::Ice), [at(9, 10), at(8, 10), at(7, 10)]);
+        assert_eq!(heads(at(10, 10), Terrain::Mud), [at(10, 10), at(10, 11), at(10, 12)]);
+        assert_eq!(heads(at(10, 11), Terrain::Conveyor(Direction::Left)), [at(9, 11), at(9, 12), at(9, 13)]);
+    }
+
+    #[test]
+    fn running_into_a_snake_stuck_in_mud() {
+        let level = Level { terrain: vec![(at(9, 10), Terrain::Mud)], ..Level::default() };
+        let mut simulation = Simulation::with_level(1, quiet(), level, 2);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10)], Direction::Left);
+        simulation.place_snake(1, &[at(9, 10), at(9, 9), at(9, 8)], Direction::Up);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Snake));
+        assert!(simulation.snake(1).is_alive());
+        assert_eq!(simulation.snake(1).head(), at(9, 10));
+    }
+
+    #[test]
+    fn pushed_into_a_snake_that_is_not() {
+        let level = Level { terrain: vec![(at(9, 10), Terrain::Conveyor(Direction::Up))], ..Level::default() };
+        let mut simulation = Simulation::with_level(1, quiet(), level, 2);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10)], Direction::Left);
+        simulation.place_snake(1, &[at(8, 11), at(7, 11)], Direction::Right);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Snake));
+        assert!(simulation.snake(1).is_alive());
+        assert_eq!(simulation.snake(1).head(), at(9, 11));
+    }
+
+    #[test]
+    fn meeting_head_on() {
+        // on the same place
+        let mut simulation = Simulation::new(1, quiet(), 2);
+        simulation.place_snake(0, &[at(12, 10), at(13, 10)], Direction::Left);
+        simulation.place_snake(1, &[at(10, 10), at(9, 10)], Direction::Right);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::HeadOn));
+        assert_eq!(simulation.snake(1).death, Some(DeathCause::HeadOn));
+        assert_eq!(simulation.winner(), None);
+
+        // and going past each other
+        let mut simulation = Simulation::new(1, quiet(), 2);
+        simulation.place_snake(0, &[at(11, 10), at(12, 10)], Direction::Left);
+        simulation.place_snake(1, &[at(10, 10), at(9, 10)], Direction::Right);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::HeadOn));
+        assert_eq!(simulation.snake(1).death, Some(DeathCause::HeadOn));
+    }
+
+    #[test]
+    fn food_and_bombs_appear_on_their_ticks() {
+        let rules = Rules { food_spawn_ticks: 3, bomb_spawn_ticks: 4, bomb_fuse_ticks: 20, clock_spawn_ticks: 0 };
+        let mut simulation = Simulation::new(3, rules, 1);
+        simulation.place_snake(0, &[at(15, 1)], Direction::Up);
+        let mut counts = Vec::new();
+        for _ in 0..8 {
+            simulation.step();
+            counts.push((simulation.foods().len(), simulation.bombs().len()));
+        }
+        assert_eq!(counts, [(0, 0), (0, 0), (1, 0), (1, 1), (1, 1), (2, 1), (2, 1), (2, 2)]);
+
+        let mut simulation = Simulation::new(3, quiet(), 1);
+        for _ in 0..10 {
+            simulation.step();
+        }
+        assert!(simulation.foods().is_empty() && simulation.bombs().is_empty());
+    }
+
+    fn with_enemies(enemies: Vec<EnemyKind>) -> Simulation {
+        Simulation::with_level(1, quiet(), Level { enemies, ..Level::default() }, 1)
+    }
+
+    #[test]
+    fn patrols_walk_their_path_there_and_back() {
+        let mut simulation = with_enemies(vec![EnemyKind::Patrol(vec![at(5, 5), at(6, 5), at(7, 5)])]);
+        simulation.place_snake(0, &[at(15, 1)], Direction::Up);
+        let mut places = Vec::new();
+        for _ in 0..5 {
+            simulation.step();
+            places.push(simulation.enemies()[0].position);
+        }
+        assert_eq!(places, [at(6, 5), at(7, 5), at(6, 5), at(5, 5), at(6, 5)]);
+    }
+
+    #[test]
+    fn chasers_step_towards_the_closest_head_every_other_tick() {
+        let mut simulation = with_enemies(vec![EnemyKind::Chaser(at(20, 10))]);
+        simulation.place_snake(0, &[at(10, 2)], Direction::Up);
+        let mut places = Vec::new();
+        for _ in 0..4 {
+            simulation.step();
+            places.push(simulation.enemies()[0].position);
+        }
+        assert_eq!(places, [at(20, 10), at(19, 10), at(19, 10), at(18, 10)]);
+    }
+
+    #[test]
+    fn enemies_kill_the_snakes_they_meet_head_first() {
+        // running into one
+        let mut simulation = with_enemies(vec![EnemyKind::Patrol(vec![at(9, 10)])]);
+        simulation.place_snake(0, &[at(10, 10), at(11, 10)], Direction::Left);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Enemy));
+
+        // and having one walk into the head
+        let mut simulation = with_enemies(vec![EnemyKind::Patrol(vec![at(12, 10), at(11, 10)])]);
+        simulation.place_snake(0, &[at(11, 9), at(11, 8)], Direction::Up);
+        simulation.step();
+        assert_eq!(simulation.snake(0).death, Some(DeathCause::Enemy));
+        assert_eq!(simulation.snake(0).head(), at(11, 10));
+    }
+
+    #[test]
+    fn bombs_kill_the_enemies_in_the_blast() {
+        let level = Level {
+            bomb_spawns: vec![at(11, 10)],
+            enemies: vec![EnemyKind::Patrol(vec![at(10, 10)]), EnemyKind::Patrol(vec![at(20, 10)])],
+            ..Level::default()
+        };
+        let rules = Rules { food_spawn_ticks: 0, bomb_spawn_ticks: 2, bomb_fuse_ticks: 3, clock_spawn_ticks: 0 };
+        let mut simulation = Simulation::with_level(5, rules, level, 1);
+        simulation.place_snake(0, &[at(15, 1)], Direction::Up);
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            events.extend(simulation.step());
+        }
+        assert!(events.contains(&GameEvent::EnemyKilled { position: at(10, 10) }));
+        assert_eq!(simulation.enemies().iter().map(|enemy| enemy.position).collect::<Vec<_>>(), [at(20, 10)]);
+        assert!(simulation.snake(0).is_alive());
+    }
+
+    #[test]
+    fn bombs_explode_when_the_fuse_runs_out() {
+        let rules = Rules { food_spawn_ticks: 0, bomb_spawn_ticks: 2, bomb_fuse_ticks: 3, clock_spawn_ticks: 0 };
+        let mut simulation = Simulation::new(5, rules, 1);
+        simulation.place_snake(0, &[at(15, 1)], Direction::Up);
+        let mut exploded = Vec::new();
+        for _ in 0..6 {
+            let events = simulation.step();
+            exploded.push(events.iter().any(|event| matches!(event, GameEvent::BombExploded { .. })));
+        }
+        assert_eq!(exploded, [false, false, false, false, true, false]);
+    }
+}
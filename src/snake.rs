@@ -1,87 +1,57 @@
 use bevy::prelude::*;
 
-use crate::common::Direction;
-
 #[derive(Component)]
 pub(crate) struct SnakeBodyPiece;
 
 impl SnakeBodyPiece {
     pub const BODY_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
+    // the first player keeps the grey snake
+    pub const PLAYER_COLORS: [Color; 4] = [
+        SnakeBodyPiece::BODY_COLOR,
+        Color::rgb(0.35, 0.6, 0.9),
+        Color::rgb(0.9, 0.55, 0.3),
+        Color::rgb(0.6, 0.8, 0.4),
+    ];
 
     pub fn new() -> SnakeBodyPiece {
         SnakeBodyPiece {}
     }
 }
 
+// the snake itself lives in the simulation,
+// these only keep track of the sprites showing it
 #[derive(Component)]
 pub(crate) struct SnakeHead {
-    pub direction: Direction,
-    pub next_turn: bool,
-    growth: u32,
     body: Vec<Entity>,
 }
 
 impl SnakeHead {
     pub const HEAD_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
+    pub const PLAYER_COLORS: [Color; 4] = [
+        SnakeHead::HEAD_COLOR,
+        Color::rgb(0.45, 0.7, 1.0),
+        Color::rgb(1.0, 0.65, 0.4),
+        Color::rgb(0.7, 0.9, 0.5),
+    ];
+    // what is left of a snake that is out of a multiplayer game
+    pub const DEAD_COLOR: Color = Color::rgba(0.4, 0.4, 0.4, 0.5);
 
     pub fn new() -> Self {
         SnakeHead {
-            direction: Direction::Left,
-            next_turn: false,
-            growth: 0,
             body: Vec::new(),
         }
     }
 
-    pub fn turn(&mut self, direction: Direction) {
-        if SnakeHead::get_opposite_direction(direction) != self.direction {
-            self.direction = direction;
-            self.next_turn = true;
-        }
-    }
-
-    pub fn grow(&mut self, value: u32) {
-        self.growth += value;
-    }
-
-    pub fn can_grow(&self) -> bool {
-        if self.growth > 0 {
-            true
-        }
-        else {
-            false
-        }
+    pub fn body(&self) -> &[Entity] {
+        &self.body
     }
 
     pub fn add_body_piece(&mut self, entity: Entity) {
-        if self.growth > 0 {
-            self.growth -= 1;
-        }
         debug!("Adding body piece: {:?}", entity);
-        self.body.insert(0, entity);
-    }
-
-    pub fn get_last_body_piece(&self) -> Option<Entity> {
-        match self.body.len() {
-            0 => None,
-            n => Some(self.body[n-1])
-        }
+        self.body.push(entity);
     }
 
-    pub fn move_last_body_piece_to_front(&mut self) {
-        let len = self.body.len();
-        if len > 1 {
-            self.body.insert(0, self.body[len-1]);
-            self.body.pop();
-        }
-    }
-
-    fn get_opposite_direction(direction: Direction) -> Direction {
-        match direction {
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-        }
+    pub fn remove_body_piece(&mut self) -> Option<Entity> {
+        self.body.pop()
     }
 }
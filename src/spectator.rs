@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+
+use crate::common::{AppState, ScreenPosition, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::gameplay::GameSimulation;
+use crate::online::OnlineGame;
+
+// a free camera for watching a game, a replay or an online game as a spectator,
+// that can also follow one of the snakes
+
+const FOLLOW_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+// pixels a second at full zoom
+const PAN_SPEED: f32 = 600.0;
+// how fast the camera catches up with the snake it follows
+const FOLLOW_SPEED: f32 = 6.0;
+const ZOOM_STEP: f32 = 1.25;
+// the camera never shows more than the whole field
+const MIN_SCALE: f32 = 0.25;
+
+pub struct SpectatorPlugin;
+
+#[derive(Resource, Default)]
+pub struct SpectatorCamera {
+    pub follow: Option<usize>,
+}
+
+#[derive(Component)]
+struct SpectatorText;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct SpectatorSet;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .configure_set(SpectatorSet.run_if(resource_exists::<SpectatorCamera>()))
+            .add_systems((spectator_input_system, follow_system, update_spectator_text_system)
+                .chain()
+                .in_set(SpectatorSet));
+        for state in [AppState::Replay, AppState::Online] {
+            app
+                .add_system(spawn_spectator_system.in_schedule(OnEnter(state.clone())))
+                .add_system(despawn_spectator_system.in_schedule(OnExit(state)));
+        }
+    }
+}
+
+// replays are always watched, online games only when there is no snake to play
+fn spawn_spectator_system(mut commands: Commands,
+                          asset_server: Res<AssetServer>,
+                          online: Option<Res<OnlineGame>>) {
+    if online.is_some_and(|online| online.game.player().is_some()) {
+        return;
+    }
+    commands.init_resource::<SpectatorCamera>();
+    commands.spawn(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(SpectatorText);
+}
+
+fn spectator_input_system(time: Res<Time>,
+                          keyboard_input: Res<Input<KeyCode>>,
+                          mut mouse_wheel: EventReader<MouseWheel>,
+                          simulation: Res<GameSimulation>,
+                          mut spectator: ResMut<SpectatorCamera>,
+                          mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>) {
+    if let Some(player) = FOLLOW_KEYS.iter()
+        .take(simulation.players())
+        .position(|key| keyboard_input.just_pressed(*key)) {
+        spectator.follow = Some(player);
+    }
+
+    let mut pan = Vec2::ZERO;
+    for (key, direction) in [(KeyCode::W, Vec2::Y), (KeyCode::S, Vec2::NEG_Y),
+                             (KeyCode::A, Vec2::NEG_X), (KeyCode::D, Vec2::X)] {
+        if keyboard_input.pressed(key) {
+            pan += direction;
+        }
+    }
+    if pan != Vec2::ZERO {
+        spectator.follow = None;
+    }
+
+    let mut zoom = 0.0;
+    if keyboard_input.just_pressed(KeyCode::Equals) || keyboard_input.just_pressed(KeyCode::NumpadAdd) {
+        zoom += 1.0;
+    }
+    if keyboard_input.just_pressed(KeyCode::Minus) || keyboard_input.just_pressed(KeyCode::NumpadSubtract) {
+        zoom -= 1.0;
+    }
+    for event in mouse_wheel.iter() {
+        zoom += event.y.signum();
+    }
+
+    for (mut transform, mut projection) in &mut query {
+        if keyboard_input.just_pressed(KeyCode::Key0) {
+            spectator.follow = None;
+            transform.translation = Vec3::new(0.0, 0.0, transform.translation.z);
+            projection.scale = 1.0;
+        }
+        projection.scale = (projection.scale / ZOOM_STEP.powf(zoom)).clamp(MIN_SCALE, 1.0);
+        let translation = transform.translation.truncate() + pan * PAN_SPEED * projection.scale * time.delta_seconds();
+        set_camera_position(&mut transform, projection.scale, translation);
+    }
+}
+
+fn follow_system(time: Res<Time>,
+                 simulation: Res<GameSimulation>,
+                 spectator: Res<SpectatorCamera>,
+                 mut query: Query<(&mut Transform, &OrthographicProjection), With<Camera2d>>) {
+    let Some(player) = spectator.follow.filter(|player| *player < simulation.players()) else {
+        return;
+    };
+    let head = ScreenPosition::from(simulation.snake(player).head());
+    for (mut transform, projection) in &mut query {
+        let current = transform.translation.truncate();
+        let amount = (FOLLOW_SPEED * time.delta_seconds()).min(1.0);
+        set_camera_position(&mut transform, projection.scale, current.lerp(Vec2::new(head.x, head.y), amount));
+    }
+}
+
+// zoomed in, the camera stays over the field
+fn set_camera_position(transform: &mut Transform, scale: f32, position: Vec2) {
+    let limit = Vec2::new(WINDOW_WIDTH, WINDOW_HEIGHT) / 2.0 * (1.0 - scale);
+    let position = position.clamp(-limit, limit);
+    transform.translation = position.extend(transform.translation.z);
+}
+
+fn update_spectator_text_system(spectator: Res<SpectatorCamera>,
+                                online: Option<Res<OnlineGame>>,
+                                mut query: Query<&mut Text, With<SpectatorText>>) {
+    if !spectator.is_changed() {
+        return;
+    }
+    let following = match spectator.follow {
+        Some(player) => {
+            let name = online.as_ref()
+                .and_then(|online| online.game.names().get(player).cloned())
+                .unwrap_or_else(|| format!("player {}", player + 1));
+            format!("Following {}", name)
+        }
+        None => "Free camera".to_string(),
+    };
+    let value = format!("{}  -  1 to 4 follow a snake, WASD moves, + and - zoom, 0 shows the whole field", following);
+    for mut text in &mut query {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+// the next game starts with the whole field in view
+fn despawn_spectator_system(mut commands: Commands,
+                            text_query: Query<Entity, With<SpectatorText>>,
+                            mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>) {
+    commands.remove_resource::<SpectatorCamera>();
+    for entity in text_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (mut transform, mut projection) in &mut camera_query {
+        transform.translation = Vec3::new(0.0, 0.0, transform.translation.z);
+        projection.scale = 1.0;
+    }
+}
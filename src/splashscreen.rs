@@ -4,6 +4,7 @@ use bevy::utils::Duration;
 
 use crate::common::AppState;
 use crate::common::BackgroundImage;
+use crate::launch::LaunchConfig;
 
 pub struct SplashScreenPlugin;
 
@@ -11,9 +12,14 @@ impl Plugin for SplashScreenPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_system(spawn_splashscreen_system.in_schedule(OnEnter(AppState::SplashScreen)))
+            .init_resource::<LaunchConfig>()
             .add_system(start_game_system
                 .in_set(OnUpdate(AppState::SplashScreen))
                 .run_if(on_timer(Duration::from_millis(3000))))
+            // a run set up with options goes straight to it
+            .add_system(start_game_system
+                .in_set(OnUpdate(AppState::SplashScreen))
+                .run_if(launched))
             .add_system(despawn_splashscreen_system.in_schedule(OnExit(AppState::SplashScreen)));
     }
 }
@@ -33,9 +39,14 @@ fn spawn_splashscreen_system(mut commands: Commands,
     }).insert(BackgroundImage);
 }
 
-fn start_game_system(mut state: ResMut<NextState<AppState>>) {
+fn launched(launch: Res<LaunchConfig>) -> bool {
+    !launch.is_empty()
+}
+
+fn start_game_system(mut state: ResMut<NextState<AppState>>,
+                     launch: Res<LaunchConfig>) {
     debug!("Running start game system");
-    state.set(AppState::Gameplay);
+    state.set(launch.first_state());
 }
 
 fn despawn_splashscreen_system(mut commands: Commands,
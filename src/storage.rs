@@ -0,0 +1,66 @@
+// saving small files, to a directory on native builds
+// and to the local storage of the browser on wasm
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::path::PathBuf;
+
+    // SNAKE_SAVE_DIR can be used to move the saves away from the working directory
+    fn path(name: &str) -> PathBuf {
+        let directory = std::env::var_os("SNAKE_SAVE_DIR").unwrap_or_else(|| "saves".into());
+        PathBuf::from(directory).join(name)
+    }
+
+    pub fn save(name: &str, data: &[u8]) -> std::io::Result<()> {
+        let path = path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, data)
+    }
+
+    pub fn load(name: &str) -> std::io::Result<Vec<u8>> {
+        std::fs::read(path(name))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use std::io::{Error, ErrorKind};
+
+    fn local_storage() -> std::io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| Error::new(ErrorKind::Other, "local storage is not available"))
+    }
+
+    fn key(name: &str) -> String {
+        format!("snake/{}", name)
+    }
+
+    // local storage only holds strings, so the data is stored as hex
+    pub fn save(name: &str, data: &[u8]) -> std::io::Result<()> {
+        let value: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        local_storage()?
+            .set_item(&key(name), &value)
+            .map_err(|_| Error::new(ErrorKind::Other, "local storage is full"))
+    }
+
+    pub fn load(name: &str) -> std::io::Result<Vec<u8>> {
+        let value = local_storage()?
+            .get_item(&key(name))
+            .ok()
+            .flatten()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} is not saved", name)))?;
+        (0..value.len())
+            .step_by(2)
+            .map(|i| {
+                value.get(i..i + 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("{} is corrupted", name)))
+            })
+            .collect()
+    }
+}
+
+pub use platform::{load, save};
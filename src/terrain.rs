@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+use crate::common::{Direction, GRID_SIZE};
+use crate::level::Terrain;
+
+// the terrain of a level, every kind a tile with its own look
+// and its own say in how the snakes move over it
+
+// the simulation asks the tile the head of a snake is on, through level::Terrain
+pub trait Tile {
+    // drawn at the middle of the place
+    fn sprites(&self) -> Vec<SpriteBundle>;
+
+    fn lets_turn(&self) -> bool {
+        true
+    }
+
+    // whether the snake waits out this tick, given whether it waited out the last one
+    fn holds(&self, _held: bool) -> bool {
+        false
+    }
+
+    // the way a snake that just came onto it is pushed
+    fn push(&self) -> Option<Direction> {
+        None
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Ice {}
+
+#[derive(Component)]
+pub struct Conveyor {
+    pub direction: Direction,
+}
+
+#[derive(Component, Default)]
+pub struct Mud {}
+
+impl Ice {
+    const COLOR: Color = Color::rgba(0.7, 0.9, 1.0, 0.7);
+}
+
+impl Tile for Ice {
+    fn sprites(&self) -> Vec<SpriteBundle> {
+        vec![tile(Ice::COLOR)]
+    }
+
+    fn lets_turn(&self) -> bool {
+        false
+    }
+}
+
+impl Conveyor {
+    const COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+    const ARROW_COLOR: Color = Color::rgb(0.9, 0.8, 0.2);
+}
+
+impl Tile for Conveyor {
+    // a belt with marks getting bigger the way it runs
+    fn sprites(&self) -> Vec<SpriteBundle> {
+        let ahead = match self.direction {
+            Direction::Left => Vec2::new(-1.0, 0.0),
+            Direction::Right => Vec2::new(1.0, 0.0),
+            Direction::Up => Vec2::new(0.0, 1.0),
+            Direction::Down => Vec2::new(0.0, -1.0),
+        } * GRID_SIZE / 4.0;
+        let mut sprites = vec![tile(Conveyor::COLOR)];
+        for (offset, size) in [(-1.0, 4.0), (0.0, 6.0), (1.0, 8.0)] {
+            let mark = ahead * offset;
+            sprites.push(SpriteBundle {
+                sprite: Sprite { color: Conveyor::ARROW_COLOR, custom_size: Some(Vec2::splat(size)), ..default() },
+                transform: Transform::from_xyz(mark.x, mark.y, 1.0),
+                ..default()
+            });
+        }
+        sprites
+    }
+
+    fn push(&self) -> Option<Direction> {
+        Some(self.direction)
+    }
+}
+
+impl Mud {
+    const COLOR: Color = Color::rgba(0.45, 0.3, 0.15, 0.85);
+}
+
+impl Tile for Mud {
+    fn sprites(&self) -> Vec<SpriteBundle> {
+        vec![tile(Mud::COLOR)]
+    }
+
+    fn holds(&self, held: bool) -> bool {
+        !held
+    }
+}
+
+// how a place with the terrain on it is drawn, at the middle of the place
+pub fn sprites(terrain: Terrain) -> Vec<SpriteBundle> {
+    terrain.with_tile(|tile| tile.sprites())
+}
+
+// the sprites of a place get the component of its terrain
+pub fn spawn(commands: &mut Commands, terrain: Terrain, bundle: SpriteBundle) -> Entity {
+    let mut entity = commands.spawn(bundle);
+    match terrain {
+        Terrain::Ice => entity.insert(Ice::default()),
+        Terrain::Conveyor(direction) => entity.insert(Conveyor { direction }),
+        Terrain::Mud => entity.insert(Mud::default()),
+    };
+    entity.id()
+}
+
+fn tile(color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite { color, custom_size: Some(Vec2::splat(GRID_SIZE)), ..default() },
+        ..default()
+    }
+}
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::common::{AppState, Text};
+use crate::gameplay::{GameClock, GameMode, GameSimulation, step_simulation_system};
+use crate::highscores::{HighScore, HighScores};
+use crate::simulation::GameEvent;
+
+// a time attack game is played alone against the clock,
+// the clocks on the grid give more time and the best scores get a table of their own
+
+pub const TIME_ATTACK_SCORES: &str = "time-attack-scores.json";
+pub const TIME_LIMIT: Duration = Duration::from_secs(120);
+pub const CLOCK_BONUS: Duration = Duration::from_secs(10);
+
+pub struct TimeAttackPlugin;
+
+// the time left of the time attack game being played,
+// counted down while the game is not paused
+#[derive(Resource, Debug)]
+pub struct Countdown {
+    pub left: Duration,
+    limit: Duration,
+}
+
+impl Countdown {
+    pub fn is_up(&self) -> bool {
+        self.left.is_zero()
+    }
+}
+
+// the best time attack scores, and the place the last game got among them
+#[derive(Resource)]
+struct TimeAttackScores {
+    scores: HighScores,
+    last: Option<usize>,
+}
+
+impl Plugin for TimeAttackPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(TimeAttackScores { scores: HighScores::load(TIME_ATTACK_SCORES), last: None })
+            .add_system(start_countdown_system.in_schedule(OnEnter(AppState::Gameplay)))
+            .add_system(countdown_system
+                .after(step_simulation_system)
+                .run_if(resource_exists::<Countdown>())
+                .in_set(OnUpdate(AppState::Gameplay)))
+            .add_system(record_score_system.in_schedule(OnExit(AppState::Gameplay)))
+            .add_system(high_scores_system.in_schedule(OnEnter(AppState::GameOver)));
+    }
+}
+
+fn start_countdown_system(mut commands: Commands,
+                          mode: Res<GameMode>) {
+    if *mode == GameMode::TimeAttack {
+        commands.insert_resource(Countdown { left: TIME_LIMIT, limit: TIME_LIMIT });
+    }
+}
+
+fn countdown_system(mut state: ResMut<NextState<AppState>>,
+                    clock: Res<GameClock>,
+                    mut countdown: ResMut<Countdown>,
+                    mut events: EventReader<GameEvent>) {
+    for event in events.iter() {
+        if let GameEvent::ClockPicked { .. } = event {
+            countdown.limit += CLOCK_BONUS;
+        }
+    }
+    countdown.left = countdown.limit.saturating_sub(clock.played());
+    if countdown.is_up() {
+        debug!("Time is up");
+        state.set(AppState::GameOver);
+    }
+}
+
+// a game left before the time is up counts as well
+fn record_score_system(mut commands: Commands,
+                       simulation: Res<GameSimulation>,
+                       countdown: Option<Res<Countdown>>,
+                       mut scores: ResMut<TimeAttackScores>) {
+    scores.last = None;
+    if countdown.is_none() {
+        return;
+    }
+    commands.remove_resource::<Countdown>();
+    let snake = simulation.snake(0);
+    scores.last = scores.scores.insert(HighScore { score: snake.score, length: snake.len() as u32 });
+    if scores.last.is_some() {
+        if let Err(error) = scores.scores.save(TIME_ATTACK_SCORES) {
+            error!("Failed to save the time attack scores: {}", error);
+        }
+    }
+}
+
+fn high_scores_system(mut commands: Commands,
+                      asset_server: Res<AssetServer>,
+                      mode: Res<GameMode>,
+                      scores: Res<TimeAttackScores>) {
+    if *mode != GameMode::TimeAttack {
+        return;
+    }
+    let mut lines = scores.scores.top_lines(scores.last);
+    lines.insert(0, "Time attack best".to_string());
+
+    commands.spawn(
+        TextBundle::from_section(
+            lines.join("\n"),
+            TextStyle {
+                font: asset_server.load("FiraSans-Bold.ttf"),
+                font_size: 22.0,
+                color: Color::GRAY,
+            }
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(20.0),
+                ..default()
+            },
+            ..default()
+        }),
+    ).insert(Text);
+}
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+use bevy::input::touch::Touch;
+use bevy::ui::UiSystem;
+use bevy::utils::HashSet;
+
+use crate::common::{AppState, Direction};
+use crate::input::{GameInput, GameInputSet};
+
+// touch screens steer the first snake by swiping, and a tap continues like space,
+// the swipes and taps are sent on as GameInput events like the keys
+
+// a touch has to move this many pixels to be a swipe, less than that is a tap
+pub const SWIPE_MIN_DISTANCE: f32 = 30.0;
+
+const DPAD_BUTTON_SIZE: f32 = 70.0;
+const DPAD_COLOR: Color = Color::rgba(0.5, 0.5, 0.5, 0.3);
+const DPAD_PRESSED_COLOR: Color = Color::rgba(0.5, 0.5, 0.5, 0.6);
+
+// the arrow, and the row and column of the button in the D-pad
+const DPAD_BUTTONS: [(Direction, &str, f32, f32); 4] = [
+    (Direction::Up, "\u{2191}", 0.0, 1.0),
+    (Direction::Left, "\u{2190}", 1.0, 0.0),
+    (Direction::Right, "\u{2192}", 1.0, 2.0),
+    (Direction::Down, "\u{2193}", 2.0, 1.0),
+];
+
+pub struct TouchPlugin;
+
+// the on-screen D-pad is shown while playing when it is on,
+// a tap with two fingers turns it on and off
+#[derive(Resource, Default, Debug)]
+pub struct TouchControls {
+    pub dpad: bool,
+}
+
+#[derive(Component)]
+struct Dpad;
+
+#[derive(Component)]
+struct DpadButton(Direction);
+
+// the touches of the gesture going on that are not taps, like swipes and presses of the D-pad,
+// and how many fingers were down at once
+#[derive(Default)]
+struct Gesture {
+    swiped: HashSet<u64>,
+    fingers: usize,
+}
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TouchControls>()
+            .add_system(touch_input_system
+                .in_set(GameInputSet)
+                .after(UiSystem::Focus))
+            .add_system(dpad_input_system
+                .in_set(GameInputSet)
+                .after(UiSystem::Focus))
+            .add_system(dpad_system);
+    }
+}
+
+fn touch_input_system(touches: Res<Touches>,
+                      buttons: Query<&Interaction, With<DpadButton>>,
+                      mut gesture: Local<Gesture>,
+                      mut controls: ResMut<TouchControls>,
+                      mut input: EventWriter<GameInput>) {
+    if touches.iter_just_pressed().next().is_some() {
+        gesture.fingers = gesture.fingers.max(touches.iter().count());
+    }
+    // a touch starting on the D-pad presses its button, lifting it is not a tap
+    if buttons.iter().any(|interaction| *interaction == Interaction::Clicked) {
+        for touch in touches.iter_just_pressed() {
+            gesture.swiped.insert(touch.id());
+        }
+    }
+
+    // a swipe turns as soon as it is long enough, not only when the finger is lifted
+    for touch in touches.iter().chain(touches.iter_just_released()) {
+        if gesture.swiped.contains(&touch.id()) {
+            continue;
+        }
+        if let Some(direction) = swipe_direction(touch) {
+            input.send(GameInput::Turn { player: 0, direction });
+            gesture.swiped.insert(touch.id());
+        }
+    }
+    // a cancelled touch is not a tap
+    for touch in touches.iter_just_cancelled() {
+        gesture.swiped.insert(touch.id());
+    }
+
+    let ended = touches.iter_just_released().chain(touches.iter_just_cancelled()).next().is_some();
+    if ended && touches.iter().next().is_none() {
+        if gesture.swiped.is_empty() {
+            if gesture.fingers > 1 {
+                controls.dpad = !controls.dpad;
+            }
+            else {
+                input.send(GameInput::Confirm);
+            }
+        }
+        *gesture = Gesture::default();
+    }
+}
+
+// the direction of a swipe snaps to the axis it moved the most along,
+// the y axis of the screen points down
+fn swipe_direction(touch: &Touch) -> Option<Direction> {
+    let distance = touch.distance();
+    if distance.length() < SWIPE_MIN_DISTANCE {
+        None
+    }
+    else if distance.x.abs() > distance.y.abs() {
+        Some(if distance.x > 0.0 { Direction::Right } else { Direction::Left })
+    }
+    else {
+        Some(if distance.y > 0.0 { Direction::Down } else { Direction::Up })
+    }
+}
+
+fn dpad_input_system(mut query: Query<(&Interaction, &DpadButton, &mut BackgroundColor), Changed<Interaction>>,
+                     mut input: EventWriter<GameInput>) {
+    for (interaction, button, mut color) in &mut query {
+        if *interaction == Interaction::Clicked {
+            input.send(GameInput::Turn { player: 0, direction: button.0 });
+            *color = DPAD_PRESSED_COLOR.into();
+        }
+        else {
+            *color = DPAD_COLOR.into();
+        }
+    }
+}
+
+// the D-pad is there while a snake is steered and it is turned on
+fn dpad_system(mut commands: Commands,
+               asset_server: Res<AssetServer>,
+               state: Res<State<AppState>>,
+               controls: Res<TouchControls>,
+               query: Query<Entity, With<Dpad>>) {
+    let shown = controls.dpad && matches!(state.0, AppState::Gameplay | AppState::Online);
+    if !shown {
+        for entity in query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !query.is_empty() {
+        return;
+    }
+
+    let font = asset_server.load("FiraSans-Bold.ttf");
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(20.0),
+                right: Val::Px(20.0),
+                ..default()
+            },
+            size: Size::new(Val::Px(3.0 * DPAD_BUTTON_SIZE), Val::Px(3.0 * DPAD_BUTTON_SIZE)),
+            ..default()
+        },
+        ..default()
+    }).insert(Dpad).with_children(|parent| {
+        for (direction, arrow, row, column) in DPAD_BUTTONS {
+            parent.spawn(ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(row * DPAD_BUTTON_SIZE),
+                        left: Val::Px(column * DPAD_BUTTON_SIZE),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(DPAD_BUTTON_SIZE), Val::Px(DPAD_BUTTON_SIZE)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: DPAD_COLOR.into(),
+                ..default()
+            }).insert(DpadButton(direction)).with_children(|button| {
+                button.spawn(TextBundle::from_section(
+                    arrow,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 40.0,
+                        color: Color::GRAY,
+                    }
+                ));
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::input::touch::{TouchInput, TouchPhase};
+
+    use crate::input::GameInputPlugin;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(bevy::input::InputPlugin)
+            .add_state::<AppState>()
+            .add_plugin(GameInputPlugin)
+            .add_plugin(TouchPlugin);
+        app.update();
+        app
+    }
+
+    fn touch(id: u64, phase: TouchPhase, x: f32, y: f32) -> TouchInput {
+        TouchInput { phase, position: Vec2::new(x, y), force: None, id }
+    }
+
+    // the game input of a frame with these touches
+    fn frame(app: &mut App, touches: impl IntoIterator<Item = TouchInput>) -> Vec<GameInput> {
+        for touch in touches {
+            app.world.send_event(touch);
+        }
+        app.update();
+        app.world.resource_mut::<Events<GameInput>>().drain().collect()
+    }
+
+    #[test]
+    fn swipes_turn_the_first_snake() {
+        let mut app = app();
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Started, 100.0, 100.0)]), []);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Moved, 105.0, 110.0)]), []);
+        // the turn comes as soon as the swipe is long enough, and only once
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Moved, 100.0, 150.0)]),
+                   [GameInput::Turn { player: 0, direction: Direction::Down }]);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Moved, 100.0, 200.0)]), []);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Ended, 100.0, 200.0)]), []);
+
+        // a quick swipe is over by the next frame
+        frame(&mut app, [touch(2, TouchPhase::Started, 300.0, 100.0)]);
+        assert_eq!(frame(&mut app, [touch(2, TouchPhase::Moved, 200.0, 90.0), touch(2, TouchPhase::Ended, 200.0, 90.0)]),
+                   [GameInput::Turn { player: 0, direction: Direction::Left }]);
+    }
+
+    #[test]
+    fn a_tap_continues() {
+        let mut app = app();
+        frame(&mut app, [touch(1, TouchPhase::Started, 100.0, 100.0)]);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Ended, 102.0, 101.0)]), [GameInput::Confirm]);
+
+        // but not when it is cancelled
+        frame(&mut app, [touch(2, TouchPhase::Started, 100.0, 100.0)]);
+        assert_eq!(frame(&mut app, [touch(2, TouchPhase::Cancelled, 100.0, 100.0)]), []);
+    }
+
+    #[test]
+    fn a_press_of_the_dpad_is_not_a_tap() {
+        let mut app = app();
+        let button = app.world.spawn((DpadButton(Direction::Up), Interaction::None, BackgroundColor::default())).id();
+        app.update();
+        // as the UI marks the button the touch started on
+        app.world.entity_mut(button).insert(Interaction::Clicked);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Started, 100.0, 100.0)]),
+                   [GameInput::Turn { player: 0, direction: Direction::Up }]);
+        app.world.entity_mut(button).insert(Interaction::None);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Ended, 100.0, 100.0)]), []);
+    }
+
+    #[test]
+    fn a_two_finger_tap_toggles_the_dpad() {
+        let mut app = app();
+        frame(&mut app, [touch(1, TouchPhase::Started, 100.0, 100.0), touch(2, TouchPhase::Started, 200.0, 100.0)]);
+        assert_eq!(frame(&mut app, [touch(1, TouchPhase::Ended, 100.0, 100.0)]), []);
+        assert_eq!(frame(&mut app, [touch(2, TouchPhase::Ended, 200.0, 100.0)]), []);
+        assert!(app.world.resource::<TouchControls>().dpad);
+    }
+}
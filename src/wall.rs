@@ -1,6 +1,6 @@
 use bevy::prelude::Component;
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct Wall {}
 
 impl Wall {
@@ -0,0 +1,43 @@
+// the JavaScript API of the wasm build, run under Node with
+// `wasm-pack test --node` or `cargo test --target wasm32-unknown-unknown`
+// and wasm-bindgen-test-runner
+#![cfg(target_arch = "wasm32")]
+
+use snake::embed::{self, EmbedCommand, EmbedConfig};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+#[wasm_bindgen_test]
+fn controls_are_queued_for_the_game() {
+    embed::take_commands();
+    embed::start();
+    embed::pause();
+    embed::resume();
+    embed::restart();
+    assert_eq!(embed::take_commands(),
+               vec![EmbedCommand::Start, EmbedCommand::Pause, EmbedCommand::Resume, EmbedCommand::Restart]);
+}
+
+#[wasm_bindgen_test]
+fn config_is_read_from_an_object() {
+    embed::take_commands();
+    let config = js_sys::JSON::parse(r#"{ "seed": 42, "players": 2 }"#).unwrap();
+    embed::set_config(config).unwrap();
+    let expected = EmbedConfig { seed: Some(42), players: Some(2), rules: None };
+    assert_eq!(embed::take_commands(), vec![EmbedCommand::Configure(expected)]);
+}
+
+#[wasm_bindgen_test]
+fn bad_config_is_refused() {
+    embed::take_commands();
+    for config in [r#"{ "players": 5 }"#, r#"{ "speed": 2 }"#, r#"{ "seed": "abc" }"#] {
+        assert!(embed::set_config(js_sys::JSON::parse(config).unwrap()).is_err());
+    }
+    assert!(embed::set_config(JsValue::from_str("not an object")).is_err());
+    assert!(embed::take_commands().is_empty());
+}
+
+#[wasm_bindgen_test]
+fn score_is_zero_before_a_game() {
+    assert_eq!(embed::get_score(), 0);
+}
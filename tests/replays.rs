@@ -0,0 +1,48 @@
+// the golden replays: games recorded by the greedy bot on the built-in levels,
+// played again to check that changes to the rules code do not change how they end
+//
+// expected.txt has the way each game ended, next to the result the replay file itself
+// recorded, so that a replay made again after a rules change shows up in the diff
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::path::{Path, PathBuf};
+
+use snake::replay::{Replay, ReplayResult};
+
+fn directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/replays")
+}
+
+fn golden_replays() -> Vec<(String, String)> {
+    let expected = std::fs::read_to_string(directory().join("expected.txt")).expect("expected.txt is readable");
+    expected.lines()
+        .map(|line| {
+            let (file, result) = line.split_once(": ").expect("a line is <file>: <result>");
+            (file.to_string(), result.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn golden_replays_end_as_expected() {
+    let replays = golden_replays();
+    assert!(!replays.is_empty());
+    for (file, expected) in replays {
+        let data = std::fs::read(directory().join(&file)).unwrap_or_else(|error| panic!("{}: {}", file, error));
+        let replay = Replay::decode(&data).unwrap_or_else(|error| panic!("{}: {}", file, error));
+        assert_eq!(replay.result.to_string(), expected, "{} recorded another result", file);
+        assert_eq!(ReplayResult::of(&replay.simulate()).to_string(), expected, "{} played differently", file);
+    }
+}
+
+#[test]
+fn every_golden_replay_is_expected() {
+    let expected: Vec<String> = golden_replays().into_iter().map(|(file, _)| file).collect();
+    for entry in std::fs::read_dir(directory()).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "snkr") {
+            let file = path.file_name().unwrap().to_string_lossy().to_string();
+            assert!(expected.contains(&file), "{} is not in expected.txt", file);
+        }
+    }
+}